@@ -32,6 +32,19 @@ signupLoginLink.addEventListener("click", (e) => {
 
 // -----------------------------------------------------
 
+// Set when an app signing in through /authorize sent the user here
+const returnTo = new URLSearchParams(window.location.search).get("return_to");
+
+// Send the user back to the app that asked them to sign in, if any. Only
+// /authorize is accepted so the parameter cannot redirect anywhere else.
+function returnToApp() {
+    if (returnTo !== null && returnTo.startsWith("/authorize?")) {
+        window.location.assign(returnTo);
+        return true;
+    }
+    return false;
+}
+
 const loginForm = document.getElementById("login-form");
 const loginButton = document.getElementById("login-form-submit");
 const loginErrAlter = document.getElementById("login-err-alert");
@@ -66,6 +79,9 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
+            if (returnToApp()) {
+                return;
+            }
             alert("You have successfully logged in.");
         } else {
             response.json().then(data => {
@@ -81,6 +97,30 @@ loginButton.addEventListener("click", (e) => {
     });
 });
 
+// Only the email field is needed, and the link in the email finishes the sign in
+const magicLinkButton = document.getElementById("magic-link-submit");
+
+magicLinkButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value;
+
+    fetch('/login/magic-link', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        if (response.ok) {
+            loginErrAlter.style.display = "none";
+            response.json().then(data => alert(data.message));
+        } else {
+            showError(loginErrAlter, response);
+        }
+    });
+});
+
 const signupForm = document.getElementById("signup-form");
 const signupButton = document.getElementById("signup-form-submit");
 const signupErrAlter = document.getElementById("signup-err-alert");
@@ -145,6 +185,9 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
+            if (returnToApp()) {
+                return;
+            }
             alert("You have successfully logged in.");
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
@@ -161,4 +204,147 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+// Email a fresh code for the same login attempt in case the first one never arrived
+const TwoFAResendButton = document.getElementById("2fa-resend");
+
+TwoFAResendButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = TwoFAForm.email.value;
+    const loginAttemptId = TwoFAForm.login_attempt_id.value;
+
+    fetch('/resend-2fa', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email, loginAttemptId }),
+    }).then(response => {
+        if (response.ok) {
+            TwoFAErrAlter.style.display = "none";
+            alert("A new code has been sent to your email.");
+        } else {
+            showError(TwoFAErrAlter, response);
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+// Offer a link for each identity provider users can sign in with
+const oidcProviders = document.getElementById("oidc-providers");
+
+fetch('/oidc/providers').then(response => response.json()).then(data => {
+    for (const provider of data.providers) {
+        const params = new URLSearchParams();
+        if (returnTo !== null) {
+            params.set("return_to", returnTo);
+        }
+
+        const link = document.createElement("a");
+        link.className = "btn btn-outline-dark d-block w-100 mb-2";
+        link.href = `/oidc/${encodeURIComponent(provider)}/login?${params}`;
+        link.textContent = `Sign in with ${provider}`;
+        oidcProviders.appendChild(link);
+    }
+});
+
+// Passkeys need a browser that can read and write WebAuthn options as JSON
+const passkeysSupported = window.PublicKeyCredential !== undefined
+    && PublicKeyCredential.parseRequestOptionsFromJSON !== undefined;
+
+// Ask the authenticator to answer a challenge from /passkeys/login/start
+function getPasskeyAssertion(body) {
+    return fetch('/passkeys/login/start', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(body),
+    })
+        .then(response => response.json())
+        .then(options => navigator.credentials.get({
+            publicKey: PublicKeyCredential.parseRequestOptionsFromJSON(options),
+        }))
+        .then(credential => credential.toJSON());
+}
+
+function showError(alert, response) {
+    response.json().then(data => {
+        alert.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+        alert.style.display = "block";
+    });
+}
+
+const passkeyLoginButton = document.getElementById("passkey-login");
+const passkey2FAButton = document.getElementById("2fa-passkey-submit");
+
+if (passkeysSupported) {
+    passkeyLoginButton.style.display = "block";
+    passkey2FAButton.style.display = "block";
+}
+
+passkeyLoginButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    getPasskeyAssertion({}).then(credential => fetch('/passkeys/login/finish', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(credential),
+    })).then(response => {
+        if (response.ok) {
+            loginErrAlter.style.display = "none";
+            if (returnToApp()) {
+                return;
+            }
+            alert("You have successfully logged in.");
+        } else {
+            showError(loginErrAlter, response);
+        }
+    });
+});
+
+passkey2FAButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = TwoFAForm.email.value;
+    const loginAttemptId = TwoFAForm.login_attempt_id.value;
+
+    getPasskeyAssertion({ email, loginAttemptId }).then(passkey => fetch('/verify-2fa', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email, loginAttemptId, passkey }),
+    })).then(response => {
+        if (response.ok) {
+            TwoFAErrAlter.style.display = "none";
+            if (returnToApp()) {
+                return;
+            }
+            alert("You have successfully logged in.");
+            loginSection.style.display = "block";
+            twoFASection.style.display = "none";
+            signupSection.style.display = "none";
+        } else {
+            showError(TwoFAErrAlter, response);
+        }
+    });
+});
+
+// Users with 2FA who signed in through an identity provider are sent back here
+// to enter their code
+const pendingLogin = new URLSearchParams(window.location.search);
+
+if (pendingLogin.has("login_attempt_id")) {
+    TwoFAForm.email.value = pendingLogin.get("email");
+    TwoFAForm.login_attempt_id.value = pendingLogin.get("login_attempt_id");
+
+    loginSection.style.display = "none";
+    twoFASection.style.display = "block";
+    signupSection.style.display = "none";
+}
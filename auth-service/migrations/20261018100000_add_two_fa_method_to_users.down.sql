@@ -0,0 +1,7 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS requires_2fa BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users SET requires_2fa = TRUE WHERE two_fa_method <> 'disabled';
+
+ALTER TABLE users
+    DROP COLUMN IF EXISTS two_fa_method,
+    DROP COLUMN IF EXISTS totp_secret;
@@ -0,0 +1,9 @@
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'disabled'
+        CHECK (two_fa_method IN ('disabled', 'email', 'totp')),
+    -- AES-256-GCM encrypted, base64 encoded TOTP shared secret
+    ADD COLUMN IF NOT EXISTS totp_secret TEXT;
+
+UPDATE users SET two_fa_method = 'email' WHERE requires_2fa;
+
+ALTER TABLE users DROP COLUMN IF EXISTS requires_2fa;
@@ -0,0 +1,2 @@
+-- Tokens issued before this Unix timestamp are no longer accepted for the user
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tokens_revoked_before BIGINT;
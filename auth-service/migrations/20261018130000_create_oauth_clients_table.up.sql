@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS oauth_clients(
+   client_id TEXT PRIMARY KEY NOT NULL,
+   secret_hash TEXT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
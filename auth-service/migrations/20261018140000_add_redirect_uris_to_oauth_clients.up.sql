@@ -0,0 +1,2 @@
+-- Exact URIs the client may have authorization codes sent to
+ALTER TABLE oauth_clients ADD COLUMN IF NOT EXISTS redirect_uris TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,8 @@
+UPDATE users SET two_fa_method = 'email' WHERE two_fa_method = 'passkey';
+
+ALTER TABLE users
+    DROP CONSTRAINT IF EXISTS users_two_fa_method_check,
+    ADD CONSTRAINT users_two_fa_method_check
+        CHECK (two_fa_method IN ('disabled', 'email', 'totp'));
+
+DROP TABLE IF EXISTS passkeys;
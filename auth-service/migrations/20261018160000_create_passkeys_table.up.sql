@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS passkeys(
+   -- base64url credential id chosen by the authenticator
+   credential_id TEXT PRIMARY KEY NOT NULL,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   -- uncompressed SEC1 P-256 public key
+   public_key BYTEA NOT NULL,
+   sign_count BIGINT NOT NULL DEFAULT 0,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS passkeys_email_idx ON passkeys(email);
+
+ALTER TABLE users
+    DROP CONSTRAINT IF EXISTS users_two_fa_method_check,
+    ADD CONSTRAINT users_two_fa_method_check
+        CHECK (two_fa_method IN ('disabled', 'email', 'passkey', 'totp'));
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS email_outbox(
+   id TEXT PRIMARY KEY NOT NULL,
+   -- Keeps emails queued within the same second in the order they were queued
+   position BIGSERIAL NOT NULL,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   text_body TEXT NOT NULL,
+   -- Only templated emails have an HTML alternative
+   html_body TEXT,
+   attempts INTEGER NOT NULL DEFAULT 0,
+   -- Unix timestamp the worker may next try to deliver the email at
+   next_attempt_at BIGINT NOT NULL,
+   last_error TEXT,
+   created_at BIGINT NOT NULL,
+   -- Set once delivery has been given up on
+   failed_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx
+    ON email_outbox(next_attempt_at, position) WHERE failed_at IS NULL;
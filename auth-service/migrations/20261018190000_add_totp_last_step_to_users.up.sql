@@ -0,0 +1,2 @@
+-- Time step of the last TOTP code accepted for the user, so that codes cannot be replayed
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
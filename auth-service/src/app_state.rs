@@ -1,32 +1,93 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::{
+    domain::{
+        AuthorizationCodeStore, BannedTokenStore, ClientStore, EmailClient, EmailOutbox,
+        EmailVerificationTokenStore, FailedLoginStore, MagicLinkStore, OidcLoginStore,
+        PasskeyChallengeStore, PasswordResetTokenStore, RateLimitStore, RefreshTokenStore,
+        SessionStore, TwoFACodeStore, UserStore,
+    },
+    services::{OidcProviders, RateLimiter},
+};
 
 // Using a type alias to improve readability!
+pub type AuthorizationCodeStoreType = Arc<RwLock<dyn AuthorizationCodeStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
+pub type ClientStoreType = Arc<RwLock<dyn ClientStore + Send + Sync>>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type EmailOutboxType = Arc<RwLock<dyn EmailOutbox + Send + Sync>>;
+pub type EmailVerificationTokenStoreType =
+    Arc<RwLock<dyn EmailVerificationTokenStore + Send + Sync>>;
+pub type FailedLoginStoreType = Arc<RwLock<dyn FailedLoginStore + Send + Sync>>;
+pub type MagicLinkStoreType = Arc<RwLock<dyn MagicLinkStore + Send + Sync>>;
+pub type OidcLoginStoreType = Arc<RwLock<dyn OidcLoginStore + Send + Sync>>;
+pub type OidcProvidersType = Arc<OidcProviders>;
+pub type PasskeyChallengeStoreType = Arc<RwLock<dyn PasskeyChallengeStore + Send + Sync>>;
+pub type PasswordResetTokenStoreType = Arc<RwLock<dyn PasswordResetTokenStore + Send + Sync>>;
+pub type RateLimitStoreType = Arc<RwLock<dyn RateLimitStore + Send + Sync>>;
+pub type RateLimiterType = Arc<RateLimiter>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 
 #[derive(Clone)]
 pub struct AppState {
+    pub authorization_code_store: AuthorizationCodeStoreType,
     pub banned_token_store: BannedTokenStoreType,
-    pub email_client: EmailClientType,
+    pub client_store: ClientStoreType,
+    // Emails are queued here and sent by the outbox worker
+    pub email_outbox: EmailOutboxType,
+    pub email_verification_token_store: EmailVerificationTokenStoreType,
+    pub failed_login_store: FailedLoginStoreType,
+    pub magic_link_store: MagicLinkStoreType,
+    pub oidc_login_store: OidcLoginStoreType,
+    pub oidc_providers: OidcProvidersType,
+    pub passkey_challenge_store: PasskeyChallengeStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub rate_limiter: RateLimiterType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
     pub user_store: UserStoreType,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        authorization_code_store: AuthorizationCodeStoreType,
         banned_token_store: BannedTokenStoreType,
-        email_client: EmailClientType,
+        client_store: ClientStoreType,
+        email_outbox: EmailOutboxType,
+        email_verification_token_store: EmailVerificationTokenStoreType,
+        failed_login_store: FailedLoginStoreType,
+        magic_link_store: MagicLinkStoreType,
+        oidc_login_store: OidcLoginStoreType,
+        oidc_providers: OidcProvidersType,
+        passkey_challenge_store: PasskeyChallengeStoreType,
+        password_reset_token_store: PasswordResetTokenStoreType,
+        rate_limiter: RateLimiterType,
+        refresh_token_store: RefreshTokenStoreType,
+        session_store: SessionStoreType,
         two_fa_code_store: TwoFACodeStoreType,
         user_store: UserStoreType,
     ) -> Self {
         Self {
+            authorization_code_store,
             banned_token_store,
-            email_client,
+            client_store,
+            email_outbox,
+            email_verification_token_store,
+            failed_login_store,
+            magic_link_store,
+            oidc_login_store,
+            oidc_providers,
+            passkey_challenge_store,
+            password_reset_token_store,
+            rate_limiter,
+            refresh_token_store,
+            session_store,
             two_fa_code_store,
             user_store,
         }
@@ -1,13 +1,21 @@
+mod client;
 pub mod data_stores;
 pub mod email;
 pub mod email_client;
 mod error;
+mod passkey;
 mod password;
+mod recovery_code;
+mod totp;
 mod user;
 
+pub use client::*;
 pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use error::*;
+pub use passkey::*;
 pub use password::*;
+pub use recovery_code::*;
+pub use totp::*;
 pub use user::*;
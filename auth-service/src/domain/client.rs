@@ -0,0 +1,206 @@
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+
+const CLIENT_ID_MAX_LENGTH: usize = 64;
+const REDIRECT_URI_MAX_LENGTH: usize = 2048;
+const SCOPE_MAX_LENGTH: usize = 64;
+// Secrets are generated rather than chosen, so they can be hashed without a slow KDF
+const CLIENT_SECRET_MIN_LENGTH: usize = 32;
+const CLIENT_SECRET_LENGTH: usize = 48;
+
+// A registered OAuth client. Its secret is held by the ClientStore and never read back.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Client {
+    pub id: ClientId,
+    // Authorization codes are only ever sent to one of these exact URIs
+    pub redirect_uris: Vec<RedirectUri>,
+    // Scopes the client may request for its own tokens with the client_credentials grant
+    pub scopes: Vec<String>,
+    // Disabled clients cannot authenticate, and tokens issued to them stop validating
+    pub disabled: bool,
+}
+
+impl Client {
+    pub fn new(id: ClientId, redirect_uris: Vec<RedirectUri>, scopes: Vec<String>) -> Self {
+        Self {
+            id,
+            redirect_uris,
+            scopes,
+            disabled: false,
+        }
+    }
+
+    pub fn redirect_uri(&self, uri: &str) -> Option<&RedirectUri> {
+        self.redirect_uris
+            .iter()
+            .find(|redirect_uri| redirect_uri.as_ref() == uri)
+    }
+}
+
+// A scope token as defined by RFC 6749 section 3.3, such as "reports:read"
+pub fn is_valid_scope(scope: &str) -> bool {
+    !scope.is_empty()
+        && scope.len() <= SCOPE_MAX_LENGTH
+        && scope
+            .bytes()
+            .all(|c| c.is_ascii_graphic() && c != b'"' && c != b'\\')
+}
+
+// Identifies an OAuth client, such as a resource server or a backend job
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct ClientId(String);
+
+impl ClientId {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        let valid = !id.is_empty()
+            && id.len() <= CLIENT_ID_MAX_LENGTH
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+        match valid {
+            true => Ok(ClientId(id.to_owned())),
+            false => Err("Invalid client id".to_string()),
+        }
+    }
+}
+
+impl AsRef<str> for ClientId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RedirectUri(String);
+
+impl RedirectUri {
+    pub fn parse(uri: &str) -> Result<Self, String> {
+        let host = uri
+            .strip_prefix("https://")
+            .or_else(|| uri.strip_prefix("http://"))
+            .and_then(|rest| rest.split(['/', '?']).next())
+            .unwrap_or_default();
+        // Fragments are not allowed, since the code is appended to the query
+        let valid = !host.is_empty()
+            && uri.len() <= REDIRECT_URI_MAX_LENGTH
+            && !uri.contains('#')
+            && !uri.chars().any(|c| c.is_whitespace() || c.is_control());
+
+        match valid {
+            true => Ok(RedirectUri(uri.to_owned())),
+            false => Err("Invalid redirect URI".to_string()),
+        }
+    }
+
+    // Build the URI to send the user back to, keeping any query it already has
+    pub fn with_query(&self, query: &str) -> String {
+        let separator = match self.0.contains('?') {
+            true => '&',
+            false => '?',
+        };
+        format!("{}{}{}", self.0, separator, query)
+    }
+}
+
+impl AsRef<str> for RedirectUri {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ClientSecret(String);
+
+impl ClientSecret {
+    pub fn parse(secret: &str) -> Result<Self, String> {
+        match secret.len() >= CLIENT_SECRET_MIN_LENGTH {
+            true => Ok(ClientSecret(secret.to_owned())),
+            false => Err(format!(
+                "Client secrets must be at least {CLIENT_SECRET_MIN_LENGTH} characters"
+            )),
+        }
+    }
+}
+
+impl Default for ClientSecret {
+    fn default() -> Self {
+        let secret = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(CLIENT_SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+        ClientSecret(secret)
+    }
+}
+
+impl AsRef<str> for ClientSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid_client_id() {
+        assert!(ClientId::parse("resource-server.v2_1").is_ok());
+    }
+
+    #[test]
+    fn parse_invalid_client_id_returns_err() {
+        for id in ["", "has space", "colon:id", &"a".repeat(65)] {
+            assert!(ClientId::parse(id).is_err(), "{id} should be invalid");
+        }
+    }
+
+    #[test]
+    fn parse_invalid_redirect_uri_returns_err() {
+        for uri in [
+            "",
+            "/callback",
+            "ftp://example.com/callback",
+            "https://",
+            "https://example.com/callback#fragment",
+            "https://example.com/call back",
+        ] {
+            assert!(RedirectUri::parse(uri).is_err(), "{uri} should be invalid");
+        }
+    }
+
+    #[test]
+    fn redirect_uri_keeps_existing_query() {
+        let uri = RedirectUri::parse("https://example.com/callback").unwrap();
+        assert_eq!(
+            uri.with_query("code=abc"),
+            "https://example.com/callback?code=abc"
+        );
+
+        let uri = RedirectUri::parse("https://example.com/callback?app=web").unwrap();
+        assert_eq!(
+            uri.with_query("code=abc"),
+            "https://example.com/callback?app=web&code=abc"
+        );
+    }
+
+    #[test]
+    fn scope_validation() {
+        assert!(is_valid_scope("reports:read"));
+        for scope in ["", "two words", "quote\"", "back\\slash"] {
+            assert!(!is_valid_scope(scope), "{scope} should be invalid");
+        }
+    }
+
+    #[test]
+    fn short_client_secret_returns_err() {
+        assert!(ClientSecret::parse("too-short").is_err());
+    }
+
+    #[test]
+    fn generated_client_secret_parses() {
+        let secret = ClientSecret::default();
+        assert_eq!(ClientSecret::parse(secret.as_ref()), Ok(secret));
+    }
+}
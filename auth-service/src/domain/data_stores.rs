@@ -1,32 +1,169 @@
-use std::fmt::{self, Display, Formatter};
+use std::{
+    fmt::{self, Display, Formatter},
+    net::IpAddr,
+};
 
-use rand::Rng;
-use serde::Serialize;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use chrono::Utc;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
-use super::{Email, Password, User};
+use super::{
+    Client, ClientId, ClientSecret, Email, EmailMessage, Passkey, Password, RecoveryCode,
+    RedirectUri, TotpSecret, TwoFAMethod, User,
+};
 
 #[derive(Debug, PartialEq, Serialize)]
 pub enum UserStoreError {
     InvalidCredentials,
     InvalidEmail,
+    InvalidRecoveryCode,
+    PasskeyAlreadyExists,
+    PasskeyNotFound,
+    TotpCodeAlreadyUsed,
+    TotpSecretNotFound,
     UnexpectedError,
     UserAlreadyExists,
     UserNotFound,
 }
 
+// Changes the user is told about take the email to send them as `notification`. It is
+// queued in the email outbox together with the change, so it goes out if and only if
+// the change is saved.
 #[async_trait::async_trait]
 pub trait UserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    async fn add_user(
+        &mut self,
+        user: User,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
         -> Result<(), UserStoreError>;
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    async fn set_email_verified(
+        &mut self,
+        email: &Email,
+        email_verified: bool,
+    ) -> Result<(), UserStoreError>;
+    async fn set_password_login_disabled(
+        &mut self,
+        email: &Email,
+        password_login_disabled: bool,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError>;
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError>;
+    async fn set_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError>;
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError>;
+    // Records the time step of a TOTP code the user presented, refusing steps at or before
+    // the last recorded one so that every code is only accepted once
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<(), UserStoreError>;
+    // Replaces any existing recovery codes for the user
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), UserStoreError>;
+    // Removes the code so that it cannot be used again
+    async fn use_recovery_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), UserStoreError>;
+    // Durable record of when all of the user's tokens were last revoked
+    async fn set_tokens_revoked_before(
+        &mut self,
+        email: &Email,
+        issued_before: usize,
+    ) -> Result<(), UserStoreError>;
+    async fn get_tokens_revoked_before(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, UserStoreError>;
+    async fn add_passkey(
+        &mut self,
+        email: &Email,
+        passkey: Passkey,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError>;
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, UserStoreError>;
+    // Finds a passkey and its owner from the credential id an authenticator returned
+    async fn get_passkey(&self, credential_id: &str) -> Result<(Email, Passkey), UserStoreError>;
+    async fn set_passkey_sign_count(
+        &mut self,
+        credential_id: &str,
+        sign_count: u32,
+    ) -> Result<(), UserStoreError>;
+    async fn remove_passkeys(&mut self, email: &Email) -> Result<(), UserStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait ClientStore {
+    // Registers the client, or replaces the secret and redirect URIs of an existing
+    // one while leaving its scopes and disabled state alone
+    async fn set_client(
+        &mut self,
+        client: Client,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError>;
+    async fn add_client(
+        &mut self,
+        client: Client,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError>;
+    async fn get_client(&self, client_id: &ClientId) -> Result<Client, ClientStoreError>;
+    async fn rotate_secret(
+        &mut self,
+        client_id: &ClientId,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError>;
+    async fn disable_client(&mut self, client_id: &ClientId) -> Result<(), ClientStoreError>;
+    async fn authenticate_client(
+        &self,
+        client_id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<(), ClientStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientStoreError {
+    ClientAlreadyExists,
+    ClientDisabled,
+    ClientNotFound,
+    InvalidCredentials,
+    UnexpectedError,
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
     async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
     async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    // Bans every token for the user that was issued before the given timestamp
+    async fn ban_user_tokens(
+        &mut self,
+        email: &Email,
+        issued_before: usize,
+    ) -> Result<(), BannedTokenStoreError>;
+    async fn user_tokens_banned_before(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, BannedTokenStoreError>;
 }
 
 #[derive(Debug)]
@@ -34,33 +171,73 @@ pub enum BannedTokenStoreError {
     UnexpectedError,
 }
 
+// Login attempts waiting for their second factor. A user may have a few pending at
+// once, such as when signing in on two devices, and starting more drops the oldest.
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
     async fn add_code(
         &mut self,
-        email: Email,
         login_attempt_id: LoginAttemptId,
-        code: TwoFACode,
+        challenge: TwoFAChallenge,
+        started_at: usize,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAChallenge, TwoFACodeStoreError>;
+    // The user's pending login attempts, oldest first
+    async fn get_pending(
         &self,
         email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    ) -> Result<Vec<(LoginAttemptId, TwoFAChallenge)>, TwoFACodeStoreError>;
+    // Count a wrong guess against the login attempt, returning how many there have been
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError>;
+    // Swap the login attempt's code for a new one
+    async fn resend_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        sent_at: usize,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn get_resends(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFACodeResends, TwoFACodeStoreError>;
+    // Codes emailed to confirm a settings change are kept apart from login attempts,
+    // one per user, each replacing the one sent before it
+    async fn add_reauth_code(
+        &mut self,
+        challenge: TwoFAChallenge,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn get_reauth_code(&self, email: &Email) -> Result<TwoFAChallenge, TwoFACodeStoreError>;
+    // Count a wrong guess against the user's code, returning how many there have been
+    async fn record_failed_reauth_attempt(
+        &mut self,
+        email: &Email,
+    ) -> Result<u32, TwoFACodeStoreError>;
+    async fn remove_reauth_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
 pub enum TwoFACodeStoreError {
     LoginAttemptIdNotFound,
+    ReauthCodeNotFound,
     UnexpectedError,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub struct LoginAttemptId(String);
 
 impl LoginAttemptId {
     pub fn parse(id: &str) -> Result<Self, String> {
-        match Uuid::parse_str(&id) {
+        match Uuid::parse_str(id) {
             Ok(_) => Ok(LoginAttemptId(id.to_string())),
             Err(_) => Err("Invalid UUID".to_string()),
         }
@@ -109,3 +286,1019 @@ impl AsRef<str> for TwoFACode {
         &self.0
     }
 }
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TwoFAChallenge {
+    pub email: Email,
+    pub code: TwoFACode,
+    // Only the client holding this in its login attempt cookie may answer the challenge
+    pub client_binding: LoginAttemptBinding,
+}
+
+// Random value kept in a cookie by the client that started a login, tying its login
+// attempts to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginAttemptBinding(String);
+
+impl LoginAttemptBinding {
+    pub fn parse(binding: &str) -> Result<Self, String> {
+        match is_random_token(binding) {
+            true => Ok(LoginAttemptBinding(binding.to_string())),
+            false => Err("Invalid login attempt binding".to_string()),
+        }
+    }
+}
+
+impl Default for LoginAttemptBinding {
+    fn default() -> Self {
+        LoginAttemptBinding(generate_random_token())
+    }
+}
+
+impl AsRef<str> for LoginAttemptBinding {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Codes sent again for a login attempt after the first one
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TwoFACodeResends {
+    pub count: u32,
+    // Unix timestamp of the latest resend
+    pub last_sent_at: usize,
+}
+
+impl TwoFACodeResends {
+    // When another code may be sent. The first resend is allowed straight away in case
+    // the original email never arrived.
+    pub fn next_allowed_at(&self, cooldown_seconds: u64) -> usize {
+        match self.count {
+            0 => 0,
+            _ => self.last_sent_at + cooldown_seconds as usize,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError>;
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<PasswordResetToken, PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordResetTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PasswordResetToken(String);
+
+impl PasswordResetToken {
+    pub fn parse(token: &str) -> Result<Self, String> {
+        match is_random_token(token) {
+            true => Ok(PasswordResetToken(token.to_string())),
+            false => Err("Invalid password reset token".to_string()),
+        }
+    }
+
+    // Compare in constant time so response timing doesn't reveal how much of a guess was right
+    pub fn matches(&self, other: &PasswordResetToken) -> bool {
+        self.0.as_bytes().ct_eq(other.0.as_bytes()).into()
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        PasswordResetToken(generate_random_token())
+    }
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+pub trait EmailVerificationTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError>;
+    async fn remove_token(&mut self, email: &Email)
+        -> Result<(), EmailVerificationTokenStoreError>;
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<EmailVerificationToken, EmailVerificationTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailVerificationTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EmailVerificationToken(String);
+
+impl EmailVerificationToken {
+    pub fn parse(token: &str) -> Result<Self, String> {
+        match is_random_token(token) {
+            true => Ok(EmailVerificationToken(token.to_string())),
+            false => Err("Invalid email verification token".to_string()),
+        }
+    }
+
+    // Compare in constant time so response timing doesn't reveal how much of a guess was right
+    pub fn matches(&self, other: &EmailVerificationToken) -> bool {
+        self.0.as_bytes().ct_eq(other.0.as_bytes()).into()
+    }
+}
+
+impl Default for EmailVerificationToken {
+    fn default() -> Self {
+        EmailVerificationToken(generate_random_token())
+    }
+}
+
+impl AsRef<str> for EmailVerificationToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    // Store a token as the current member of its family
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        family: RefreshTokenFamily,
+    ) -> Result<(), RefreshTokenStoreError>;
+    // Exchange a token for a new one in the same family. Presenting a token
+    // that has already been rotated revokes the whole family.
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<RefreshTokenFamily, RefreshTokenStoreError>;
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshTokenStoreError {
+    FamilyRevoked,
+    TokenNotFound,
+    TokenReused,
+    UnexpectedError,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    pub fn parse(token: &str) -> Result<Self, String> {
+        match is_random_token(token) {
+            true => Ok(RefreshToken(token.to_string())),
+            false => Err("Invalid refresh token".to_string()),
+        }
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        RefreshToken(generate_random_token())
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// The chain of refresh tokens descended from a single login
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshTokenFamily {
+    pub id: Uuid,
+    pub email: Email,
+    pub session_id: SessionId,
+    // Unix timestamp of the login that started the family
+    pub issued_at: usize,
+}
+
+impl RefreshTokenFamily {
+    pub fn new(email: Email, session_id: SessionId) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            email,
+            session_id,
+            issued_at: unix_timestamp(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError>;
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    // Record activity on the session and return it
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        last_seen: usize,
+    ) -> Result<Session, SessionStoreError>;
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        match Uuid::parse_str(id) {
+            Ok(_) => Ok(SessionId(id.to_string())),
+            Err(_) => Err("Invalid UUID".to_string()),
+        }
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        SessionId(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A signed in device, created at login and referenced by the `sid` JWT claim
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub email: Email,
+    pub created_at: usize,
+    pub last_seen: usize,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl Session {
+    pub fn new(email: Email, user_agent: Option<String>, ip_address: Option<String>) -> Self {
+        let now = unix_timestamp();
+        Self {
+            id: SessionId::default(),
+            email,
+            created_at: now,
+            last_seen: now,
+            user_agent,
+            ip_address,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+    // Codes are single use, so a code is removed as it is read
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthorizationCodeStoreError {
+    CodeNotFound,
+    UnexpectedError,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct AuthorizationCode(String);
+
+impl AuthorizationCode {
+    pub fn parse(code: &str) -> Result<Self, String> {
+        match is_random_token(code) {
+            true => Ok(AuthorizationCode(code.to_string())),
+            false => Err("Invalid authorization code".to_string()),
+        }
+    }
+}
+
+impl Default for AuthorizationCode {
+    fn default() -> Self {
+        AuthorizationCode(generate_random_token())
+    }
+}
+
+impl AsRef<str> for AuthorizationCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// What the user approved at /authorize, redeemed by the client at /token
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthorizationGrant {
+    pub client_id: ClientId,
+    pub redirect_uri: RedirectUri,
+    pub email: Email,
+    pub session_id: SessionId,
+    pub scope: String,
+    // PKCE S256 challenge, the base64url SHA-256 digest of the client's verifier
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+}
+
+impl AuthorizationGrant {
+    pub fn verify_code_verifier(&self, code_verifier: &str) -> bool {
+        let valid_verifier = (43..=128).contains(&code_verifier.len())
+            && code_verifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'));
+
+        valid_verifier && s256_challenge(code_verifier) == self.code_challenge
+    }
+}
+
+fn s256_challenge(code_verifier: &str) -> String {
+    BASE64_URL.encode(Sha256::digest(code_verifier))
+}
+
+#[async_trait::async_trait]
+pub trait OidcLoginStore {
+    async fn add_login(
+        &mut self,
+        state: OidcLoginState,
+        login: OidcLogin,
+    ) -> Result<(), OidcLoginStoreError>;
+    // Logins are single use, so a login is removed as it is read
+    async fn take_login(
+        &mut self,
+        state: &OidcLoginState,
+    ) -> Result<OidcLogin, OidcLoginStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OidcLoginStoreError {
+    LoginNotFound,
+    UnexpectedError,
+}
+
+// The state parameter sent to an upstream identity provider, which names the login
+// it belongs to and is also kept in a cookie so that only the browser which
+// started the login can finish it
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OidcLoginState(String);
+
+impl OidcLoginState {
+    pub fn parse(state: &str) -> Result<Self, String> {
+        match is_random_token(state) {
+            true => Ok(OidcLoginState(state.to_string())),
+            false => Err("Invalid login state".to_string()),
+        }
+    }
+}
+
+impl Default for OidcLoginState {
+    fn default() -> Self {
+        OidcLoginState(generate_random_token())
+    }
+}
+
+impl AsRef<str> for OidcLoginState {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A login sent to an upstream identity provider that has not come back yet
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OidcLogin {
+    pub provider: String,
+    // Must come back in the provider's ID token, tying the token to this login
+    pub nonce: String,
+    // PKCE verifier, whose S256 challenge is sent with the authorization request
+    pub code_verifier: String,
+    // Where to send the user once they are signed in
+    pub return_to: Option<String>,
+}
+
+impl OidcLogin {
+    pub fn new(provider: String, return_to: Option<String>) -> Self {
+        Self {
+            provider,
+            nonce: generate_random_token(),
+            // Two random tokens give a verifier within the 43 to 128 characters PKCE allows
+            code_verifier: format!("{}{}", generate_random_token(), generate_random_token()),
+            return_to,
+        }
+    }
+
+    pub fn code_challenge(&self) -> String {
+        s256_challenge(&self.code_verifier)
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: PasskeyChallenge,
+        ceremony: PasskeyCeremony,
+    ) -> Result<(), PasskeyChallengeStoreError>;
+    // Challenges are single use, so a challenge is removed as it is read
+    async fn take_challenge(
+        &mut self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PasskeyCeremony, PasskeyChallengeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasskeyChallengeStoreError {
+    ChallengeNotFound,
+    UnexpectedError,
+}
+
+// A random WebAuthn challenge. The token is also valid base64url, so it is sent to
+// the browser as is and comes back unchanged in the client data.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PasskeyChallenge(String);
+
+impl PasskeyChallenge {
+    pub fn parse(challenge: &str) -> Result<Self, String> {
+        match is_random_token(challenge) {
+            true => Ok(PasskeyChallenge(challenge.to_string())),
+            false => Err("Invalid passkey challenge".to_string()),
+        }
+    }
+}
+
+impl Default for PasskeyChallenge {
+    fn default() -> Self {
+        PasskeyChallenge(generate_random_token())
+    }
+}
+
+impl AsRef<str> for PasskeyChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// What a passkey challenge was issued for
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PasskeyCeremony {
+    // Adding a passkey to the signed in user's account
+    Registration {
+        email: String,
+    },
+    // Signing in with a passkey in place of a password
+    Login,
+    // Presenting a passkey as the second factor of a login attempt
+    #[serde(rename_all = "camelCase")]
+    SecondFactor {
+        email: String,
+        login_attempt_id: String,
+    },
+    // Confirming it is still the signed in user before a security setting changes
+    Reauthentication {
+        email: String,
+    },
+}
+
+#[async_trait::async_trait]
+pub trait MagicLinkStore {
+    async fn add_link(&mut self, id: MagicLinkId, email: Email) -> Result<(), MagicLinkStoreError>;
+    // Links are single use, so a link is removed as it is read
+    async fn take_link(&mut self, id: &MagicLinkId) -> Result<Email, MagicLinkStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MagicLinkStoreError {
+    LinkNotFound,
+    UnexpectedError,
+}
+
+// Names an emailed magic link, and is the jti of the signed token the link carries
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MagicLinkId(String);
+
+impl MagicLinkId {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        match is_random_token(id) {
+            true => Ok(MagicLinkId(id.to_string())),
+            false => Err("Invalid magic link id".to_string()),
+        }
+    }
+}
+
+impl Default for MagicLinkId {
+    fn default() -> Self {
+        MagicLinkId(generate_random_token())
+    }
+}
+
+impl AsRef<str> for MagicLinkId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+pub trait FailedLoginStore {
+    // Counts a failed login and returns the failures recorded so far
+    async fn record_failure(
+        &mut self,
+        key: &FailedLoginKey,
+        failed_at: usize,
+    ) -> Result<FailedLogins, FailedLoginStoreError>;
+    async fn get_failures(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<FailedLogins, FailedLoginStoreError>;
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FailedLoginStoreError {
+    UnexpectedError,
+}
+
+// Failed logins are counted for the account as a whole and for the account from
+// each address, so a single attacker is held back long before the owner is locked out
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum FailedLoginKey {
+    Account(Email),
+    AccountFromIp(Email, IpAddr),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FailedLogins {
+    pub count: u32,
+    // Unix timestamp of the latest failure
+    pub last_failed_at: usize,
+}
+
+impl FailedLogins {
+    // When logins may be tried again. Reaching the threshold locks them for the given
+    // time, and every failure after that doubles it, up to the maximum.
+    pub fn locked_until(
+        &self,
+        threshold: u32,
+        lockout_seconds: u64,
+        max_lockout_seconds: u64,
+    ) -> Option<usize> {
+        if threshold == 0 || self.count < threshold {
+            return None;
+        }
+
+        let doublings = (self.count - threshold).min(u64::BITS - 1);
+        let lockout = lockout_seconds
+            .saturating_mul(1 << doublings)
+            .min(max_lockout_seconds);
+
+        Some(self.last_failed_at + lockout as usize)
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RateLimitStore {
+    // Takes a token from the key's bucket, which starts full and refills steadily
+    // over the limit's period
+    async fn take_token(
+        &mut self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+        now_ms: u64,
+    ) -> Result<RateLimitDecision, RateLimitStoreError>;
+    // Puts back a token taken for a request that another bucket then turned away
+    async fn return_token(
+        &mut self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<(), RateLimitStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RateLimitStoreError {
+    UnexpectedError,
+}
+
+// Names the bucket requests are counted in, such as one route for one IP address
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RateLimitKey(String);
+
+impl RateLimitKey {
+    pub fn new(route: &str, kind: &str, value: &str) -> Self {
+        RateLimitKey(format!("{route}:{kind}:{value}"))
+    }
+}
+
+impl AsRef<str> for RateLimitKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Allows a burst of up to `capacity` requests, refilling at `capacity` per period
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period_seconds: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after_seconds: u64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TokenBucket {
+    pub tokens: f64,
+    pub updated_at_ms: u64,
+}
+
+impl TokenBucket {
+    pub fn full(limit: &RateLimit, now_ms: u64) -> Self {
+        Self {
+            tokens: limit.capacity as f64,
+            updated_at_ms: now_ms,
+        }
+    }
+
+    // Refill the bucket for the time since it was last updated, then take a token
+    // from it if there is one
+    pub fn take(&mut self, limit: &RateLimit, now_ms: u64) -> RateLimitDecision {
+        let capacity = limit.capacity as f64;
+        let period_ms = (limit.period_seconds.max(1) * 1_000) as f64;
+        let elapsed_ms = now_ms.saturating_sub(self.updated_at_ms) as f64;
+
+        self.tokens = (self.tokens + elapsed_ms * capacity / period_ms).min(capacity);
+        self.updated_at_ms = now_ms.max(self.updated_at_ms);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return RateLimitDecision::Allowed;
+        }
+
+        // Rounded to whole milliseconds first so floating point error cannot add a second
+        let wait_ms = ((1.0 - self.tokens) * period_ms / capacity).round() as u64;
+        RateLimitDecision::Limited {
+            retry_after_seconds: wait_ms.div_ceil(1_000).max(1),
+        }
+    }
+
+    pub fn put_back(&mut self, limit: &RateLimit) {
+        self.tokens = (self.tokens + 1.0).min(limit.capacity as f64);
+    }
+}
+
+// Emails waiting to be delivered by the outbox worker, so a change is never lost to a
+// mail provider that is down and the request that made it does not have to wait
+#[async_trait::async_trait]
+pub trait EmailOutbox {
+    async fn enqueue(
+        &mut self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<OutboxEmailId, EmailOutboxError>;
+    // Takes up to `limit` emails that are due, oldest first, counting the attempt about
+    // to be made. They are not handed out again until `lease_until`, in case the worker
+    // dies before it reports back.
+    async fn claim_due(
+        &mut self,
+        now: usize,
+        lease_until: usize,
+        limit: usize,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxError>;
+    async fn mark_sent(&mut self, id: &OutboxEmailId) -> Result<(), EmailOutboxError>;
+    async fn retry_later(
+        &mut self,
+        id: &OutboxEmailId,
+        error: &str,
+        retry_at: usize,
+    ) -> Result<(), EmailOutboxError>;
+    // Gives up on the email, keeping it for an admin to look at
+    async fn dead_letter(
+        &mut self,
+        id: &OutboxEmailId,
+        error: &str,
+        failed_at: usize,
+    ) -> Result<(), EmailOutboxError>;
+    // Emails that were given up on, oldest first
+    async fn get_failed(&self) -> Result<Vec<OutboxEmail>, EmailOutboxError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailOutboxError {
+    EmailNotFound,
+    UnexpectedError,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OutboxEmailId(String);
+
+impl OutboxEmailId {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        match Uuid::parse_str(id) {
+            Ok(_) => Ok(OutboxEmailId(id.to_string())),
+            Err(_) => Err("Invalid UUID".to_string()),
+        }
+    }
+}
+
+impl Default for OutboxEmailId {
+    fn default() -> Self {
+        OutboxEmailId(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for OutboxEmailId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxEmail {
+    pub id: OutboxEmailId,
+    pub recipient: Email,
+    pub message: EmailMessage,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: usize,
+    // Set once the email has been given up on
+    pub failed_at: Option<usize>,
+}
+
+fn unix_timestamp() -> usize {
+    Utc::now().timestamp().try_into().unwrap_or_default()
+}
+
+const RANDOM_TOKEN_LENGTH: usize = 32;
+
+fn generate_random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(RANDOM_TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+fn is_random_token(token: &str) -> bool {
+    token.len() == RANDOM_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_password_reset_token_parses() {
+        let token = PasswordResetToken::default();
+        assert_eq!(PasswordResetToken::parse(token.as_ref()), Ok(token));
+    }
+
+    #[test]
+    fn password_reset_token_matches_only_itself() {
+        let token = PasswordResetToken::default();
+        assert!(token.matches(&token.clone()));
+        assert!(!token.matches(&PasswordResetToken::default()));
+    }
+
+    #[test]
+    fn parse_short_password_reset_token_returns_err() {
+        assert!(PasswordResetToken::parse("abc123").is_err());
+    }
+
+    #[test]
+    fn parse_non_alphanumeric_password_reset_token_returns_err() {
+        assert!(PasswordResetToken::parse("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!").is_err());
+    }
+
+    #[test]
+    fn default_email_verification_token_parses() {
+        let token = EmailVerificationToken::default();
+        assert_eq!(EmailVerificationToken::parse(token.as_ref()), Ok(token));
+    }
+
+    #[test]
+    fn email_verification_token_matches_only_itself() {
+        let token = EmailVerificationToken::default();
+        assert!(token.matches(&token.clone()));
+        assert!(!token.matches(&EmailVerificationToken::default()));
+    }
+
+    #[test]
+    fn parse_short_email_verification_token_returns_err() {
+        assert!(EmailVerificationToken::parse("abc123").is_err());
+    }
+
+    #[test]
+    fn default_refresh_token_parses() {
+        let token = RefreshToken::default();
+        assert_eq!(RefreshToken::parse(token.as_ref()), Ok(token));
+    }
+
+    #[test]
+    fn parse_invalid_session_id_returns_err() {
+        assert!(SessionId::parse("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn default_authorization_code_parses() {
+        let code = AuthorizationCode::default();
+        assert_eq!(AuthorizationCode::parse(code.as_ref()), Ok(code));
+    }
+
+    #[test]
+    fn code_verifier_matches_s256_challenge() {
+        // Example from RFC 7636 appendix B
+        let grant = AuthorizationGrant {
+            client_id: ClientId::parse("client").unwrap(),
+            redirect_uri: RedirectUri::parse("https://example.com/callback").unwrap(),
+            email: Email::parse("test@example.com").unwrap(),
+            session_id: SessionId::default(),
+            scope: "openid".to_owned(),
+            code_challenge: "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_owned(),
+            nonce: None,
+        };
+
+        assert!(grant.verify_code_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"));
+        assert!(!grant.verify_code_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXj"));
+        assert!(!grant.verify_code_verifier("short"));
+    }
+
+    #[test]
+    fn default_oidc_login_state_parses() {
+        let state = OidcLoginState::default();
+        assert_eq!(OidcLoginState::parse(state.as_ref()), Ok(state));
+    }
+
+    #[test]
+    fn default_passkey_challenge_parses() {
+        let challenge = PasskeyChallenge::default();
+        assert_eq!(PasskeyChallenge::parse(challenge.as_ref()), Ok(challenge));
+    }
+
+    #[test]
+    fn default_magic_link_id_parses() {
+        let id = MagicLinkId::default();
+        assert_eq!(MagicLinkId::parse(id.as_ref()), Ok(id));
+    }
+
+    #[test]
+    fn failed_logins_below_threshold_are_not_locked() {
+        let failures = FailedLogins {
+            count: 4,
+            last_failed_at: 1_000,
+        };
+        assert_eq!(failures.locked_until(5, 60, 3_600), None);
+    }
+
+    #[test]
+    fn failed_logins_lockout_doubles_after_threshold() {
+        let locked_until = |count| {
+            FailedLogins {
+                count,
+                last_failed_at: 1_000,
+            }
+            .locked_until(5, 60, 3_600)
+        };
+
+        assert_eq!(locked_until(5), Some(1_060));
+        assert_eq!(locked_until(6), Some(1_120));
+        assert_eq!(locked_until(7), Some(1_240));
+    }
+
+    #[test]
+    fn failed_logins_lockout_is_capped() {
+        let failures = FailedLogins {
+            count: 1_000,
+            last_failed_at: 1_000,
+        };
+        assert_eq!(failures.locked_until(5, 60, 3_600), Some(4_600));
+    }
+
+    #[test]
+    fn default_login_attempt_binding_parses() {
+        let binding = LoginAttemptBinding::default();
+        assert_eq!(LoginAttemptBinding::parse(binding.as_ref()), Ok(binding));
+    }
+
+    #[test]
+    fn first_two_fa_code_resend_is_allowed_immediately() {
+        assert_eq!(TwoFACodeResends::default().next_allowed_at(30), 0);
+
+        let resends = TwoFACodeResends {
+            count: 1,
+            last_sent_at: 1_000,
+        };
+        assert_eq!(resends.next_allowed_at(30), 1_030);
+    }
+
+    fn limit() -> RateLimit {
+        RateLimit {
+            capacity: 2,
+            period_seconds: 60,
+        }
+    }
+
+    #[test]
+    fn token_bucket_allows_burst_up_to_capacity() {
+        let mut bucket = TokenBucket::full(&limit(), 0);
+
+        assert_eq!(bucket.take(&limit(), 0), RateLimitDecision::Allowed);
+        assert_eq!(bucket.take(&limit(), 0), RateLimitDecision::Allowed);
+        assert_eq!(
+            bucket.take(&limit(), 0),
+            RateLimitDecision::Limited {
+                retry_after_seconds: 30
+            }
+        );
+    }
+
+    #[test]
+    fn token_bucket_refills_over_period() {
+        let mut bucket = TokenBucket::full(&limit(), 0);
+        bucket.take(&limit(), 0);
+        bucket.take(&limit(), 0);
+
+        assert_eq!(
+            bucket.take(&limit(), 20_000),
+            RateLimitDecision::Limited {
+                retry_after_seconds: 10
+            }
+        );
+        assert_eq!(bucket.take(&limit(), 31_000), RateLimitDecision::Allowed);
+    }
+
+    #[test]
+    fn token_bucket_does_not_refill_past_capacity() {
+        let mut bucket = TokenBucket::full(&limit(), 0);
+
+        for _ in 0..2 {
+            assert_eq!(bucket.take(&limit(), 3_600_000), RateLimitDecision::Allowed);
+        }
+        assert!(matches!(
+            bucket.take(&limit(), 3_600_000),
+            RateLimitDecision::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn token_bucket_put_back_does_not_exceed_capacity() {
+        let mut bucket = TokenBucket::full(&limit(), 0);
+        bucket.take(&limit(), 0);
+        bucket.put_back(&limit());
+        bucket.put_back(&limit());
+
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    #[test]
+    fn oidc_login_code_challenge_is_s256() {
+        // Example from RFC 7636 appendix B
+        let login = OidcLogin {
+            provider: "corp".to_owned(),
+            nonce: "nonce".to_owned(),
+            code_verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_owned(),
+            return_to: None,
+        };
+
+        assert_eq!(
+            login.code_challenge(),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use super::UserStoreError;
 use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
 use validator::ValidateEmail;
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
@@ -20,9 +21,9 @@ impl AsRef<str> for Email {
     }
 }
 
-impl ToString for Email {
-    fn to_string(&self) -> String {
-        self.0.clone()
+impl Display for Email {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
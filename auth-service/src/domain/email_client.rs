@@ -1,5 +1,24 @@
 use super::Email;
 
+// An email with a plain text body and, for templated emails, an HTML version of the
+// same content
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+impl EmailMessage {
+    pub fn plain(subject: &str, content: &str) -> Self {
+        Self {
+            subject: subject.to_owned(),
+            text: content.to_owned(),
+            html: None,
+        }
+    }
+}
+
 // This trait represents the interface all concrete email clients should implement
 #[async_trait::async_trait]
 pub trait EmailClient {
@@ -9,4 +28,10 @@ pub trait EmailClient {
         subject: &str,
         content: &str,
     ) -> Result<(), String>;
+
+    // Clients that cannot send HTML fall back to the plain text body
+    async fn send_message(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        self.send_email(recipient, &message.subject, &message.text)
+            .await
+    }
 }
@@ -1,8 +1,33 @@
 pub enum AuthAPIError {
+    // Seconds until logins may be tried again
+    AccountLocked(u64),
+    ClientAlreadyExists,
+    ClientNotFound,
+    EmailNotVerified,
+    IdentityProviderNotFound,
     IncorrectCredentials,
+    InvalidClient,
+    InvalidClientMetadata,
     InvalidCredentials,
+    InvalidGrant,
+    InvalidRedirectUri,
+    InvalidScope,
     InvalidToken,
     MissingToken,
+    PasskeyAlreadyRegistered,
+    PasskeyNotRegistered,
+    PasswordLoginDisabled,
+    PayloadTooLarge,
+    // Seconds until the request may be retried
+    RateLimited(u64),
+    SessionNotFound,
+    TooManyTwoFAAttempts,
+    TooManyTwoFAResends,
+    TotpAlreadyEnabled,
+    TwoFAAlreadyEnabled,
+    TwoFANotEnabled,
     UnexpectedError,
+    UnsupportedGrantType,
+    UpstreamLoginFailed,
     UserAlreadyExists,
 }
@@ -0,0 +1,542 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use ciborium::Value;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::PasskeyChallenge;
+
+// COSE key parameters of the only key type accepted, ES256 on P-256 (RFC 9053)
+const COSE_KEY_TYPE: i128 = 1;
+const COSE_KEY_ALGORITHM: i128 = 3;
+const COSE_EC2_CURVE: i128 = -1;
+const COSE_EC2_X: i128 = -2;
+const COSE_EC2_Y: i128 = -3;
+const COSE_KEY_TYPE_EC2: i128 = 2;
+const COSE_ALGORITHM_ES256: i128 = -7;
+const COSE_CURVE_P256: i128 = 1;
+
+// Authenticator data flags, WebAuthn section 6.1
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+const RP_ID_HASH_LENGTH: usize = 32;
+const AAGUID_LENGTH: usize = 16;
+
+// A WebAuthn credential registered to a user
+#[derive(Clone, Debug, PartialEq)]
+pub struct Passkey {
+    // Base64url id the authenticator chose for the credential
+    pub credential_id: String,
+    // Uncompressed SEC1 encoding of the credential's P-256 public key
+    pub public_key: Vec<u8>,
+    // Number of signatures the authenticator reported at its last use
+    pub sign_count: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasskeyError {
+    InvalidSignature,
+    Malformed(String),
+    SignCountRegressed,
+    UnsupportedKey,
+    UserNotPresent,
+    UserNotVerified,
+    WrongCeremony,
+}
+
+// The site passkeys are scoped to, which is the origin the auth service is served from
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelyingParty {
+    pub id: String,
+    pub origin: String,
+}
+
+impl RelyingParty {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let url = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+        let id = url
+            .host_str()
+            .ok_or_else(|| format!("{url} has no host"))?
+            .to_owned();
+
+        Ok(Self {
+            id,
+            origin: url.origin().ascii_serialization(),
+        })
+    }
+
+    // Check the response to a registration challenge and return the passkey it created.
+    // Attestation is not requested, so whatever statement came with the key is ignored.
+    pub fn verify_registration(
+        &self,
+        challenge: &PasskeyChallenge,
+        credential: &RegistrationCredential,
+    ) -> Result<Passkey, PasskeyError> {
+        let client_data_json = decode(&credential.response.client_data_json)?;
+        self.verify_client_data(&client_data_json, "webauthn.create", challenge)?;
+
+        let attestation_object: Value =
+            ciborium::de::from_reader(decode(&credential.response.attestation_object)?.as_slice())
+                .map_err(|e| PasskeyError::Malformed(e.to_string()))?;
+        let authenticator_data = attestation_object
+            .as_map()
+            .and_then(|fields| text_field(fields, "authData"))
+            .and_then(Value::as_bytes)
+            .ok_or_else(|| malformed("attestation object has no authenticator data"))?;
+
+        let authenticator_data = AuthenticatorData::parse(authenticator_data)?;
+        self.verify_authenticator_data(&authenticator_data, false)?;
+
+        let (credential_id, public_key) = authenticator_data
+            .attested_credential
+            .ok_or_else(|| malformed("authenticator data has no credential"))?;
+        let credential_id = BASE64_URL.encode(credential_id);
+        if credential_id != credential.id {
+            return Err(malformed("credential id does not match authenticator data"));
+        }
+
+        Ok(Passkey {
+            credential_id,
+            public_key,
+            sign_count: authenticator_data.sign_count,
+        })
+    }
+
+    // Check the response to an authentication challenge against the passkey that
+    // signed it, returning the passkey's new signature count
+    pub fn verify_authentication(
+        &self,
+        challenge: &PasskeyChallenge,
+        passkey: &Passkey,
+        credential: &AuthenticationCredential,
+        require_user_verification: bool,
+    ) -> Result<u32, PasskeyError> {
+        let client_data_json = decode(&credential.response.client_data_json)?;
+        self.verify_client_data(&client_data_json, "webauthn.get", challenge)?;
+
+        let raw_authenticator_data = decode(&credential.response.authenticator_data)?;
+        let authenticator_data = AuthenticatorData::parse(&raw_authenticator_data)?;
+        self.verify_authenticator_data(&authenticator_data, require_user_verification)?;
+
+        let verifying_key = VerifyingKey::from_sec1_bytes(&passkey.public_key)
+            .map_err(|_| PasskeyError::UnsupportedKey)?;
+        let signature = Signature::from_der(&decode(&credential.response.signature)?)
+            .map_err(|e| PasskeyError::Malformed(e.to_string()))?;
+
+        let mut signed_data = raw_authenticator_data.clone();
+        signed_data.extend_from_slice(&Sha256::digest(&client_data_json));
+        verifying_key
+            .verify(&signed_data, &signature)
+            .map_err(|_| PasskeyError::InvalidSignature)?;
+
+        // A count that does not move forward means the credential was copied to a
+        // second authenticator. Authenticators without a counter always report zero.
+        let sign_count = authenticator_data.sign_count;
+        if (sign_count != 0 || passkey.sign_count != 0) && sign_count <= passkey.sign_count {
+            return Err(PasskeyError::SignCountRegressed);
+        }
+
+        Ok(sign_count)
+    }
+
+    fn verify_client_data(
+        &self,
+        client_data_json: &[u8],
+        ceremony_type: &str,
+        challenge: &PasskeyChallenge,
+    ) -> Result<(), PasskeyError> {
+        let client_data: ClientData = serde_json::from_slice(client_data_json)
+            .map_err(|e| PasskeyError::Malformed(e.to_string()))?;
+
+        if client_data.ceremony_type != ceremony_type
+            || client_data.challenge != challenge.as_ref()
+            || client_data.origin != self.origin
+            || client_data.cross_origin
+        {
+            return Err(PasskeyError::WrongCeremony);
+        }
+
+        Ok(())
+    }
+
+    fn verify_authenticator_data(
+        &self,
+        authenticator_data: &AuthenticatorData,
+        require_user_verification: bool,
+    ) -> Result<(), PasskeyError> {
+        if authenticator_data.rp_id_hash != Sha256::digest(self.id.as_bytes()).as_slice() {
+            return Err(PasskeyError::WrongCeremony);
+        }
+        if authenticator_data.flags & FLAG_USER_PRESENT == 0 {
+            return Err(PasskeyError::UserNotPresent);
+        }
+        if require_user_verification && authenticator_data.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(PasskeyError::UserNotVerified);
+        }
+
+        Ok(())
+    }
+}
+
+// The JSON form of a newly created PublicKeyCredential, as produced by its toJSON()
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+impl RegistrationCredential {
+    // The challenge the credential claims to answer, which names the ceremony it belongs to
+    pub fn challenge(&self) -> Result<PasskeyChallenge, PasskeyError> {
+        client_data_challenge(&self.response.client_data_json)
+    }
+}
+
+// The JSON form of a PublicKeyCredential returned for an authentication challenge
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuthenticationCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+impl AuthenticationCredential {
+    pub fn challenge(&self) -> Result<PasskeyChallenge, PasskeyError> {
+        client_data_challenge(&self.response.client_data_json)
+    }
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+    #[serde(rename = "crossOrigin", default)]
+    cross_origin: bool,
+}
+
+fn client_data_challenge(client_data_json: &str) -> Result<PasskeyChallenge, PasskeyError> {
+    let client_data: ClientData = serde_json::from_slice(&decode(client_data_json)?)
+        .map_err(|e| PasskeyError::Malformed(e.to_string()))?;
+
+    PasskeyChallenge::parse(&client_data.challenge).map_err(PasskeyError::Malformed)
+}
+
+// Authenticator data, WebAuthn section 6.1
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    // Credential id and SEC1 public key, present only when a credential is created
+    attested_credential: Option<(&'a [u8], Vec<u8>)>,
+}
+
+impl<'a> AuthenticatorData<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, PasskeyError> {
+        if data.len() < RP_ID_HASH_LENGTH + 5 {
+            return Err(malformed("authenticator data is too short"));
+        }
+
+        let (rp_id_hash, rest) = data.split_at(RP_ID_HASH_LENGTH);
+        let flags = rest[0];
+        let sign_count = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]);
+
+        let attested_credential = match flags & FLAG_ATTESTED_CREDENTIAL_DATA {
+            0 => None,
+            _ => Some(parse_attested_credential(&rest[5..])?),
+        };
+
+        Ok(Self {
+            rp_id_hash,
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+}
+
+fn parse_attested_credential(data: &[u8]) -> Result<(&[u8], Vec<u8>), PasskeyError> {
+    let too_short = || malformed("attested credential data is too short");
+
+    let data = data.get(AAGUID_LENGTH..).ok_or_else(too_short)?;
+    let length = data.get(..2).ok_or_else(too_short)?;
+    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+    let credential_id = data.get(2..2 + length).ok_or_else(too_short)?;
+
+    // Any extension data after the key is left unread
+    let cose_key: Value = ciborium::de::from_reader(&data[2 + length..])
+        .map_err(|e| PasskeyError::Malformed(e.to_string()))?;
+
+    Ok((credential_id, cose_key_to_sec1(&cose_key)?))
+}
+
+fn cose_key_to_sec1(cose_key: &Value) -> Result<Vec<u8>, PasskeyError> {
+    let fields = cose_key
+        .as_map()
+        .ok_or_else(|| malformed("public key is not a COSE key"))?;
+    let integer = |label| {
+        integer_field(fields, label)
+            .and_then(Value::as_integer)
+            .map(i128::from)
+    };
+    let coordinate = |label| {
+        integer_field(fields, label)
+            .and_then(Value::as_bytes)
+            .filter(|coordinate| coordinate.len() == 32)
+    };
+
+    if integer(COSE_KEY_TYPE) != Some(COSE_KEY_TYPE_EC2)
+        || integer(COSE_KEY_ALGORITHM) != Some(COSE_ALGORITHM_ES256)
+        || integer(COSE_EC2_CURVE) != Some(COSE_CURVE_P256)
+    {
+        return Err(PasskeyError::UnsupportedKey);
+    }
+    let (Some(x), Some(y)) = (coordinate(COSE_EC2_X), coordinate(COSE_EC2_Y)) else {
+        return Err(PasskeyError::UnsupportedKey);
+    };
+
+    let mut public_key = vec![0x04];
+    public_key.extend_from_slice(x);
+    public_key.extend_from_slice(y);
+
+    // Reject points that are not on the curve now rather than at the first login
+    VerifyingKey::from_sec1_bytes(&public_key).map_err(|_| PasskeyError::UnsupportedKey)?;
+
+    Ok(public_key)
+}
+
+fn text_field<'a>(fields: &'a [(Value, Value)], name: &str) -> Option<&'a Value> {
+    fields
+        .iter()
+        .find(|(key, _)| key.as_text() == Some(name))
+        .map(|(_, value)| value)
+}
+
+fn integer_field(fields: &[(Value, Value)], label: i128) -> Option<&Value> {
+    fields
+        .iter()
+        .find(|(key, _)| key.as_integer().map(i128::from) == Some(label))
+        .map(|(_, value)| value)
+}
+
+// Browsers send base64url without padding, but padded values are accepted too
+fn decode(value: &str) -> Result<Vec<u8>, PasskeyError> {
+    BASE64_URL
+        .decode(value.trim_end_matches('='))
+        .map_err(|e| PasskeyError::Malformed(e.to_string()))
+}
+
+fn malformed(message: &str) -> PasskeyError {
+    PasskeyError::Malformed(message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use serde_json::json;
+
+    const ORIGIN: &str = "https://auth.example.com";
+    const CHALLENGE: &str = "abcdefghijklmnopqrstuvwxyz012345";
+
+    fn challenge() -> PasskeyChallenge {
+        PasskeyChallenge::parse(CHALLENGE).unwrap()
+    }
+
+    fn relying_party() -> RelyingParty {
+        RelyingParty::parse(&format!("{ORIGIN}/some/path")).unwrap()
+    }
+
+    fn client_data(ceremony_type: &str, challenge: &PasskeyChallenge, origin: &str) -> String {
+        let client_data = json!({
+            "type": ceremony_type,
+            "challenge": challenge.as_ref(),
+            "origin": origin,
+        });
+        BASE64_URL.encode(client_data.to_string())
+    }
+
+    fn authenticator_data(flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(b"auth.example.com").to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn register(key: &SigningKey, origin: &str) -> RegistrationCredential {
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(-7)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::from(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::from(point.y().unwrap().to_vec())),
+        ]);
+        let credential_id = [7u8; 16];
+
+        let mut data = authenticator_data(FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA, 0);
+        data.extend_from_slice(&[0u8; AAGUID_LENGTH]);
+        data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        data.extend_from_slice(&credential_id);
+        ciborium::ser::into_writer(&cose_key, &mut data).unwrap();
+
+        let attestation_object = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(Vec::new())),
+            (Value::from("authData"), Value::from(data)),
+        ]);
+        let mut attestation_bytes = Vec::new();
+        ciborium::ser::into_writer(&attestation_object, &mut attestation_bytes).unwrap();
+
+        RegistrationCredential {
+            id: BASE64_URL.encode(credential_id),
+            response: AttestationResponse {
+                client_data_json: client_data("webauthn.create", &challenge(), origin),
+                attestation_object: BASE64_URL.encode(attestation_bytes),
+            },
+        }
+    }
+
+    fn authenticate(
+        key: &SigningKey,
+        passkey: &Passkey,
+        flags: u8,
+        sign_count: u32,
+    ) -> AuthenticationCredential {
+        let client_data_json = client_data("webauthn.get", &challenge(), ORIGIN);
+        let data = authenticator_data(flags, sign_count);
+
+        let mut signed_data = data.clone();
+        signed_data.extend_from_slice(&Sha256::digest(decode(&client_data_json).unwrap()));
+        let signature: Signature = key.sign(&signed_data);
+
+        AuthenticationCredential {
+            id: passkey.credential_id.clone(),
+            response: AssertionResponse {
+                client_data_json,
+                authenticator_data: BASE64_URL.encode(data),
+                signature: BASE64_URL.encode(signature.to_der()),
+            },
+        }
+    }
+
+    #[test]
+    fn relying_party_is_the_service_origin() {
+        assert_eq!(
+            relying_party(),
+            RelyingParty {
+                id: "auth.example.com".to_owned(),
+                origin: ORIGIN.to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn registration_returns_passkey() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let credential = register(&key, ORIGIN);
+
+        assert_eq!(credential.challenge(), Ok(challenge()));
+
+        let passkey = relying_party()
+            .verify_registration(&challenge(), &credential)
+            .unwrap();
+        assert_eq!(passkey.credential_id, credential.id);
+        assert_eq!(
+            passkey.public_key,
+            key.verifying_key().to_encoded_point(false).as_bytes()
+        );
+    }
+
+    #[test]
+    fn registration_from_another_origin_returns_err() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let credential = register(&key, "https://evil.example.com");
+
+        assert_eq!(
+            relying_party().verify_registration(&challenge(), &credential),
+            Err(PasskeyError::WrongCeremony)
+        );
+    }
+
+    #[test]
+    fn authentication_returns_new_sign_count() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let passkey = relying_party()
+            .verify_registration(&challenge(), &register(&key, ORIGIN))
+            .unwrap();
+        let credential = authenticate(&key, &passkey, FLAG_USER_PRESENT | FLAG_USER_VERIFIED, 1);
+
+        assert_eq!(
+            relying_party().verify_authentication(&challenge(), &passkey, &credential, true),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn authentication_signed_by_another_key_returns_err() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let passkey = relying_party()
+            .verify_registration(&challenge(), &register(&key, ORIGIN))
+            .unwrap();
+        let other_key = SigningKey::random(&mut rand::rngs::OsRng);
+        let credential = authenticate(&other_key, &passkey, FLAG_USER_PRESENT, 1);
+
+        assert_eq!(
+            relying_party().verify_authentication(&challenge(), &passkey, &credential, false),
+            Err(PasskeyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn authentication_without_user_verification_returns_err_when_required() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let passkey = relying_party()
+            .verify_registration(&challenge(), &register(&key, ORIGIN))
+            .unwrap();
+        let credential = authenticate(&key, &passkey, FLAG_USER_PRESENT, 1);
+
+        assert_eq!(
+            relying_party().verify_authentication(&challenge(), &passkey, &credential, true),
+            Err(PasskeyError::UserNotVerified)
+        );
+        assert_eq!(
+            relying_party().verify_authentication(&challenge(), &passkey, &credential, false),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn authentication_with_stale_sign_count_returns_err() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let mut passkey = relying_party()
+            .verify_registration(&challenge(), &register(&key, ORIGIN))
+            .unwrap();
+        passkey.sign_count = 5;
+        let credential = authenticate(&key, &passkey, FLAG_USER_PRESENT, 5);
+
+        assert_eq!(
+            relying_party().verify_authentication(&challenge(), &passkey, &credential, false),
+            Err(PasskeyError::SignCountRegressed)
+        );
+    }
+}
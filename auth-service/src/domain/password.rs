@@ -1,5 +1,9 @@
+use rand::{distributions::Alphanumeric, Rng};
+
 use super::UserStoreError;
 
+const GENERATED_PASSWORD_LENGTH: usize = 40;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Password(String);
 
@@ -23,6 +27,20 @@ impl Password {
     }
 }
 
+// An unguessable password for accounts that are created without one, such as
+// those signing in through an identity provider. The fixed suffix makes sure it
+// passes the same rules as a chosen password.
+impl Default for Password {
+    fn default() -> Self {
+        let password: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(GENERATED_PASSWORD_LENGTH)
+            .map(char::from)
+            .collect();
+        Password(format!("{password}Aa1"))
+    }
+}
+
 impl AsRef<str> for Password {
     fn as_ref(&self) -> &str {
         &self.0
@@ -33,6 +51,12 @@ impl AsRef<str> for Password {
 mod tests {
     use super::*;
 
+    #[test]
+    fn default_password_parses() {
+        let password = Password::default();
+        assert_eq!(Password::parse(password.as_ref()), Ok(password));
+    }
+
     #[test]
     fn missing_number_returns_err() {
         assert_eq!(
@@ -0,0 +1,93 @@
+use rand::Rng;
+use serde::Serialize;
+
+// Number of codes issued each time 2FA is enabled or the codes are regenerated
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+const RECOVERY_CODE_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+// A single-use code formatted as two groups of five, e.g. "k3v9q-x7m2p"
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let code = code.trim().to_lowercase();
+        let valid = match code.split_once('-') {
+            Some((first, second)) => [first, second].iter().all(|group| {
+                group.len() == RECOVERY_CODE_GROUP_LENGTH
+                    && group.bytes().all(|c| RECOVERY_CODE_CHARSET.contains(&c))
+            }),
+            None => false,
+        };
+
+        match valid {
+            true => Ok(RecoveryCode(code)),
+            false => Err("Invalid recovery code".to_string()),
+        }
+    }
+
+    pub fn generate_set() -> Vec<Self> {
+        (0..RECOVERY_CODE_COUNT)
+            .map(|_| RecoveryCode::default())
+            .collect()
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut group = || -> String {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| {
+                    RECOVERY_CODE_CHARSET[rng.gen_range(0..RECOVERY_CODE_CHARSET.len())] as char
+                })
+                .collect()
+        };
+        let first = group();
+        let second = group();
+        RecoveryCode(format!("{first}-{second}"))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn default_recovery_code_parses() {
+        let code = RecoveryCode::default();
+        assert_eq!(RecoveryCode::parse(code.as_ref()), Ok(code));
+    }
+
+    #[test]
+    fn parse_normalizes_case_and_whitespace() {
+        assert_eq!(
+            RecoveryCode::parse(" K3V9Q-X7M2P "),
+            RecoveryCode::parse("k3v9q-x7m2p")
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_codes() {
+        for code in ["", "123456", "k3v9qx7m2p", "k3v9q-x7m2", "k3v9q-x7m2p!"] {
+            assert!(
+                RecoveryCode::parse(code).is_err(),
+                "{code} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn generate_set_returns_distinct_codes() {
+        let codes: HashSet<_> = RecoveryCode::generate_set().into_iter().collect();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+    }
+}
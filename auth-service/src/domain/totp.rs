@@ -0,0 +1,153 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, TOTP};
+
+use super::{Email, TwoFACode};
+
+// RFC 4226 recommends a 160 bit shared secret and requires at least 128 bits
+const TOTP_SECRET_LENGTH: usize = 20;
+const TOTP_MIN_SECRET_LENGTH: usize = 16;
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const TOTP_ISSUER: &str = "auth-service";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    pub fn parse(secret: Vec<u8>) -> Result<Self, String> {
+        match secret.len() >= TOTP_MIN_SECRET_LENGTH {
+            true => Ok(TotpSecret(secret)),
+            false => Err("TOTP secret is too short".to_string()),
+        }
+    }
+
+    // Base32 form that users can type into an authenticator app
+    pub fn to_base32(&self) -> String {
+        self.totp(0, "").get_secret_base32()
+    }
+
+    pub fn otpauth_uri(&self, email: &Email) -> Result<String, String> {
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP_SECONDS,
+            self.0.clone(),
+            Some(TOTP_ISSUER.to_string()),
+            email.as_ref().to_string(),
+        )
+        .map(|totp| totp.get_url())
+        .map_err(|e| e.to_string())
+    }
+
+    // The time step the code was generated for, if it is accepted at the given time.
+    // Codes up to `skew` time steps before or after the given time are accepted.
+    pub fn matching_step(&self, code: &TwoFACode, time: u64, skew: u8) -> Option<u64> {
+        let totp = self.totp(0, "");
+        let step = time / TOTP_STEP_SECONDS;
+
+        (step.saturating_sub(skew.into())..=step + u64::from(skew)).find(|step| {
+            totp.generate(step * TOTP_STEP_SECONDS)
+                .as_bytes()
+                .ct_eq(code.as_ref().as_bytes())
+                .into()
+        })
+    }
+
+    pub fn matching_current_step(&self, code: &TwoFACode, skew: u8) -> Option<u64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        self.matching_step(code, now.as_secs(), skew)
+    }
+
+    fn totp(&self, skew: u8, account_name: &str) -> TOTP {
+        TOTP::new_unchecked(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            skew,
+            TOTP_STEP_SECONDS,
+            self.0.clone(),
+            None,
+            account_name.to_string(),
+        )
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut secret = vec![0u8; TOTP_SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut secret);
+        TotpSecret(secret)
+    }
+}
+
+impl AsRef<[u8]> for TotpSecret {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector from RFC 6238 Appendix B
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn code_at(secret: &TotpSecret, time: u64) -> TwoFACode {
+        TwoFACode::parse(&secret.totp(0, "").generate(time)).unwrap()
+    }
+
+    #[test]
+    fn parse_short_secret_returns_err() {
+        assert!(TotpSecret::parse(vec![0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn matching_step_accepts_rfc_6238_code() {
+        let secret = TotpSecret::parse(RFC_SECRET.to_vec()).unwrap();
+        let code = TwoFACode::parse("287082").unwrap();
+        assert_eq!(secret.matching_step(&code, 59, 0), Some(1));
+    }
+
+    #[test]
+    fn matching_step_rejects_incorrect_code() {
+        let secret = TotpSecret::default();
+        let code = code_at(&secret, 1_000_000);
+        let incorrect = TwoFACode::parse(if code.as_ref() == "123456" {
+            "654321"
+        } else {
+            "123456"
+        })
+        .unwrap();
+        assert_eq!(secret.matching_step(&incorrect, 1_000_000, 1), None);
+    }
+
+    #[test]
+    fn matching_step_accepts_code_within_skew() {
+        let secret = TotpSecret::default();
+        let time = 1_000_020;
+        let previous_code = code_at(&secret, time - TOTP_STEP_SECONDS);
+
+        assert_eq!(
+            secret.matching_step(&previous_code, time, 1),
+            Some(time / TOTP_STEP_SECONDS - 1)
+        );
+        assert_eq!(
+            secret.matching_step(&previous_code, time + TOTP_STEP_SECONDS, 1),
+            None
+        );
+    }
+
+    #[test]
+    fn otpauth_uri_contains_issuer_and_secret() {
+        let secret = TotpSecret::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let uri = secret.otpauth_uri(&email).unwrap();
+
+        assert!(uri.starts_with("otpauth://totp/auth-service:test%40example.com?"));
+        assert!(uri.contains(&format!("secret={}", secret.to_base32())));
+    }
+}
@@ -1,18 +1,85 @@
+use serde::{Deserialize, Serialize};
+
 use super::{Email, Password};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
     pub email: Email,
     pub password: Password,
-    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub email_verified: bool,
+    // Users who opt out of passwords sign in with magic links, passkeys or an
+    // identity provider instead
+    pub password_login_disabled: bool,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    pub fn new(email: Email, password: Password, two_fa_method: TwoFAMethod) -> Self {
         Self {
             email,
             password,
-            requires_2fa,
+            two_fa_method,
+            email_verified: false,
+            password_login_disabled: false,
+        }
+    }
+
+    pub fn requires_2fa(&self) -> bool {
+        self.two_fa_method != TwoFAMethod::Disabled
+    }
+}
+
+// The second factor a user must present after their password
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFAMethod {
+    Disabled,
+    Email,
+    Passkey,
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(method: &str) -> Result<Self, String> {
+        match method {
+            "disabled" => Ok(TwoFAMethod::Disabled),
+            "email" => Ok(TwoFAMethod::Email),
+            "passkey" => Ok(TwoFAMethod::Passkey),
+            "totp" => Ok(TwoFAMethod::Totp),
+            _ => Err(format!("Invalid 2FA method: {method}")),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            TwoFAMethod::Disabled => "disabled",
+            TwoFAMethod::Email => "email",
+            TwoFAMethod::Passkey => "passkey",
+            TwoFAMethod::Totp => "totp",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_fa_method_round_trips_through_str() {
+        for method in [
+            TwoFAMethod::Disabled,
+            TwoFAMethod::Email,
+            TwoFAMethod::Passkey,
+            TwoFAMethod::Totp,
+        ] {
+            assert_eq!(TwoFAMethod::parse(method.as_ref()), Ok(method));
         }
     }
+
+    #[test]
+    fn parse_unknown_two_fa_method_returns_err() {
+        assert!(TwoFAMethod::parse("sms").is_err());
+    }
 }
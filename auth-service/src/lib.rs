@@ -6,22 +6,28 @@ pub mod utils;
 
 use app_state::AppState;
 use axum::{
-    http::{Method, StatusCode},
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
+    http::{header::RETRY_AFTER, Method, StatusCode},
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
 use domain::AuthAPIError;
 use routes::*;
 use serde::{Deserialize, Serialize};
+use services::rate_limit;
 use sqlx::postgres::{PgPool, PgPoolOptions};
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     pub address: String,
 }
 
@@ -33,25 +39,88 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            // Allow GET and POST requests
-            .allow_methods([Method::GET, Method::POST])
+            // Allow GET, POST and DELETE requests
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             // Allow cookies to be included in requests
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
+            .route("/.well-known/jwks.json", get(jwks))
+            .route(
+                "/.well-known/openid-configuration",
+                get(openid_configuration),
+            )
+            .route("/admin/clients", post(create_client))
+            .route("/admin/clients/:id/disable", post(disable_client))
+            .route(
+                "/admin/clients/:id/rotate-secret",
+                post(rotate_client_secret),
+            )
+            .route("/admin/outbox/failed", get(list_failed_emails))
+            .route("/authorize", get(authorize))
+            .route("/change-password", post(change_password))
+            .route("/introspect", post(introspect))
             .route("/login", post(login))
+            .route("/login/magic-link", post(request_magic_link))
+            .route(
+                "/login/magic-link/callback",
+                get(magic_link_page).post(magic_link_callback),
+            )
             .route("/logout", post(logout))
+            .route("/logout-all", post(logout_all))
+            .route("/oidc/providers", get(oidc_providers))
+            .route("/oidc/:provider/callback", get(oidc_callback))
+            .route("/oidc/:provider/login", get(oidc_login))
+            .route("/passkeys/login/finish", post(finish_passkey_login))
+            .route("/passkeys/login/start", post(start_passkey_login))
+            .route(
+                "/passkeys/register/finish",
+                post(finish_passkey_registration),
+            )
+            .route("/passkeys/register/start", post(start_passkey_registration))
+            .route(
+                "/passkeys/reauthenticate/start",
+                post(start_passkey_reauthentication),
+            )
+            .route("/password-login/disable", post(disable_password_login))
+            .route("/password-login/enable", post(enable_password_login))
+            .route("/password-reset/request", post(request_password_reset))
+            .route("/password-reset/confirm", post(confirm_password_reset))
+            .route("/refresh", post(refresh))
+            .route("/resend-2fa", post(resend_2fa))
+            .route("/revoke", post(revoke))
+            .route("/sessions", get(list_sessions))
+            .route("/sessions/:id", delete(revoke_session))
             .route("/signup", post(signup))
+            .route("/token", post(token))
+            .route("/2fa/disable", post(disable_2fa))
+            .route("/2fa/email-code", post(send_2fa_code))
+            .route("/2fa/enable", post(enable_2fa))
+            .route("/2fa/passkey/enable", post(enable_passkey_2fa))
+            .route("/2fa/recovery-codes", post(regenerate_recovery_codes))
+            .route("/2fa/totp/confirm", post(confirm_totp))
+            .route("/2fa/totp/enroll", post(enroll_totp))
+            .route("/userinfo", get(userinfo))
             .route("/verify-2fa", post(verify_2fa))
+            .route("/verify-email", get(verify_email))
+            .route("/verify-email/resend", post(resend_verification_email))
             .route("/verify-token", post(verify_token))
+            .layer(middleware::from_fn_with_state(
+                app_state.rate_limiter.clone(),
+                rate_limit,
+            ))
             .with_state(app_state)
             .layer(cors);
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Sessions record the client address, so the router needs to see it
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         // Create a new Application instance and return it
         Ok(Self { server, address })
@@ -81,20 +150,69 @@ pub struct ErrorResponse {
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
+            AuthAPIError::AccountLocked(_) => (StatusCode::TOO_MANY_REQUESTS, "Account locked"),
+            AuthAPIError::ClientAlreadyExists => (StatusCode::CONFLICT, "Client already exists"),
+            AuthAPIError::ClientNotFound => (StatusCode::NOT_FOUND, "Client not found"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email not verified"),
+            AuthAPIError::IdentityProviderNotFound => {
+                (StatusCode::NOT_FOUND, "Identity provider not found")
+            }
             AuthAPIError::IncorrectCredentials => {
                 (StatusCode::UNAUTHORIZED, "Incorrect credentials")
             }
+            // Errors returned to OAuth clients use the codes defined by RFC 6749
+            AuthAPIError::InvalidClient => (StatusCode::UNAUTHORIZED, "invalid_client"),
+            AuthAPIError::InvalidClientMetadata => {
+                (StatusCode::BAD_REQUEST, "Invalid client metadata")
+            }
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::InvalidGrant => (StatusCode::BAD_REQUEST, "invalid_grant"),
+            AuthAPIError::InvalidRedirectUri => (StatusCode::BAD_REQUEST, "Invalid redirect URI"),
+            AuthAPIError::InvalidScope => (StatusCode::BAD_REQUEST, "invalid_scope"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
+            AuthAPIError::PasskeyAlreadyRegistered => {
+                (StatusCode::CONFLICT, "Passkey already registered")
+            }
+            AuthAPIError::PasskeyNotRegistered => (StatusCode::CONFLICT, "No passkey registered"),
+            AuthAPIError::PasswordLoginDisabled => {
+                (StatusCode::FORBIDDEN, "Password login is disabled")
+            }
+            AuthAPIError::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Payload too large"),
+            AuthAPIError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            // The login attempt is over, so the client has to log in again for a new code
+            AuthAPIError::TooManyTwoFAAttempts => (
+                StatusCode::FORBIDDEN,
+                "Too many incorrect 2FA codes, please log in again",
+            ),
+            AuthAPIError::TooManyTwoFAResends => (
+                StatusCode::FORBIDDEN,
+                "Too many 2FA codes sent, please log in again",
+            ),
+            AuthAPIError::TotpAlreadyEnabled => (StatusCode::CONFLICT, "TOTP already enabled"),
+            AuthAPIError::TwoFAAlreadyEnabled => (StatusCode::CONFLICT, "2FA already enabled"),
+            AuthAPIError::TwoFANotEnabled => (StatusCode::CONFLICT, "2FA not enabled"),
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::UnexpectedError => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
+            AuthAPIError::UnsupportedGrantType => {
+                (StatusCode::BAD_REQUEST, "unsupported_grant_type")
+            }
+            AuthAPIError::UpstreamLoginFailed => {
+                (StatusCode::UNAUTHORIZED, "Upstream login failed")
+            }
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
-        (status, body).into_response()
+
+        match self {
+            AuthAPIError::AccountLocked(retry_after) | AuthAPIError::RateLimited(retry_after) => {
+                (status, [(RETRY_AFTER, retry_after.to_string())], body).into_response()
+            }
+            _ => (status, body).into_response(),
+        }
     }
 }
@@ -1,25 +1,89 @@
 use auth_service::{
-    app_state::AppState,
+    app_state::{AppState, ClientStoreType, EmailClientType, EmailOutboxType},
+    domain::{Client, ClientId, ClientSecret, RedirectUri},
     get_postgres_pool, get_redis_client,
-    services::{MockEmailClient, PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
-    utils::constants::{prod, DATABASE_URL, REDIS_HOST_NAME},
+    services::{
+        MockEmailClient, OidcProviders, OutboxWorker, PostgresClientStore, PostgresEmailOutbox,
+        PostgresUserStore, RateLimiter, RedisAuthorizationCodeStore, RedisBannedTokenStore,
+        RedisEmailVerificationTokenStore, RedisFailedLoginStore, RedisMagicLinkStore,
+        RedisOidcLoginStore, RedisPasskeyChallengeStore, RedisPasswordResetTokenStore,
+        RedisRateLimitStore, RedisRefreshTokenStore, RedisSessionStore, RedisTwoFACodeStore,
+        SmtpEmailClient,
+    },
+    utils::{
+        constants::{prod, DATABASE_URL, EMAIL_CLIENT, OAUTH_CLIENTS, REDIS_HOST_NAME},
+        key_ring::{key_ring, reload_key_ring},
+    },
     Application,
 };
 use sqlx::PgPool;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::RwLock,
+};
 
 #[tokio::main]
 async fn main() {
+    // Fail at startup rather than on the first login if the signing keys are misconfigured
+    key_ring();
+    tokio::spawn(reload_key_ring_on_hangup());
+
     let pg_pool = configure_postgresql().await;
+    let client_store: ClientStoreType =
+        Arc::new(RwLock::new(PostgresClientStore::new(pg_pool.clone())));
+    register_configured_clients(&client_store).await;
+    // Emails are queued in Postgres alongside the changes that trigger them and sent
+    // in the background, so a mail provider outage does not fail requests
+    let email_outbox: EmailOutboxType =
+        Arc::new(RwLock::new(PostgresEmailOutbox::new(pg_pool.clone())));
+    tokio::spawn(OutboxWorker::new(email_outbox.clone(), configure_email_client()).run());
     let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
     let redis_conn = Arc::new(RwLock::new(configure_redis()));
+    let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+        redis_conn.clone(),
+    )));
     let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
+    let email_verification_token_store = Arc::new(RwLock::new(
+        RedisEmailVerificationTokenStore::new(redis_conn.clone()),
+    ));
+    let failed_login_store = Arc::new(RwLock::new(RedisFailedLoginStore::new(redis_conn.clone())));
+    let magic_link_store = Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_conn.clone())));
+    let oidc_login_store = Arc::new(RwLock::new(RedisOidcLoginStore::new(redis_conn.clone())));
+    let oidc_providers =
+        Arc::new(OidcProviders::from_config().expect("Failed to load OIDC providers"));
+    let passkey_challenge_store = Arc::new(RwLock::new(RedisPasskeyChallengeStore::new(
+        redis_conn.clone(),
+    )));
+    let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(
+        redis_conn.clone(),
+    )));
+    // Buckets live in Redis so limits hold across every replica
+    let rate_limiter = Arc::new(
+        RateLimiter::from_config(Arc::new(RwLock::new(RedisRateLimitStore::new(
+            redis_conn.clone(),
+        ))))
+        .expect("Failed to load rate limits"),
+    );
+    let refresh_token_store =
+        Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+    let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_conn.clone())));
     let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
-    let mock_email_client = Arc::new(MockEmailClient::default());
     let app_state = AppState::new(
+        authorization_code_store,
         banned_token_store,
-        mock_email_client,
+        client_store,
+        email_outbox,
+        email_verification_token_store,
+        failed_login_store,
+        magic_link_store,
+        oidc_login_store,
+        oidc_providers,
+        passkey_challenge_store,
+        password_reset_token_store,
+        rate_limiter,
+        refresh_token_store,
+        session_store,
         two_fa_code_store,
         user_store,
     );
@@ -46,9 +110,60 @@ async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
+// Register the clients listed in OAUTH_CLIENTS, replacing the secrets of any
+// that already exist so a secret can be rotated by restarting with a new one
+async fn register_configured_clients(client_store: &ClientStoreType) {
+    let mut client_store = client_store.write().await;
+
+    for client in OAUTH_CLIENTS.split(',').filter(|client| !client.is_empty()) {
+        let mut fields = client.splitn(3, ':');
+        let (Some(client_id), Some(secret)) = (fields.next(), fields.next()) else {
+            panic!("OAUTH_CLIENTS entries must be client_id:client_secret[:redirect_uris]");
+        };
+        let client_id = ClientId::parse(client_id.trim()).expect("Invalid OAuth client id");
+        let secret = ClientSecret::parse(secret.trim()).expect("Invalid OAuth client secret");
+        let redirect_uris = fields
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|redirect_uri| RedirectUri::parse(redirect_uri).expect("Invalid redirect URI"))
+            .collect();
+
+        client_store
+            .set_client(Client::new(client_id, redirect_uris, Vec::new()), secret)
+            .await
+            .expect("Failed to register OAuth client");
+    }
+}
+
+fn configure_email_client() -> EmailClientType {
+    match EMAIL_CLIENT.as_str() {
+        "mock" => Arc::new(MockEmailClient),
+        "smtp" => {
+            Arc::new(SmtpEmailClient::from_config().expect("Failed to configure SMTP email client"))
+        }
+        other => panic!("EMAIL_CLIENT must be mock or smtp, not {other}"),
+    }
+}
+
 fn configure_redis() -> redis::Connection {
     get_redis_client(REDIS_HOST_NAME.to_owned())
         .expect("Failed to get Redis client")
         .get_connection()
         .expect("Failed to get Redis connection")
 }
+
+// Pick up rotated signing keys on SIGHUP without restarting
+async fn reload_key_ring_on_hangup() {
+    let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+
+    while hangup.recv().await.is_some() {
+        match reload_key_ring() {
+            Ok(()) => println!("reloaded JWT key ring"),
+            Err(e) => println!(
+                "failed to reload JWT key ring, keeping current keys: {:?}",
+                e
+            ),
+        }
+    }
+}
@@ -1,12 +1,56 @@
+mod admin_clients;
+mod admin_outbox;
+mod authorize;
+mod change_password;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oidc_login;
+mod openid_configuration;
+mod passkeys;
+mod password_login;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
+mod revoke;
+mod sessions;
 mod signup;
+mod token;
+mod totp;
+mod two_fa;
+mod userinfo;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
 // re-export items from sub-modules
+pub use admin_clients::*;
+pub use admin_outbox::*;
+pub use authorize::*;
+pub use change_password::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use oidc_login::*;
+pub use openid_configuration::*;
+pub use passkeys::*;
+pub use password_login::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use resend_2fa::*;
+pub use revoke::*;
+pub use sessions::*;
 pub use signup::*;
+pub use token::*;
+pub use totp::*;
+pub use two_fa::*;
+pub use userinfo::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
@@ -0,0 +1,138 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        is_valid_scope, AuthAPIError, Client, ClientId, ClientSecret, ClientStoreError, RedirectUri,
+    },
+    utils::auth::authenticate_admin,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+// Register a new OAuth client. Its secret is generated here and only ever shown in
+// this response.
+pub async fn create_client(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authenticate_admin(&headers)?;
+
+    let client_id =
+        ClientId::parse(&request.client_id).map_err(|_| AuthAPIError::InvalidClientMetadata)?;
+    let redirect_uris = request
+        .redirect_uris
+        .iter()
+        .map(|uri| RedirectUri::parse(uri))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| AuthAPIError::InvalidClientMetadata)?;
+    if !request.scopes.iter().all(|scope| is_valid_scope(scope)) {
+        return Err(AuthAPIError::InvalidClientMetadata);
+    }
+
+    let client = Client::new(client_id.clone(), redirect_uris, request.scopes);
+    let secret = ClientSecret::default();
+
+    match state
+        .client_store
+        .write()
+        .await
+        .add_client(client, secret.clone())
+        .await
+    {
+        Ok(()) => (),
+        Err(ClientStoreError::ClientAlreadyExists) => {
+            return Err(AuthAPIError::ClientAlreadyExists)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ClientCredentialsResponse::new(client_id, secret)),
+    ))
+}
+
+// Replace a client's secret. The old secret stops working immediately.
+pub async fn rotate_client_secret(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authenticate_admin(&headers)?;
+
+    let client_id = ClientId::parse(&id).map_err(|_| AuthAPIError::ClientNotFound)?;
+    let secret = ClientSecret::default();
+
+    state
+        .client_store
+        .write()
+        .await
+        .rotate_secret(&client_id, secret.clone())
+        .await
+        .map_err(client_store_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ClientCredentialsResponse::new(client_id, secret)),
+    ))
+}
+
+// Disable a client, which also stops every token already issued to it from validating
+pub async fn disable_client(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authenticate_admin(&headers)?;
+
+    let client_id = ClientId::parse(&id).map_err(|_| AuthAPIError::ClientNotFound)?;
+
+    state
+        .client_store
+        .write()
+        .await
+        .disable_client(&client_id)
+        .await
+        .map_err(client_store_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+fn client_store_error(e: ClientStoreError) -> AuthAPIError {
+    match e {
+        ClientStoreError::ClientNotFound => AuthAPIError::ClientNotFound,
+        _ => AuthAPIError::UnexpectedError,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateClientRequest {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "redirectUris", default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClientCredentialsResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "clientSecret")]
+    pub client_secret: String,
+}
+
+impl ClientCredentialsResponse {
+    fn new(client_id: ClientId, secret: ClientSecret) -> Self {
+        Self {
+            client_id: client_id.as_ref().to_owned(),
+            client_secret: secret.as_ref().to_owned(),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, OutboxEmail},
+    utils::auth::authenticate_admin,
+};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+
+// Emails the outbox worker gave up on, oldest first. They stay until removed from
+// the database, so the cause can be fixed and the users told some other way.
+pub async fn list_failed_emails(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authenticate_admin(&headers)?;
+
+    let emails = state
+        .email_outbox
+        .read()
+        .await
+        .get_failed()
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?
+        .into_iter()
+        .map(FailedEmailResponse::from)
+        .collect();
+
+    Ok((StatusCode::OK, Json(FailedEmailsResponse { emails })))
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedEmailsResponse {
+    pub emails: Vec<FailedEmailResponse>,
+}
+
+// The body is left out, since it may hold a code or link that still works
+#[derive(Debug, Serialize)]
+pub struct FailedEmailResponse {
+    pub id: String,
+    pub recipient: String,
+    pub subject: String,
+    pub attempts: u32,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: usize,
+    #[serde(rename = "failedAt")]
+    pub failed_at: Option<usize>,
+}
+
+impl From<OutboxEmail> for FailedEmailResponse {
+    fn from(email: OutboxEmail) -> Self {
+        Self {
+            id: email.id.as_ref().to_owned(),
+            recipient: email.recipient.as_ref().to_owned(),
+            subject: email.message.subject,
+            attempts: email.attempts,
+            last_error: email.last_error,
+            created_at: email.created_at,
+            failed_at: email.failed_at,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthorizationCode, AuthorizationGrant, ClientId, ClientStoreError, Email,
+        RedirectUri, SessionId,
+    },
+    utils::auth::{authenticated_claims, scope_includes},
+};
+use axum::{
+    extract::{Query, State},
+    http::Uri,
+    response::Redirect,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+// Scopes this provider understands. Any others are left out of the granted scope.
+pub const SUPPORTED_SCOPES: [&str; 2] = ["openid", "email"];
+
+// Start the OpenID Connect authorization code flow. Users who are not signed in
+// are sent to the login page, which brings them back here once they are.
+pub async fn authorize(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    uri: Uri,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Redirect, AuthAPIError> {
+    let client_id = request
+        .client_id
+        .as_deref()
+        .and_then(|client_id| ClientId::parse(client_id).ok())
+        .ok_or(AuthAPIError::InvalidClient)?;
+
+    let client = match state.client_store.read().await.get_client(&client_id).await {
+        Ok(client) if !client.disabled => client,
+        Ok(_) | Err(ClientStoreError::ClientNotFound) => return Err(AuthAPIError::InvalidClient),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    // Until the redirect URI is known to belong to the client, errors are shown to
+    // the user rather than sent somewhere an attacker may control
+    let redirect_uri = request
+        .redirect_uri
+        .as_deref()
+        .and_then(|redirect_uri| client.redirect_uri(redirect_uri))
+        .ok_or(AuthAPIError::InvalidRedirectUri)?
+        .clone();
+
+    if request.response_type.as_deref() != Some("code") {
+        return redirect_to_client(
+            &redirect_uri,
+            "error",
+            "unsupported_response_type",
+            &request,
+        );
+    }
+
+    let scope = request.scope.as_deref().unwrap_or_default();
+    if !scope_includes(scope, "openid") {
+        return redirect_to_client(&redirect_uri, "error", "invalid_scope", &request);
+    }
+
+    // PKCE is required, and only with S256 since a plain challenge leaks the verifier
+    let code_challenge = match (
+        request.code_challenge.as_deref(),
+        request.code_challenge_method.as_deref(),
+    ) {
+        (Some(code_challenge), Some("S256")) => code_challenge.to_owned(),
+        _ => return redirect_to_client(&redirect_uri, "error", "invalid_request", &request),
+    };
+
+    let claims = match authenticated_claims(&jar, &state).await {
+        Ok(claims) => claims,
+        Err(AuthAPIError::MissingToken | AuthAPIError::InvalidToken) => {
+            let return_to = uri
+                .path_and_query()
+                .map(|path| path.as_str())
+                .unwrap_or("/");
+            let query = serde_urlencoded::to_string([("return_to", return_to)])
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            return Ok(Redirect::to(&format!("/?{}", query)));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let granted_scope = scope
+        .split_whitespace()
+        .filter(|scope| SUPPORTED_SCOPES.contains(scope))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let grant = AuthorizationGrant {
+        client_id,
+        redirect_uri: redirect_uri.clone(),
+        email: Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?,
+        session_id: SessionId::parse(&claims.sid).map_err(|_| AuthAPIError::InvalidToken)?,
+        scope: granted_scope,
+        code_challenge,
+        nonce: request.nonce.clone(),
+    };
+    let code = AuthorizationCode::default();
+
+    state
+        .authorization_code_store
+        .write()
+        .await
+        .add_code(code.clone(), grant)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    redirect_to_client(&redirect_uri, "code", code.as_ref(), &request)
+}
+
+// Send the user back to the client with either a code or an error, echoing its state
+fn redirect_to_client(
+    redirect_uri: &RedirectUri,
+    name: &str,
+    value: &str,
+    request: &AuthorizeRequest,
+) -> Result<Redirect, AuthAPIError> {
+    let mut params = vec![(name, value)];
+    if let Some(state) = request.state.as_deref() {
+        params.push(("state", state));
+    }
+
+    let query = serde_urlencoded::to_string(params).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Redirect::to(&redirect_uri.with_query(&query)))
+}
+
+// Every parameter is optional so that a missing one can be reported to the client
+// through its redirect URI, rather than rejected before the handler runs
+#[derive(Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
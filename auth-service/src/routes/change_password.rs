@@ -0,0 +1,86 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password},
+    routes::{start_session, verify_password},
+    utils::{
+        auth::{revoke_all_tokens, validate_token},
+        constants::JWT_COOKIE_NAME,
+    },
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+pub async fn change_password(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let auth_cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+    let token = auth_cookie.value().to_owned();
+
+    let claims = validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+    let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let (Ok(current_password), Ok(new_password)) = (
+        Password::parse(&request.current_password),
+        Password::parse(&request.new_password),
+    ) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+
+    // The current password is checked under a read lock so other requests are not held up
+    verify_password(
+        &email,
+        &current_password,
+        address.ip(),
+        &*state.user_store.read().await,
+        &state,
+    )
+    .await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, new_password)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Sign out every session, including this one, then start a fresh one for the caller
+    revoke_all_tokens(&email, &state).await?;
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .add_token(token)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let jar = start_session(&email, &headers, address, &state, jar).await?;
+
+    Ok((jar, StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
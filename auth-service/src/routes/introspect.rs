@@ -0,0 +1,189 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, SessionId},
+    utils::auth::{
+        authenticate_client, inspect_token, validate_access_token, validate_client_token,
+    },
+};
+use axum::{
+    extract::{MatchedPath, State},
+    http::HeaderMap,
+    response::IntoResponse,
+    Form, Json,
+};
+use serde::{Deserialize, Serialize};
+
+// RFC 7662 token introspection for resource servers. Any token that fails
+// validation, including refresh tokens, is simply reported as inactive, as are
+// tokens issued to other clients. Tokens from the user's browser session are not
+// issued to any client, so every client may look them up.
+pub async fn introspect(
+    State(state): State<AppState>,
+    route: MatchedPath,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = authenticate_client(
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+        &route,
+        &state,
+    )
+    .await?;
+
+    // Looking a token up does not count as activity on its session
+    if let Ok(claims) = validate_access_token(
+        &request.token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+    )
+    .await
+    {
+        if claims.client_id != client_id.as_ref() {
+            return Ok(Json(IntrospectResponse::inactive()));
+        }
+        return introspect_user_token(
+            TokenDetails {
+                scope: Some(claims.scope),
+                client_id: Some(claims.client_id),
+                token_type: "Bearer",
+                sub: claims.sub,
+                exp: claims.exp,
+                iat: claims.iat,
+                sid: Some(claims.sid),
+                session_created_at: None,
+                session_last_seen: None,
+            },
+            &state,
+        )
+        .await;
+    }
+
+    if let Ok(claims) = inspect_token(
+        &request.token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+    )
+    .await
+    {
+        return introspect_user_token(
+            TokenDetails {
+                scope: None,
+                client_id: None,
+                token_type: "Bearer",
+                sub: claims.sub,
+                exp: claims.exp,
+                iat: claims.iat,
+                sid: Some(claims.sid),
+                session_created_at: None,
+                session_last_seen: None,
+            },
+            &state,
+        )
+        .await;
+    }
+
+    let claims = match validate_client_token(
+        &request.token,
+        state.banned_token_store.clone(),
+        state.client_store.clone(),
+    )
+    .await
+    {
+        Ok(claims) if claims.client_id == client_id.as_ref() => claims,
+        _ => return Ok(Json(IntrospectResponse::inactive())),
+    };
+
+    Ok(Json(IntrospectResponse {
+        active: true,
+        details: Some(TokenDetails {
+            scope: Some(claims.scope),
+            client_id: Some(claims.client_id),
+            token_type: "Bearer",
+            sub: claims.sub,
+            exp: claims.exp,
+            iat: claims.iat,
+            sid: None,
+            session_created_at: None,
+            session_last_seen: None,
+        }),
+    }))
+}
+
+// Add the session to the details of an access token issued for it
+async fn introspect_user_token(
+    details: TokenDetails,
+    state: &AppState,
+) -> Result<Json<IntrospectResponse>, AuthAPIError> {
+    let session_id = details
+        .sid
+        .as_deref()
+        .and_then(|sid| SessionId::parse(sid).ok())
+        .ok_or(AuthAPIError::UnexpectedError)?;
+    let session = match state
+        .session_store
+        .read()
+        .await
+        .get_session(&session_id)
+        .await
+    {
+        Ok(session) => session,
+        // The session was revoked after the token was validated
+        Err(_) => return Ok(Json(IntrospectResponse::inactive())),
+    };
+
+    Ok(Json(IntrospectResponse {
+        active: true,
+        details: Some(TokenDetails {
+            session_created_at: Some(session.created_at),
+            session_last_seen: Some(session.last_seen),
+            ..details
+        }),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct IntrospectRequest {
+    // Any token_type_hint is ignored, since every token is looked up the same way
+    pub token: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+// Field names follow RFC 7662 rather than the camelCase used elsewhere in the API
+#[derive(Debug, Serialize)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub details: Option<TokenDetails>,
+}
+
+impl IntrospectResponse {
+    fn inactive() -> Self {
+        Self {
+            active: false,
+            details: None,
+        }
+    }
+}
+
+// Session fields are only present for tokens issued for a user's session, and scope
+// and client_id only for tokens issued to a client
+#[derive(Debug, Serialize)]
+pub struct TokenDetails {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub token_type: &'static str,
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_created_at: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_last_seen: Option<usize>,
+}
@@ -0,0 +1,8 @@
+use crate::utils::key_ring::key_ring;
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+
+// Publish the public signing keys so resource servers can verify JWTs themselves
+pub async fn jwks() -> impl IntoResponse {
+    (StatusCode::OK, Json(key_ring().jwks(Utc::now())))
+}
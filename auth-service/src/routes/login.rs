@@ -1,14 +1,38 @@
 use crate::{
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode, UserStoreError},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, EmailMessage, FailedLoginKey, LoginAttemptBinding, LoginAttemptId,
+        Password, TwoFAChallenge, TwoFACode, TwoFAMethod, UserStore, UserStoreError,
+    },
+    routes::start_session,
+    utils::{
+        auth::{LOGIN_ATTEMPT_TTL_SECONDS, MAX_LOGIN_LOCKOUT_SECONDS},
+        constants::{
+            ALLOW_UNVERIFIED_LOGIN, LOGIN_ACCOUNT_LOCKOUT_THRESHOLD, LOGIN_ATTEMPT_COOKIE_NAME,
+            LOGIN_IP_LOCKOUT_THRESHOLD, LOGIN_LOCKOUT_SECONDS,
+        },
+        email_templates::two_fa_code_email,
+    },
     AppState,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use axum_extra::extract::CookieJar;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use time::Duration;
 
 pub async fn login(
     State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
@@ -18,30 +42,169 @@ pub async fn login(
 
     let user_store = state.user_store.read().await;
 
-    user_store
-        .validate_user(&email, &password)
-        .await
-        .map_err(|e| match e {
-            UserStoreError::UserNotFound => AuthAPIError::IncorrectCredentials,
-            UserStoreError::InvalidCredentials => AuthAPIError::IncorrectCredentials,
-            _ => AuthAPIError::UnexpectedError,
-        })?;
+    verify_password(&email, &password, address.ip(), &*user_store, &state).await?;
 
     let user = user_store.get_user(&email).await.map_err(|e| match e {
         UserStoreError::UserNotFound => AuthAPIError::IncorrectCredentials,
         _ => AuthAPIError::UnexpectedError,
     })?;
 
-    let auth_cookie =
-        generate_auth_cookie(&user.email).map_err(|_| AuthAPIError::UnexpectedError)?;
+    // Checked after the password so the setting is not revealed to anyone guessing
+    if user.password_login_disabled {
+        return Err(AuthAPIError::PasswordLoginDisabled);
+    }
 
-    let jar = jar.add(auth_cookie);
+    if !user.email_verified && !*ALLOW_UNVERIFIED_LOGIN {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+    drop(user_store);
 
     // Handle request based on user's 2FA configuration
-    match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    match user.two_fa_method {
+        TwoFAMethod::Disabled => {
+            let jar = start_session(&user.email, &headers, address, &state, jar).await?;
+            Ok((jar, (StatusCode::OK, Json(LoginResponse::RegularAuth))))
+        }
+        method => handle_2fa(&user.email, method, &state, jar).await,
+    }
+}
+
+// Check the user's password, counting wrong guesses towards the account lockout. Routes
+// that ask for the password go through here, so they all share one lockout.
+pub(crate) async fn verify_password(
+    email: &Email,
+    password: &Password,
+    ip: IpAddr,
+    user_store: &(dyn UserStore + Send + Sync),
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let failed_login_keys = [
+        FailedLoginKey::Account(email.clone()),
+        FailedLoginKey::AccountFromIp(email.clone(), ip),
+    ];
+
+    // Refuse before running the password hash, which is what makes guessing expensive
+    check_lockout(&failed_login_keys, state).await?;
+
+    match user_store.validate_user(email, password).await {
+        Ok(()) => clear_failed_logins(&failed_login_keys, state).await,
+        Err(e @ (UserStoreError::UserNotFound | UserStoreError::InvalidCredentials)) => {
+            // Unknown emails are counted too, so lockouts do not reveal who has an account
+            let user_exists = e == UserStoreError::InvalidCredentials;
+            record_failed_login(email, user_exists, &failed_login_keys, state).await?;
+            Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+// The failed logins allowed for the key before it is locked
+fn lockout_threshold(key: &FailedLoginKey) -> u32 {
+    match key {
+        FailedLoginKey::Account(_) => *LOGIN_ACCOUNT_LOCKOUT_THRESHOLD,
+        FailedLoginKey::AccountFromIp(..) => *LOGIN_IP_LOCKOUT_THRESHOLD,
+    }
+}
+
+fn now() -> Result<usize, AuthAPIError> {
+    Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+async fn check_lockout(keys: &[FailedLoginKey], state: &AppState) -> Result<(), AuthAPIError> {
+    let failed_login_store = state.failed_login_store.read().await;
+    let now = now()?;
+
+    for key in keys {
+        let locked_until = failed_login_store
+            .get_failures(key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?
+            .locked_until(
+                lockout_threshold(key),
+                *LOGIN_LOCKOUT_SECONDS,
+                MAX_LOGIN_LOCKOUT_SECONDS,
+            );
+
+        match locked_until {
+            Some(locked_until) if locked_until > now => {
+                return Err(AuthAPIError::AccountLocked((locked_until - now) as u64))
+            }
+            _ => (),
+        }
     }
+
+    Ok(())
+}
+
+async fn record_failed_login(
+    email: &Email,
+    user_exists: bool,
+    keys: &[FailedLoginKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let now = now()?;
+    let mut locked_until = None;
+
+    let mut failed_login_store = state.failed_login_store.write().await;
+    for key in keys {
+        let failures = failed_login_store
+            .record_failure(key, now)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        // Only the failure that reaches the threshold starts a new lockout
+        if failures.count == lockout_threshold(key) {
+            locked_until = failures.locked_until(
+                lockout_threshold(key),
+                *LOGIN_LOCKOUT_SECONDS,
+                MAX_LOGIN_LOCKOUT_SECONDS,
+            );
+        }
+    }
+    drop(failed_login_store);
+
+    match locked_until {
+        Some(locked_until) if user_exists => {
+            let minutes = (locked_until - now).div_ceil(60);
+            let content = format!(
+                "Your account has been locked for {} minute(s) after too many failed sign in attempts. \
+                If this was not you, consider resetting your password.",
+                minutes
+            );
+
+            state
+                .email_outbox
+                .write()
+                .await
+                .enqueue(
+                    email,
+                    &EmailMessage::plain("Your Account Has Been Locked", &content),
+                )
+                .await
+                .map(|_| ())
+                .map_err(|_| AuthAPIError::UnexpectedError)
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn clear_failed_logins(
+    keys: &[FailedLoginKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let mut failed_login_store = state.failed_login_store.write().await;
+
+    for key in keys {
+        failed_login_store
+            .clear_failures(key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
 }
 
 #[derive(Deserialize)]
@@ -52,33 +215,16 @@ pub struct LoginRequest {
 
 async fn handle_2fa(
     email: &Email,
+    two_fa_method: TwoFAMethod,
     state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
-    let login_attempt_id = LoginAttemptId::default();
-    let two_fa_code = TwoFACode::default();
-
-    state
-        .two_fa_code_store
-        .write()
-        .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
-        .await
-        .map_err(|_| AuthAPIError::UnexpectedError)?;
-
-    state
-        .email_client
-        .send_email(
-            &email,
-            "Your Login Authentication Code",
-            two_fa_code.as_ref(),
-        )
-        .await
-        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let (jar, login_attempt_id) = start_2fa(email, two_fa_method, state, jar).await?;
 
     let auth_response = TwoFactorAuthResponse {
         message: "2FA required".to_string(),
         login_attempt_id: login_attempt_id.to_string(),
+        two_fa_method,
     };
 
     let response = (
@@ -88,12 +234,75 @@ async fn handle_2fa(
     Ok((jar, response))
 }
 
-async fn handle_no_2fa(
-    _email: &Email,
+// Record a login attempt that must be finished at /verify-2fa by the same client,
+// emailing the code to users who receive it that way
+pub(crate) async fn start_2fa(
+    email: &Email,
+    two_fa_method: TwoFAMethod,
+    state: &AppState,
     jar: CookieJar,
-) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
-    let response = (StatusCode::OK, Json(LoginResponse::RegularAuth));
-    Ok((jar, response))
+) -> Result<(CookieJar, LoginAttemptId), AuthAPIError> {
+    let (jar, client_binding) = login_attempt_binding(jar);
+    let login_attempt_id = LoginAttemptId::default();
+    // TOTP and passkey users never see this code, but the login attempt still has
+    // to be recorded
+    let two_fa_code = TwoFACode::default();
+    let challenge = TwoFAChallenge {
+        email: email.clone(),
+        code: two_fa_code.clone(),
+        client_binding,
+    };
+
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .add_code(login_attempt_id.clone(), challenge, now()?)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if two_fa_method == TwoFAMethod::Email {
+        state
+            .email_outbox
+            .write()
+            .await
+            .enqueue(
+                email,
+                &two_fa_code_email("Your Login Authentication Code", &two_fa_code)
+                    .map_err(|_| AuthAPIError::UnexpectedError)?,
+            )
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok((jar, login_attempt_id))
+}
+
+// The value in the client's login attempt cookie, which is set when it has none. Every
+// login attempt the client starts shares it, so they can run side by side.
+pub(crate) fn login_attempt_binding(jar: CookieJar) -> (CookieJar, LoginAttemptBinding) {
+    let client_binding = jar
+        .get(LOGIN_ATTEMPT_COOKIE_NAME)
+        .and_then(|cookie| LoginAttemptBinding::parse(cookie.value()).ok())
+        .unwrap_or_default();
+
+    let cookie = Cookie::build((
+        LOGIN_ATTEMPT_COOKIE_NAME,
+        client_binding.as_ref().to_owned(),
+    ))
+    .path("/")
+    .http_only(true)
+    .same_site(SameSite::Lax)
+    .max_age(Duration::seconds(LOGIN_ATTEMPT_TTL_SECONDS))
+    .build();
+
+    (jar.add(cookie), client_binding)
+}
+
+// Whether the request comes from the client that started the login attempt
+pub(crate) fn is_same_client(challenge: &TwoFAChallenge, jar: &CookieJar) -> bool {
+    jar.get(LOGIN_ATTEMPT_COOKIE_NAME)
+        .is_some_and(|cookie| cookie.value() == challenge.client_binding.as_ref())
 }
 
 #[derive(Debug, Serialize)]
@@ -108,4 +317,6 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    #[serde(rename = "2FAMethod")]
+    pub two_fa_method: TwoFAMethod,
 }
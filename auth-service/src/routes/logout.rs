@@ -1,7 +1,10 @@
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionId, SessionStoreError},
+    utils::{
+        auth::{authenticated_email, revoke_all_tokens, validate_token},
+        constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    },
 };
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::CookieJar;
@@ -13,11 +16,46 @@ pub async fn logout(
     let auth_cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
     let token = auth_cookie.value().to_owned();
 
-    let _claim = validate_token(&token, state.banned_token_store.clone())
+    let claims = validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+    let session_id = SessionId::parse(&claims.sid).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    match state
+        .session_store
+        .write()
         .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+        .remove_session(&session_id)
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => (),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    // The refresh token would otherwise be able to mint a new JWT
+    if let Some(refresh_token) = jar
+        .get(REFRESH_TOKEN_COOKIE_NAME)
+        .and_then(|cookie| RefreshToken::parse(cookie.value()).ok())
+    {
+        match state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_family(&refresh_token)
+            .await
+        {
+            Ok(()) | Err(RefreshTokenStoreError::TokenNotFound) => (),
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        }
+    }
 
-    let jar = jar.remove(JWT_COOKIE_NAME);
+    let jar = jar
+        .remove(JWT_COOKIE_NAME)
+        .remove(REFRESH_TOKEN_COOKIE_NAME);
     state
         .banned_token_store
         .write()
@@ -28,3 +66,19 @@ pub async fn logout(
 
     Ok((jar, StatusCode::OK))
 }
+
+// Sign the user out of every session, not just the one making the request
+pub async fn logout_all(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    revoke_all_tokens(&email, &state).await?;
+
+    let jar = jar
+        .remove(JWT_COOKIE_NAME)
+        .remove(REFRESH_TOKEN_COOKIE_NAME);
+
+    Ok((jar, StatusCode::OK))
+}
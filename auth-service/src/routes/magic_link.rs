@@ -0,0 +1,172 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailMessage, MagicLinkId, MagicLinkStoreError, TwoFAMethod,
+        UserStoreError,
+    },
+    routes::{claim_unverified_user, start_2fa, start_session},
+    utils::{
+        auth::{decode_magic_link_token, generate_magic_link_token},
+        constants::AUTH_SERVICE_URL,
+    },
+};
+use askama::Template;
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect},
+    Form, Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+// Emails the user a link that signs them in without their password
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Respond the same way whether or not the user exists to avoid leaking registered emails
+    let response = (
+        StatusCode::OK,
+        Json(MagicLinkResponse {
+            message: "If the account exists, a sign in link has been sent".to_string(),
+        }),
+    );
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => (),
+        Err(UserStoreError::UserNotFound) => return Ok(response),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let link_id = MagicLinkId::default();
+
+    state
+        .magic_link_store
+        .write()
+        .await
+        .add_link(link_id.clone(), email.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let token =
+        generate_magic_link_token(&email, &link_id).map_err(|_| AuthAPIError::UnexpectedError)?;
+    let query = serde_urlencoded::to_string(MagicLinkCallbackRequest { token })
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let link = format!(
+        "{}/login/magic-link/callback?{}",
+        AUTH_SERVICE_URL.as_str(),
+        query
+    );
+
+    state
+        .email_outbox
+        .write()
+        .await
+        .enqueue(&email, &EmailMessage::plain("Your Sign In Link", &link))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(response)
+}
+
+// Where the emailed link leads. Mail scanners and link previews follow links too, so
+// this only asks the user to confirm, and the link is used once they do.
+pub async fn magic_link_page(
+    Query(request): Query<MagicLinkCallbackRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    decode_magic_link_token(&request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let page = MagicLinkPage {
+        token: &request.token,
+    }
+    .render()
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Html(page))
+}
+
+// Posted from the confirmation page. Signs the user in and sends them to the login page.
+pub async fn magic_link_callback(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    jar: CookieJar,
+    Form(request): Form<MagicLinkCallbackRequest>,
+) -> Result<(CookieJar, Redirect), AuthAPIError> {
+    // The signature and expiry are checked before the link is looked up
+    let claims = decode_magic_link_token(&request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let link_id = MagicLinkId::parse(&claims.jti).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = match state
+        .magic_link_store
+        .write()
+        .await
+        .take_link(&link_id)
+        .await
+    {
+        Ok(email) if email.as_ref() == claims.sub => email,
+        Ok(_) | Err(MagicLinkStoreError::LinkNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    // Following the link proves the user can read mail sent to the address
+    let user = match user.email_verified {
+        true => user,
+        false => claim_unverified_user(user, &state).await?,
+    };
+
+    // The link stands in for the password only, so 2FA is still required.
+    // The login page picks the attempt up from the query and asks for the code.
+    match user.two_fa_method {
+        TwoFAMethod::Disabled => {
+            let jar = start_session(&user.email, &headers, address, &state, jar).await?;
+            Ok((jar, Redirect::to("/")))
+        }
+        method => {
+            let (jar, login_attempt_id) = start_2fa(&user.email, method, &state, jar).await?;
+            let query = serde_urlencoded::to_string([
+                ("email", user.email.as_ref()),
+                ("login_attempt_id", login_attempt_id.as_ref()),
+            ])
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            Ok((jar, Redirect::to(&format!("/?{}", query))))
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "pages/magic_link.html")]
+struct MagicLinkPage<'a> {
+    token: &'a str,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MagicLinkCallbackRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct MagicLinkResponse {
+    pub message: String,
+}
@@ -0,0 +1,258 @@
+use std::net::SocketAddr;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, OidcLogin, OidcLoginState, OidcLoginStoreError, Password, TwoFAMethod,
+        User, UserStoreError,
+    },
+    routes::{start_2fa, start_session},
+    services::OidcProviderError,
+    utils::{
+        auth::{revoke_all_tokens, OIDC_LOGIN_TTL_SECONDS},
+        constants::OIDC_STATE_COOKIE_NAME,
+    },
+};
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Redirect},
+    Json,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use serde::{Deserialize, Serialize};
+use time::Duration;
+
+const OIDC_STATE_COOKIE_PATH: &str = "/oidc";
+
+// List the upstream identity providers users can sign in with
+pub async fn oidc_providers(State(state): State<AppState>) -> impl IntoResponse {
+    let providers = state
+        .oidc_providers
+        .names()
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+
+    Json(OidcProvidersResponse { providers })
+}
+
+// Send the user to an upstream identity provider to sign in
+pub async fn oidc_login(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    jar: CookieJar,
+    Query(request): Query<OidcLoginRequest>,
+) -> Result<(CookieJar, Redirect), AuthAPIError> {
+    let provider = state
+        .oidc_providers
+        .get(&provider)
+        .ok_or(AuthAPIError::IdentityProviderNotFound)?;
+
+    // Only /authorize is accepted, as on the login page, so this cannot be used
+    // to redirect anywhere else
+    let return_to = request
+        .return_to
+        .filter(|return_to| return_to.starts_with("/authorize?"));
+    let login = OidcLogin::new(provider.name().to_owned(), return_to);
+    let login_state = OidcLoginState::default();
+
+    let authorization_url = provider
+        .authorization_url(&login_state, &login)
+        .await
+        .map_err(provider_error)?;
+
+    state
+        .oidc_login_store
+        .write()
+        .await
+        .add_login(login_state.clone(), login)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let cookie = Cookie::build((OIDC_STATE_COOKIE_NAME, login_state.as_ref().to_owned()))
+        .path(OIDC_STATE_COOKIE_PATH)
+        .http_only(true)
+        // Lax, so the cookie comes back with the provider's top-level redirect
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(OIDC_LOGIN_TTL_SECONDS))
+        .build();
+
+    Ok((jar.add(cookie), Redirect::to(&authorization_url)))
+}
+
+// Finish a login at an upstream identity provider and sign the user in to the
+// account with the email it verified, creating one if there is none
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(provider): Path<String>,
+    jar: CookieJar,
+    Query(request): Query<OidcCallbackRequest>,
+) -> Result<(CookieJar, Redirect), AuthAPIError> {
+    let login_state = request
+        .state
+        .as_deref()
+        .and_then(|login_state| OidcLoginState::parse(login_state).ok())
+        .ok_or(AuthAPIError::UpstreamLoginFailed)?;
+
+    // The state must match the cookie set when this browser started the login, so
+    // a callback URL for someone else's login cannot sign it in
+    let cookie_state = jar
+        .get(OIDC_STATE_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+    if cookie_state.as_deref() != Some(login_state.as_ref()) {
+        return Err(AuthAPIError::UpstreamLoginFailed);
+    }
+    let jar = jar.remove(Cookie::build(OIDC_STATE_COOKIE_NAME).path(OIDC_STATE_COOKIE_PATH));
+
+    let login = match state
+        .oidc_login_store
+        .write()
+        .await
+        .take_login(&login_state)
+        .await
+    {
+        Ok(login) if login.provider == provider => login,
+        Ok(_) | Err(OidcLoginStoreError::LoginNotFound) => {
+            return Err(AuthAPIError::UpstreamLoginFailed)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let provider = state
+        .oidc_providers
+        .get(&provider)
+        .ok_or(AuthAPIError::IdentityProviderNotFound)?;
+
+    // The provider sends an error instead of a code when the user cancels or is refused
+    let code = request.code.ok_or(AuthAPIError::UpstreamLoginFailed)?;
+    let identity = provider
+        .exchange_code(&code, &login)
+        .await
+        .map_err(provider_error)?;
+
+    if !identity.email_verified {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+    let email = identity
+        .email
+        .as_deref()
+        .and_then(|email| Email::parse(email).ok())
+        .ok_or(AuthAPIError::UpstreamLoginFailed)?;
+
+    let user = link_user(&email, &state).await?;
+    let return_to = login.return_to.unwrap_or_else(|| "/".to_owned());
+
+    // The provider stands in for the password only, so 2FA is still required.
+    // The login page picks the attempt up from the query and asks for the code.
+    match user.two_fa_method {
+        TwoFAMethod::Disabled => {
+            let jar = start_session(&user.email, &headers, address, &state, jar).await?;
+            Ok((jar, Redirect::to(&return_to)))
+        }
+        method => {
+            let (jar, login_attempt_id) = start_2fa(&user.email, method, &state, jar).await?;
+            let query = serde_urlencoded::to_string([
+                ("email", user.email.as_ref()),
+                ("login_attempt_id", login_attempt_id.as_ref()),
+                ("return_to", &return_to),
+            ])
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            Ok((jar, Redirect::to(&format!("/?{}", query))))
+        }
+    }
+}
+
+// Hand an account over to whoever proved they own its email. Whoever signed up with it
+// never did, so anything they set up is discarded first and their sessions are ended.
+pub(crate) async fn claim_unverified_user(
+    mut user: User,
+    state: &AppState,
+) -> Result<User, AuthAPIError> {
+    let email = user.email.clone();
+    let mut user_store = state.user_store.write().await;
+
+    user_store
+        .update_password(&email, Password::default())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    user_store
+        .set_two_fa_method(&email, TwoFAMethod::Disabled, None)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    user_store
+        .remove_passkeys(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    user_store
+        .set_recovery_codes(&email, Vec::new())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    user_store
+        .set_email_verified(&email, true)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+
+    revoke_all_tokens(&email, state).await?;
+
+    user.two_fa_method = TwoFAMethod::Disabled;
+    user.email_verified = true;
+    Ok(user)
+}
+
+// Find the user with the email the provider verified, or create one with a
+// password nobody knows
+async fn link_user(email: &Email, state: &AppState) -> Result<User, AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+
+    match user_store.get_user(email).await {
+        Ok(user) if user.email_verified => Ok(user),
+        Ok(user) => {
+            drop(user_store);
+            claim_unverified_user(user, state).await
+        }
+        Err(UserStoreError::UserNotFound) => {
+            let mut user = User::new(email.clone(), Password::default(), TwoFAMethod::Disabled);
+            user.email_verified = true;
+
+            user_store
+                .add_user(user.clone(), None)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            Ok(user)
+        }
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+fn provider_error(e: OidcProviderError) -> AuthAPIError {
+    match e {
+        OidcProviderError::InvalidIdToken(_) => AuthAPIError::UpstreamLoginFailed,
+        OidcProviderError::InvalidConfig(_) | OidcProviderError::RequestFailed(_) => {
+            AuthAPIError::UnexpectedError
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OidcLoginRequest {
+    pub return_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: Option<String>,
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OidcProvidersResponse {
+    pub providers: Vec<String>,
+}
@@ -0,0 +1,63 @@
+use crate::utils::{constants::AUTH_SERVICE_URL, key_ring::key_ring};
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::Serialize;
+
+use super::authorize::SUPPORTED_SCOPES;
+
+// OpenID Connect discovery document, so clients can configure themselves from the issuer URL
+pub async fn openid_configuration() -> Result<impl IntoResponse, StatusCode> {
+    let issuer = AUTH_SERVICE_URL.as_str();
+    let signing_algorithm = key_ring()
+        .signing_key(Utc::now())
+        .map(|signing_key| format!("{:?}", signing_key.algorithm))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(OpenIdConfiguration {
+        issuer: issuer.to_owned(),
+        authorization_endpoint: format!("{issuer}/authorize"),
+        token_endpoint: format!("{issuer}/token"),
+        userinfo_endpoint: format!("{issuer}/userinfo"),
+        jwks_uri: format!("{issuer}/.well-known/jwks.json"),
+        introspection_endpoint: format!("{issuer}/introspect"),
+        revocation_endpoint: format!("{issuer}/revoke"),
+        scopes_supported: SUPPORTED_SCOPES.to_vec(),
+        response_types_supported: vec!["code"],
+        grant_types_supported: vec!["authorization_code", "client_credentials"],
+        subject_types_supported: vec!["public"],
+        id_token_signing_alg_values_supported: vec![signing_algorithm],
+        token_endpoint_auth_methods_supported: vec!["client_secret_basic", "client_secret_post"],
+        code_challenge_methods_supported: vec!["S256"],
+        claims_supported: vec![
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "sid",
+            "email",
+            "email_verified",
+        ],
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub scopes_supported: Vec<&'static str>,
+    pub response_types_supported: Vec<&'static str>,
+    pub grant_types_supported: Vec<&'static str>,
+    pub subject_types_supported: Vec<&'static str>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<&'static str>,
+    pub code_challenge_methods_supported: Vec<&'static str>,
+    pub claims_supported: Vec<&'static str>,
+}
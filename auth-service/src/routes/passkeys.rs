@@ -0,0 +1,528 @@
+use std::net::SocketAddr;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthenticationCredential, Email, EmailMessage, LoginAttemptId, Passkey,
+        PasskeyCeremony, PasskeyChallenge, PasskeyChallengeStoreError, PasskeyError,
+        RegistrationCredential, RelyingParty, TwoFAMethod, UserStore, UserStoreError,
+    },
+    routes::{
+        is_same_client, issue_recovery_codes, reauthenticate, start_session, two_fa_changed_email,
+        SecondFactorRequest,
+    },
+    utils::{
+        auth::{authenticated_email, PASSKEY_CHALLENGE_TTL_SECONDS},
+        constants::{ALLOW_UNVERIFIED_LOGIN, AUTH_SERVICE_URL},
+    },
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const PASSKEY_RP_NAME: &str = "auth-service";
+
+// Issue a challenge for adding a passkey to the signed in user's account, once they
+// have confirmed their password and current second factor. Finishing needs the
+// challenge, so it too can only happen shortly after re-authenticating.
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<StartPasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+    let relying_party = relying_party()?;
+
+    reauthenticate(
+        &email,
+        &request.password,
+        request.second_factor,
+        &jar,
+        address.ip(),
+        &state,
+    )
+    .await?;
+
+    let passkeys = state
+        .user_store
+        .read()
+        .await
+        .get_passkeys(&email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    let challenge = add_challenge(
+        PasskeyCeremony::Registration {
+            email: email.as_ref().to_owned(),
+        },
+        &state,
+    )
+    .await?;
+
+    let options = PasskeyCreationOptions {
+        challenge: challenge.as_ref().to_owned(),
+        rp: RelyingPartyEntity {
+            id: relying_party.id,
+            name: PASSKEY_RP_NAME.to_owned(),
+        },
+        // A fresh handle each time, as it is the credential id rather than the
+        // handle that identifies the owner of a passkey
+        user: UserEntity {
+            id: BASE64_URL.encode(Uuid::new_v4().as_bytes()),
+            name: email.as_ref().to_owned(),
+            display_name: email.as_ref().to_owned(),
+        },
+        pub_key_cred_params: vec![CredentialParameters {
+            credential_type: "public-key",
+            alg: -7,
+        }],
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        // Stops the same authenticator being registered twice
+        exclude_credentials: credential_descriptors(&passkeys),
+        authenticator_selection: AuthenticatorSelection {
+            resident_key: "preferred",
+            user_verification: "preferred",
+        },
+        attestation: "none",
+    };
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+// Save the passkey an authenticator created for a registration challenge
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(credential): Json<RegistrationCredential>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let challenge = credential.challenge().map_err(passkey_error)?;
+    let ceremony = take_challenge(&challenge, &state).await?;
+    if ceremony
+        != (PasskeyCeremony::Registration {
+            email: email.as_ref().to_owned(),
+        })
+    {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let passkey = relying_party()?
+        .verify_registration(&challenge, &credential)
+        .map_err(passkey_error)?;
+    let credential_id = passkey.credential_id.clone();
+
+    // Let the user know in case it was not them
+    let notification = EmailMessage::plain(
+        "A Passkey Was Added To Your Account",
+        "A new passkey can now be used to sign in to your account.",
+    );
+
+    state
+        .user_store
+        .write()
+        .await
+        .add_passkey(&email, passkey, Some(&notification))
+        .await
+        .map_err(|e| match e {
+            UserStoreError::PasskeyAlreadyExists => AuthAPIError::PasskeyAlreadyRegistered,
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RegisterPasskeyResponse { credential_id }),
+    ))
+}
+
+// Issue a challenge for signing in with a passkey. Without an email any passkey
+// the authenticator holds may answer, while with an email and login attempt the
+// challenge is for the second factor of that attempt.
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<StartPasskeyLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let relying_party = relying_party()?;
+
+    let (ceremony, passkeys) = match (request.email, request.login_attempt_id) {
+        (None, None) => (PasskeyCeremony::Login, Vec::new()),
+        (Some(email), Some(login_attempt_id)) => {
+            let (Ok(email), Ok(login_attempt_id)) = (
+                Email::parse(&email),
+                LoginAttemptId::parse(&login_attempt_id),
+            ) else {
+                return Err(AuthAPIError::InvalidCredentials);
+            };
+
+            match state
+                .two_fa_code_store
+                .read()
+                .await
+                .get_code(&login_attempt_id)
+                .await
+            {
+                Ok(challenge) if challenge.email == email && is_same_client(&challenge, &jar) => {}
+                _ => return Err(AuthAPIError::IncorrectCredentials),
+            }
+
+            let passkeys = state
+                .user_store
+                .read()
+                .await
+                .get_passkeys(&email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            let ceremony = PasskeyCeremony::SecondFactor {
+                email: email.as_ref().to_owned(),
+                login_attempt_id: login_attempt_id.as_ref().to_owned(),
+            };
+            (ceremony, passkeys)
+        }
+        _ => return Err(AuthAPIError::InvalidCredentials),
+    };
+
+    // A passkey used in place of a password must prove who is holding it too
+    let user_verification = match ceremony {
+        PasskeyCeremony::Login => "required",
+        _ => "preferred",
+    };
+    let challenge = add_challenge(ceremony, &state).await?;
+
+    let options = PasskeyRequestOptions {
+        challenge: challenge.as_ref().to_owned(),
+        rp_id: relying_party.id,
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        allow_credentials: credential_descriptors(&passkeys),
+        user_verification,
+    };
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+// Sign in with a passkey in place of a password. The authenticator has verified
+// the user, so the passkey already counts as two factors.
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    jar: CookieJar,
+    Json(credential): Json<AuthenticationCredential>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+
+    let (ceremony, email) = verify_passkey_assertion(&credential, &mut *user_store, &state).await?;
+    if ceremony != PasskeyCeremony::Login {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+
+    if !user.email_verified && !*ALLOW_UNVERIFIED_LOGIN {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
+    let jar = start_session(&user.email, &headers, address, &state, jar).await?;
+    Ok((jar, StatusCode::OK))
+}
+
+// Require a passkey as the signed in user's second factor after checking their
+// password and current second factor, issuing a fresh set of recovery codes
+pub async fn enable_passkey_2fa(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<EnablePasskey2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user_store = state.user_store.read().await;
+
+    let user = user_store.get_user(&email).await.map_err(|e| match e {
+        UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+        _ => AuthAPIError::UnexpectedError,
+    })?;
+
+    if user.two_fa_method == TwoFAMethod::Passkey {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    let passkeys = user_store
+        .get_passkeys(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if passkeys.is_empty() {
+        return Err(AuthAPIError::PasskeyNotRegistered);
+    }
+    drop(user_store);
+
+    reauthenticate(
+        &email,
+        &request.password,
+        request.second_factor,
+        &jar,
+        address.ip(),
+        &state,
+    )
+    .await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(
+            &email,
+            TwoFAMethod::Passkey,
+            Some(&two_fa_changed_email(TwoFAMethod::Passkey)),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let recovery_codes = issue_recovery_codes(&email, &state).await?;
+
+    Ok((StatusCode::OK, Json(recovery_codes)))
+}
+
+// Issue a challenge the signed in user answers with one of their passkeys to confirm
+// it is still them, for users whose second factor is a passkey
+pub async fn start_passkey_reauthentication(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+    let relying_party = relying_party()?;
+
+    let passkeys = state
+        .user_store
+        .read()
+        .await
+        .get_passkeys(&email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+    if passkeys.is_empty() {
+        return Err(AuthAPIError::PasskeyNotRegistered);
+    }
+
+    let challenge = add_challenge(
+        PasskeyCeremony::Reauthentication {
+            email: email.as_ref().to_owned(),
+        },
+        &state,
+    )
+    .await?;
+
+    let options = PasskeyRequestOptions {
+        challenge: challenge.as_ref().to_owned(),
+        rp_id: relying_party.id,
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        allow_credentials: credential_descriptors(&passkeys),
+        user_verification: "preferred",
+    };
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+// Check an assertion against the challenge it answers, returning the ceremony the
+// challenge was issued for and the owner of the passkey that signed it. The caller
+// holds the user store lock, so it is passed in.
+pub(crate) async fn verify_passkey_assertion(
+    credential: &AuthenticationCredential,
+    user_store: &mut (dyn UserStore + Send + Sync),
+    state: &AppState,
+) -> Result<(PasskeyCeremony, Email), AuthAPIError> {
+    let challenge = credential.challenge().map_err(passkey_error)?;
+    let ceremony = take_challenge(&challenge, state).await?;
+
+    let (email, passkey) = user_store
+        .get_passkey(&credential.id)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::PasskeyNotFound => AuthAPIError::IncorrectCredentials,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    let sign_count = relying_party()?
+        .verify_authentication(
+            &challenge,
+            &passkey,
+            credential,
+            ceremony == PasskeyCeremony::Login,
+        )
+        .map_err(passkey_error)?;
+
+    user_store
+        .set_passkey_sign_count(&passkey.credential_id, sign_count)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((ceremony, email))
+}
+
+async fn add_challenge(
+    ceremony: PasskeyCeremony,
+    state: &AppState,
+) -> Result<PasskeyChallenge, AuthAPIError> {
+    let challenge = PasskeyChallenge::default();
+
+    state
+        .passkey_challenge_store
+        .write()
+        .await
+        .add_challenge(challenge.clone(), ceremony)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(challenge)
+}
+
+async fn take_challenge(
+    challenge: &PasskeyChallenge,
+    state: &AppState,
+) -> Result<PasskeyCeremony, AuthAPIError> {
+    state
+        .passkey_challenge_store
+        .write()
+        .await
+        .take_challenge(challenge)
+        .await
+        .map_err(|e| match e {
+            PasskeyChallengeStoreError::ChallengeNotFound => AuthAPIError::IncorrectCredentials,
+            PasskeyChallengeStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+        })
+}
+
+fn relying_party() -> Result<RelyingParty, AuthAPIError> {
+    RelyingParty::parse(&AUTH_SERVICE_URL).map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+fn credential_descriptors(passkeys: &[Passkey]) -> Vec<CredentialDescriptor> {
+    passkeys
+        .iter()
+        .map(|passkey| CredentialDescriptor {
+            credential_type: "public-key",
+            id: passkey.credential_id.clone(),
+        })
+        .collect()
+}
+
+fn passkey_error(e: PasskeyError) -> AuthAPIError {
+    match e {
+        PasskeyError::Malformed(_) | PasskeyError::UnsupportedKey => {
+            AuthAPIError::InvalidCredentials
+        }
+        PasskeyError::InvalidSignature
+        | PasskeyError::SignCountRegressed
+        | PasskeyError::UserNotPresent
+        | PasskeyError::UserNotVerified
+        | PasskeyError::WrongCeremony => AuthAPIError::IncorrectCredentials,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeyRegistrationRequest {
+    pub password: String,
+    #[serde(flatten)]
+    pub second_factor: Option<SecondFactorRequest>,
+}
+
+#[derive(Deserialize)]
+pub struct EnablePasskey2FARequest {
+    pub password: String,
+    #[serde(flatten)]
+    pub second_factor: Option<SecondFactorRequest>,
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    pub email: Option<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Option<String>,
+}
+
+// The JSON form of PublicKeyCredentialCreationOptions, with binary values base64url
+// encoded so that the browser can pass it to parseCreationOptionsFromJSON()
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingPartyEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: i64,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+    pub attestation: &'static str,
+}
+
+// The JSON form of PublicKeyCredentialRequestOptions
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyRequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: i64,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelyingPartyEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: &'static str,
+    // COSE algorithm, where -7 is ES256
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: &'static str,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: &'static str,
+    pub user_verification: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegisterPasskeyResponse {
+    #[serde(rename = "credentialId")]
+    pub credential_id: String,
+}
@@ -0,0 +1,98 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, EmailMessage},
+    routes::{reauthenticate, SecondFactorRequest},
+    utils::auth::authenticated_email,
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+// Stops /login from accepting the signed in user's password. They can still sign in
+// with a magic link, a passkey or an identity provider.
+pub async fn disable_password_login(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<PasswordLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    set_password_login_disabled(jar, state, address, request, true).await
+}
+
+pub async fn enable_password_login(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<PasswordLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    set_password_login_disabled(jar, state, address, request, false).await
+}
+
+// Changing how the user signs in asks for the password and current second factor, the
+// same as changing their 2FA settings, so a stolen session alone cannot do it
+async fn set_password_login_disabled(
+    jar: CookieJar,
+    state: AppState,
+    address: SocketAddr,
+    request: PasswordLoginRequest,
+    password_login_disabled: bool,
+) -> Result<(StatusCode, Json<PasswordLoginResponse>), AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    reauthenticate(
+        &email,
+        &request.password,
+        request.second_factor,
+        &jar,
+        address.ip(),
+        &state,
+    )
+    .await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_password_login_disabled(
+            &email,
+            password_login_disabled,
+            Some(&password_login_changed_email(password_login_disabled)),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasswordLoginResponse {
+        password_login_disabled,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Tell the user their sign in options changed, in case it was not them
+fn password_login_changed_email(password_login_disabled: bool) -> EmailMessage {
+    let content = match password_login_disabled {
+        true => "Your password can no longer be used to sign in.",
+        false => "Your password can be used to sign in again.",
+    };
+
+    EmailMessage::plain("Your Sign In Options Changed", content)
+}
+
+#[derive(Deserialize)]
+pub struct PasswordLoginRequest {
+    pub password: String,
+    #[serde(flatten)]
+    pub second_factor: Option<SecondFactorRequest>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct PasswordLoginResponse {
+    #[serde(rename = "passwordLoginDisabled")]
+    pub password_login_disabled: bool,
+}
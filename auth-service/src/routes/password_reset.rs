@@ -0,0 +1,114 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password, PasswordResetToken, UserStoreError},
+    utils::{auth::revoke_all_tokens, email_templates::password_reset_email},
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Respond the same way whether or not the user exists to avoid leaking registered emails
+    let response = (
+        StatusCode::OK,
+        Json(PasswordResetResponse {
+            message: "If the account exists, a password reset email has been sent".to_string(),
+        }),
+    );
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => (),
+        Err(UserStoreError::UserNotFound) => return Ok(response),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let token = PasswordResetToken::default();
+
+    state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(email.clone(), token.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_outbox
+        .write()
+        .await
+        .enqueue(
+            &email,
+            &password_reset_email(&token).map_err(|_| AuthAPIError::UnexpectedError)?,
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(response)
+}
+
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (Ok(email), Ok(token), Ok(new_password)) = (
+        Email::parse(&request.email),
+        PasswordResetToken::parse(&request.token),
+        Password::parse(&request.new_password),
+    ) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+
+    let mut password_reset_token_store = state.password_reset_token_store.write().await;
+
+    match password_reset_token_store.get_token(&email).await {
+        Ok(stored_token) if stored_token.matches(&token) => (),
+        Ok(_) | Err(_) => return Err(AuthAPIError::InvalidToken),
+    }
+
+    // The token is single-use, so consume it before changing anything else
+    password_reset_token_store
+        .remove_token(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, new_password)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    revoke_all_tokens(&email, &state).await?;
+
+    let response = Json(PasswordResetResponse {
+        message: "Password has been reset".to_string(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub email: String,
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
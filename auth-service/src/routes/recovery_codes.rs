@@ -0,0 +1,80 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, RecoveryCode},
+    routes::{reauthenticate, SecondFactorRequest},
+    utils::auth::authenticated_email,
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+// Replaces the user's recovery codes after checking the password and the current second
+// factor, since the codes stand in for it. Only hashes are stored, so this is the one
+// chance to show them.
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<RegenerateRecoveryCodesRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user = reauthenticate(
+        &email,
+        &request.password,
+        request.second_factor,
+        &jar,
+        address.ip(),
+        &state,
+    )
+    .await?;
+
+    if !user.requires_2fa() {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let response = issue_recovery_codes(&email, &state).await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+pub(crate) async fn issue_recovery_codes(
+    email: &Email,
+    state: &AppState,
+) -> Result<RecoveryCodesResponse, AuthAPIError> {
+    let recovery_codes = RecoveryCode::generate_set();
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_recovery_codes(email, recovery_codes.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(RecoveryCodesResponse {
+        recovery_codes: recovery_codes
+            .iter()
+            .map(|code| code.as_ref().to_owned())
+            .collect(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateRecoveryCodesRequest {
+    pub password: String,
+    #[serde(flatten)]
+    pub second_factor: Option<SecondFactorRequest>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,91 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionStoreError, UserStoreError,
+    },
+    utils::{
+        auth::{create_refresh_cookie, generate_auth_cookie},
+        constants::REFRESH_TOKEN_COOKIE_NAME,
+    },
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+
+// Exchange the refresh token cookie for a new JWT and a rotated refresh token
+pub async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let refresh_cookie = jar
+        .get(REFRESH_TOKEN_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?;
+    let token =
+        RefreshToken::parse(refresh_cookie.value()).map_err(|_| AuthAPIError::InvalidToken)?;
+    let new_token = RefreshToken::default();
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let family = refresh_token_store
+        .rotate_token(&token, new_token.clone())
+        .await
+        .map_err(|e| match e {
+            RefreshTokenStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+            _ => AuthAPIError::InvalidToken,
+        })?;
+
+    // Families started before the user's tokens were revoked cannot be renewed. Those
+    // from the same second are caught by the session check, as their session was ended.
+    let revoked_before = state
+        .user_store
+        .read()
+        .await
+        .get_tokens_revoked_before(&family.email)
+        .await;
+
+    let revoked = match revoked_before {
+        Ok(Some(issued_before)) => family.issued_at < issued_before,
+        Ok(None) => false,
+        Err(UserStoreError::UserNotFound) => true,
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if revoked {
+        refresh_token_store
+            .revoke_family(&new_token)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    // Revoking the session also ends its refresh token family
+    let now = Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let session = state
+        .session_store
+        .write()
+        .await
+        .touch_session(&family.session_id, now)
+        .await;
+
+    match session {
+        Ok(session) if session.email == family.email => (),
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {
+            refresh_token_store
+                .revoke_family(&new_token)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            return Err(AuthAPIError::InvalidToken);
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let auth_cookie = generate_auth_cookie(&family.email, &family.session_id)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let jar = jar.add(auth_cookie).add(create_refresh_cookie(&new_token));
+
+    Ok((jar, StatusCode::OK))
+}
@@ -0,0 +1,104 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode, TwoFAMethod, UserStoreError},
+    routes::{is_same_client, TwoFactorAuthResponse},
+    utils::{
+        auth::{MAX_TWO_FA_RESENDS, TWO_FA_RESEND_COOLDOWN_SECONDS},
+        email_templates::two_fa_code_email,
+    },
+};
+
+// Emails a new code for a login attempt whose first email went missing, so the user
+// does not have to enter their password again
+pub async fn resend_2fa(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (Ok(email), Ok(login_attempt_id)) = (
+        Email::parse(&request.email),
+        LoginAttemptId::parse(&request.login_attempt_id),
+    ) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    match two_fa_code_store.get_code(&login_attempt_id).await {
+        Ok(challenge) if challenge.email == email && is_same_client(&challenge, &jar) => (),
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    }
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::IncorrectCredentials,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    // TOTP and passkey users are never emailed a code
+    if user.two_fa_method != TwoFAMethod::Email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let resends = two_fa_code_store
+        .get_resends(&login_attempt_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if resends.count >= MAX_TWO_FA_RESENDS {
+        return Err(AuthAPIError::TooManyTwoFAResends);
+    }
+
+    let now: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let next_allowed_at = resends.next_allowed_at(TWO_FA_RESEND_COOLDOWN_SECONDS);
+    if now < next_allowed_at {
+        return Err(AuthAPIError::RateLimited((next_allowed_at - now) as u64));
+    }
+
+    let two_fa_code = TwoFACode::default();
+    two_fa_code_store
+        .resend_code(&login_attempt_id, two_fa_code.clone(), now)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(two_fa_code_store);
+
+    state
+        .email_outbox
+        .write()
+        .await
+        .enqueue(
+            &email,
+            &two_fa_code_email("Your Login Authentication Code", &two_fa_code)
+                .map_err(|_| AuthAPIError::UnexpectedError)?,
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = TwoFactorAuthResponse {
+        message: "2FA code sent".to_string(),
+        login_attempt_id: login_attempt_id.to_string(),
+        two_fa_method: TwoFAMethod::Email,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Resend2FARequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
@@ -0,0 +1,91 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RefreshToken, RefreshTokenStoreError},
+    utils::auth::{authenticate_client, validate_access_token, validate_client_token},
+};
+use axum::{
+    extract::{MatchedPath, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Form,
+};
+use serde::Deserialize;
+
+// RFC 7009 token revocation. Refresh tokens and JWTs are told apart by their
+// format, and unknown or already invalid tokens are accepted without error. So are
+// JWTs issued to another client or to a browser session, which are left alone
+// (RFC 7009 section 2.1).
+//
+// Refresh tokens are the exception to that ownership check, on purpose. They are only
+// issued to first-party browser sessions, never to a registered client, so there is no
+// client for section 2.1 to compare against and checking would make them impossible to
+// revoke. Any authenticated client presenting one may end its family: the token is a
+// bearer secret, and a caller holding it could already use it at /refresh, so revoking
+// it gives them nothing that having it did not.
+pub async fn revoke(
+    State(state): State<AppState>,
+    route: MatchedPath,
+    headers: HeaderMap,
+    Form(request): Form<RevokeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = authenticate_client(
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+        &route,
+        &state,
+    )
+    .await?;
+
+    if let Ok(refresh_token) = RefreshToken::parse(&request.token) {
+        return match state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_family(&refresh_token)
+            .await
+        {
+            Ok(()) | Err(RefreshTokenStoreError::TokenNotFound) => Ok(StatusCode::OK),
+            Err(_) => Err(AuthAPIError::UnexpectedError),
+        };
+    }
+
+    // Only valid JWTs are banned, so clients cannot fill the store with junk
+    let issued_to = match validate_access_token(
+        &request.token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+    )
+    .await
+    {
+        Ok(claims) => Some(claims.client_id),
+        Err(_) => validate_client_token(
+            &request.token,
+            state.banned_token_store.clone(),
+            state.client_store.clone(),
+        )
+        .await
+        .ok()
+        .map(|claims| claims.client_id),
+    };
+
+    if issued_to.as_deref() == Some(client_id.as_ref()) {
+        state
+            .banned_token_store
+            .write()
+            .await
+            .add_token(request.token)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct RevokeRequest {
+    // Any token_type_hint is ignored, since the token format identifies its type
+    pub token: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
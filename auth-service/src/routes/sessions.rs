@@ -0,0 +1,134 @@
+use std::net::SocketAddr;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Session, SessionId, SessionStoreError},
+    utils::auth::{
+        authenticated_claims, authenticated_email, generate_auth_cookie, generate_refresh_cookie,
+    },
+};
+use axum::{
+    extract::{Path, State},
+    http::{header::USER_AGENT, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::Serialize;
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = authenticated_claims(&jar, &state).await?;
+    let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let mut sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen));
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse::new(session, &claims.sid))
+        .collect();
+
+    Ok((StatusCode::OK, Json(SessionsResponse { sessions })))
+}
+
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+    let session_id = SessionId::parse(&id).map_err(|_| AuthAPIError::SessionNotFound)?;
+
+    let mut session_store = state.session_store.write().await;
+
+    // Sessions belonging to other users are reported as missing
+    match session_store.get_session(&session_id).await {
+        Ok(session) if session.email == email => (),
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {
+            return Err(AuthAPIError::SessionNotFound)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    session_store
+        .remove_session(&session_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+}
+
+// Record a new session for a fully authenticated user and add its auth and refresh cookies
+pub(crate) async fn start_session(
+    email: &Email,
+    headers: &HeaderMap,
+    address: SocketAddr,
+    state: &AppState,
+    jar: CookieJar,
+) -> Result<CookieJar, AuthAPIError> {
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let session = Session::new(email.clone(), user_agent, Some(address.ip().to_string()));
+    let session_id = session.id.clone();
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let auth_cookie =
+        generate_auth_cookie(email, &session_id).map_err(|_| AuthAPIError::UnexpectedError)?;
+    let refresh_cookie =
+        generate_refresh_cookie(email, &session_id, state.refresh_token_store.clone())
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(jar.add(auth_cookie).add(refresh_cookie))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: SessionId,
+    #[serde(rename = "createdAt")]
+    pub created_at: usize,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: usize,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    // Whether this is the session making the request
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_sid: &str) -> Self {
+        Self {
+            current: session.id.as_ref() == current_sid,
+            id: session.id,
+            created_at: session.created_at,
+            last_seen: session.last_seen,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::app_state::AppState;
-use crate::domain::{AuthAPIError, Email, Password, User, UserStoreError};
+use crate::domain::{
+    AuthAPIError, Email, EmailVerificationToken, Password, TwoFAMethod, User, UserStoreError,
+};
+use crate::routes::{add_verification_token, issue_recovery_codes, verification_message};
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 
@@ -17,22 +20,39 @@ pub async fn signup(
         Err(_) => return Err(AuthAPIError::InvalidCredentials),
     };
 
-    let user = User {
-        email,
-        password,
-        requires_2fa: request.requires_2fa,
+    // Authenticator apps are enrolled after signup, so signup can only opt into email codes
+    let two_fa_method = match request.requires_2fa {
+        true => TwoFAMethod::Email,
+        false => TwoFAMethod::Disabled,
     };
 
+    let user = User::new(email.clone(), password, two_fa_method);
+    let requires_2fa = user.requires_2fa();
+
+    // The verification email is queued with the new user, but the token is only stored
+    // once the user exists so a repeated signup cannot replace an existing user's token
+    let token = EmailVerificationToken::default();
+    let message = verification_message(&email, &token)?;
+
     let mut user_store = state.user_store.write().await;
 
-    match user_store.add_user(user).await {
+    match user_store.add_user(user, Some(&message)).await {
         Ok(_) => (),
         Err(UserStoreError::UserAlreadyExists) => return Err(AuthAPIError::UserAlreadyExists),
         Err(_) => return Err(AuthAPIError::UnexpectedError),
     }
+    drop(user_store);
+
+    add_verification_token(&email, token, &state).await?;
+
+    let recovery_codes = match requires_2fa {
+        true => issue_recovery_codes(&email, &state).await?.recovery_codes,
+        false => Vec::new(),
+    };
 
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
@@ -49,4 +69,10 @@ pub struct SignupRequest {
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct SignupResponse {
     pub message: String,
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub recovery_codes: Vec<String>,
 }
@@ -0,0 +1,181 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthorizationCode, AuthorizationCodeStoreError, ClientId, SessionStoreError,
+        UserStoreError,
+    },
+    utils::auth::{
+        authenticate_client, generate_access_token, generate_client_token, generate_id_token,
+        TOKEN_TTL_SECONDS,
+    },
+};
+use axum::{
+    extract::{MatchedPath, State},
+    http::{
+        header::{CACHE_CONTROL, PRAGMA},
+        HeaderMap,
+    },
+    response::IntoResponse,
+    Form, Json,
+};
+use serde::{Deserialize, Serialize};
+
+// OAuth token endpoint, where clients exchange a grant for tokens
+pub async fn token(
+    State(state): State<AppState>,
+    route: MatchedPath,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = authenticate_client(
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+        &route,
+        &state,
+    )
+    .await?;
+
+    let response = match request.grant_type.as_str() {
+        "authorization_code" => exchange_authorization_code(&client_id, &request, &state).await?,
+        "client_credentials" => issue_client_token(&client_id, &request, &state).await?,
+        _ => return Err(AuthAPIError::UnsupportedGrantType),
+    };
+
+    // Responses carrying tokens must not be cached (RFC 6749 section 5.1)
+    Ok((
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+        Json(response),
+    ))
+}
+
+// Redeem an authorization code issued by /authorize for an access token and ID token
+async fn exchange_authorization_code(
+    client_id: &ClientId,
+    request: &TokenRequest,
+    state: &AppState,
+) -> Result<TokenResponse, AuthAPIError> {
+    let code = request
+        .code
+        .as_deref()
+        .and_then(|code| AuthorizationCode::parse(code).ok())
+        .ok_or(AuthAPIError::InvalidGrant)?;
+
+    let grant = match state
+        .authorization_code_store
+        .write()
+        .await
+        .take_code(&code)
+        .await
+    {
+        Ok(grant) => grant,
+        Err(AuthorizationCodeStoreError::CodeNotFound) => return Err(AuthAPIError::InvalidGrant),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let valid = grant.client_id == *client_id
+        && request.redirect_uri.as_deref() == Some(grant.redirect_uri.as_ref())
+        && request
+            .code_verifier
+            .as_deref()
+            .is_some_and(|code_verifier| grant.verify_code_verifier(code_verifier));
+    if !valid {
+        return Err(AuthAPIError::InvalidGrant);
+    }
+
+    // The user may have signed out since approving the request
+    let session = match state
+        .session_store
+        .read()
+        .await
+        .get_session(&grant.session_id)
+        .await
+    {
+        Ok(session) if session.email == grant.email => session,
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => return Err(AuthAPIError::InvalidGrant),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let user = match state.user_store.read().await.get_user(&grant.email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidGrant),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let access_token =
+        generate_access_token(&grant.email, &grant.session_id, client_id, &grant.scope)
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let id_token = generate_id_token(&grant, session.created_at, user.email_verified)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: Some(id_token),
+        scope: grant.scope,
+    })
+}
+
+// Issue a token to the client itself, limited to the scopes it is registered for.
+// Without a scope parameter the client gets every scope it is allowed.
+async fn issue_client_token(
+    client_id: &ClientId,
+    request: &TokenRequest,
+    state: &AppState,
+) -> Result<TokenResponse, AuthAPIError> {
+    let client = state
+        .client_store
+        .read()
+        .await
+        .get_client(client_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let scope = match request.scope.as_deref() {
+        Some(scope) => {
+            if !scope
+                .split_whitespace()
+                .all(|scope| client.scopes.iter().any(|allowed| allowed == scope))
+            {
+                return Err(AuthAPIError::InvalidScope);
+            }
+            scope.split_whitespace().collect::<Vec<_>>().join(" ")
+        }
+        None => client.scopes.join(" "),
+    };
+
+    let access_token =
+        generate_client_token(client_id, &scope).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: None,
+        scope,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+// Field names follow RFC 6749 rather than the camelCase used elsewhere in the API
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    // Only issued when a user signed in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    pub scope: String,
+}
@@ -0,0 +1,152 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthenticationCredential, Email, TotpSecret, TwoFACode, TwoFAMethod,
+        UserStore, UserStoreError,
+    },
+    routes::{issue_recovery_codes, reauthenticate, two_fa_changed_email, SecondFactorRequest},
+    utils::{auth::authenticated_email, constants::TOTP_SKEW_STEPS},
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+// Generates a new secret for the signed in user. It is not used for login until confirmed.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let mut user_store = state.user_store.write().await;
+
+    let user = user_store.get_user(&email).await.map_err(|e| match e {
+        UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+        _ => AuthAPIError::UnexpectedError,
+    })?;
+
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TotpAlreadyEnabled);
+    }
+
+    let secret = TotpSecret::default();
+    let otpauth_uri = secret
+        .otpauth_uri(&email)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    user_store
+        .set_totp_secret(&email, secret.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(EnrollTotpResponse {
+        secret: secret.to_base32(),
+        otpauth_uri,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Switches the user to TOTP once their authenticator app has produced a valid code,
+// issuing a fresh set of recovery codes. The password is required, and so is a code for
+// the method being replaced if 2FA is already on.
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+    let two_fa_code =
+        TwoFACode::parse(&request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // The current second factor may be a code or a passkey
+    let current_second_factor = match (request.current_two_fa_code, request.passkey) {
+        (Some(two_fa_code), _) => Some(SecondFactorRequest::Code { two_fa_code }),
+        (None, Some(passkey)) => Some(SecondFactorRequest::Passkey { passkey }),
+        (None, None) => None,
+    };
+
+    reauthenticate(
+        &email,
+        &request.password,
+        current_second_factor,
+        &jar,
+        address.ip(),
+        &state,
+    )
+    .await?;
+
+    let mut user_store = state.user_store.write().await;
+    let secret = user_store
+        .get_totp_secret(&email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::TotpSecretNotFound => AuthAPIError::IncorrectCredentials,
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    if !verify_totp_code(&email, &secret, &two_fa_code, &mut *user_store).await? {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    user_store
+        .set_two_fa_method(
+            &email,
+            TwoFAMethod::Totp,
+            Some(&two_fa_changed_email(TwoFAMethod::Totp)),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+
+    let recovery_codes = issue_recovery_codes(&email, &state).await?;
+
+    Ok((StatusCode::OK, Json(recovery_codes)))
+}
+
+// Check a code from the user's authenticator app. Each time step is accepted once, so a
+// code that has already been used cannot be replayed. The caller holds the user store
+// lock, so it is passed in.
+pub(crate) async fn verify_totp_code(
+    email: &Email,
+    secret: &TotpSecret,
+    code: &TwoFACode,
+    user_store: &mut (dyn UserStore + Send + Sync),
+) -> Result<bool, AuthAPIError> {
+    let Some(step) = secret.matching_current_step(code, *TOTP_SKEW_STEPS) else {
+        return Ok(false);
+    };
+
+    match user_store.use_totp_step(email, step).await {
+        Ok(()) => Ok(true),
+        Err(UserStoreError::TotpCodeAlreadyUsed) => Ok(false),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+    pub password: String,
+    // Code for the 2FA method being replaced, if any
+    #[serde(rename = "current2FACode")]
+    pub current_two_fa_code: Option<String>,
+    // Answer to a re-authentication challenge, if the method being replaced is a passkey
+    pub passkey: Option<AuthenticationCredential>,
+}
@@ -0,0 +1,308 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailMessage, PasskeyCeremony, Password, RecoveryCode, TwoFAChallenge,
+        TwoFACode, TwoFACodeStoreError, TwoFAMethod, User, UserStoreError,
+    },
+    routes::{
+        is_same_client, issue_recovery_codes, login_attempt_binding, verify_passkey_assertion,
+        verify_password, verify_totp_code, SecondFactorRequest,
+    },
+    utils::{
+        auth::{authenticated_email, MAX_TWO_FA_ATTEMPTS},
+        email_templates::two_fa_code_email,
+    },
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+
+// Turns on emailed 2FA codes for the signed in user after checking their password.
+// Authenticator apps are enabled through TOTP enrolment instead.
+pub async fn enable_2fa(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<Enable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    if user.requires_2fa() {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    // 2FA is off, so this only checks the password
+    reauthenticate(&email, &request.password, None, &jar, address.ip(), &state).await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(
+            &email,
+            TwoFAMethod::Email,
+            Some(&two_fa_changed_email(TwoFAMethod::Email)),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let recovery_codes = issue_recovery_codes(&email, &state).await?;
+
+    Ok((StatusCode::OK, Json(recovery_codes)))
+}
+
+// Emails a code the signed in user can present to confirm a change to their 2FA settings.
+// It replaces any code sent before and cannot be used to finish a login.
+pub async fn send_2fa_code(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+    let (jar, client_binding) = login_attempt_binding(jar);
+    let two_fa_code = TwoFACode::default();
+    let challenge = TwoFAChallenge {
+        email: email.clone(),
+        code: two_fa_code.clone(),
+        client_binding,
+    };
+
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .add_reauth_code(challenge)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_outbox
+        .write()
+        .await
+        .enqueue(
+            &email,
+            &two_fa_code_email("Your Authentication Code", &two_fa_code)
+                .map_err(|_| AuthAPIError::UnexpectedError)?,
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((jar, StatusCode::OK))
+}
+
+// Turns 2FA off after checking the password and the current second factor
+pub async fn disable_2fa(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Json(request): Json<Disable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user = reauthenticate(
+        &email,
+        &request.password,
+        request.second_factor,
+        &jar,
+        address.ip(),
+        &state,
+    )
+    .await?;
+
+    if !user.requires_2fa() {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let mut user_store = state.user_store.write().await;
+    user_store
+        .set_two_fa_method(
+            &email,
+            TwoFAMethod::Disabled,
+            Some(&two_fa_changed_email(TwoFAMethod::Disabled)),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Recovery codes only make sense while 2FA is on
+    user_store
+        .set_recovery_codes(&email, Vec::new())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+
+    Ok(StatusCode::OK)
+}
+
+// Checks the password and, while 2FA is on, the second factor of the current method or
+// a recovery code, so that a stolen session alone cannot change how the user signs in.
+// Passkey users answer a challenge from /passkeys/reauthenticate/start. The password is
+// checked under a read lock, and write locks are only taken to use something up, so
+// callers should not hold either store's lock.
+pub(crate) async fn reauthenticate(
+    email: &Email,
+    password: &str,
+    second_factor: Option<SecondFactorRequest>,
+    jar: &CookieJar,
+    ip: IpAddr,
+    state: &AppState,
+) -> Result<User, AuthAPIError> {
+    let password = Password::parse(password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user = {
+        let user_store = state.user_store.read().await;
+        verify_password(email, &password, ip, &*user_store, state).await?;
+
+        user_store
+            .get_user(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?
+    };
+
+    if !user.requires_2fa() {
+        return Ok(user);
+    }
+
+    let verified = match second_factor.ok_or(AuthAPIError::InvalidCredentials)? {
+        SecondFactorRequest::Passkey { passkey } => {
+            let mut user_store = state.user_store.write().await;
+            let (ceremony, owner) =
+                verify_passkey_assertion(&passkey, &mut *user_store, state).await?;
+
+            // The challenge must have been issued to this user for re-authentication
+            user.two_fa_method == TwoFAMethod::Passkey
+                && owner == *email
+                && ceremony
+                    == PasskeyCeremony::Reauthentication {
+                        email: email.as_ref().to_owned(),
+                    }
+        }
+        SecondFactorRequest::Code { two_fa_code } => match (
+            TwoFACode::parse(&two_fa_code),
+            RecoveryCode::parse(&two_fa_code),
+        ) {
+            (Ok(two_fa_code), _) if user.two_fa_method == TwoFAMethod::Totp => {
+                let mut user_store = state.user_store.write().await;
+                let secret = user_store
+                    .get_totp_secret(email)
+                    .await
+                    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+                verify_totp_code(email, &secret, &two_fa_code, &mut *user_store).await?
+            }
+            (Ok(two_fa_code), _) if user.two_fa_method == TwoFAMethod::Email => {
+                verify_reauth_code(email, &two_fa_code, jar, state).await?
+            }
+            // Passkey users are never sent codes, so an emailed one cannot stand in for
+            // their passkey
+            (Ok(_), _) => false,
+            (_, Ok(recovery_code)) => {
+                let mut user_store = state.user_store.write().await;
+                match user_store.use_recovery_code(email, &recovery_code).await {
+                    Ok(()) => true,
+                    Err(UserStoreError::InvalidRecoveryCode) => false,
+                    Err(_) => return Err(AuthAPIError::UnexpectedError),
+                }
+            }
+            _ => return Err(AuthAPIError::InvalidCredentials),
+        },
+    };
+
+    if !verified {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    Ok(user)
+}
+
+// Only the latest code, and only from the client it was sent to, is checked. Wrong
+// guesses count against it the same way they do on /verify-2fa.
+async fn verify_reauth_code(
+    email: &Email,
+    two_fa_code: &TwoFACode,
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<bool, AuthAPIError> {
+    let challenge = match state
+        .two_fa_code_store
+        .read()
+        .await
+        .get_reauth_code(email)
+        .await
+    {
+        Ok(challenge) if is_same_client(&challenge, jar) => challenge,
+        Ok(_) | Err(TwoFACodeStoreError::ReauthCodeNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    if challenge.code != *two_fa_code {
+        let attempts = match two_fa_code_store.record_failed_reauth_attempt(email).await {
+            Ok(attempts) => attempts,
+            Err(TwoFACodeStoreError::ReauthCodeNotFound) => {
+                return Err(AuthAPIError::IncorrectCredentials)
+            }
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        };
+
+        if attempts < MAX_TWO_FA_ATTEMPTS {
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+
+        two_fa_code_store
+            .remove_reauth_code(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        return Err(AuthAPIError::TooManyTwoFAAttempts);
+    }
+
+    // The code has served its purpose. Removing it fails if a concurrent request already
+    // has, so it is only accepted once.
+    match two_fa_code_store.remove_reauth_code(email).await {
+        Ok(()) => Ok(true),
+        Err(TwoFACodeStoreError::ReauthCodeNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+// Lets the user know their 2FA settings changed, in case it was not them
+pub(crate) fn two_fa_changed_email(two_fa_method: TwoFAMethod) -> EmailMessage {
+    let content = match two_fa_method {
+        TwoFAMethod::Disabled => "Two-factor authentication has been turned off for your account.",
+        TwoFAMethod::Email => "Two-factor authentication codes will now be sent to this address.",
+        TwoFAMethod::Passkey => "Two-factor authentication now uses your passkeys.",
+        TwoFAMethod::Totp => "Two-factor authentication now uses your authenticator app.",
+    };
+
+    EmailMessage::plain("Your Two-Factor Authentication Settings Changed", content)
+}
+
+#[derive(Deserialize)]
+pub struct Enable2FARequest {
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct Disable2FARequest {
+    pub password: String,
+    #[serde(flatten)]
+    pub second_factor: Option<SecondFactorRequest>,
+}
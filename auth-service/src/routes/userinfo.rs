@@ -0,0 +1,53 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, UserStoreError},
+    utils::auth::{bearer_token, scope_includes, validate_access_token},
+};
+use axum::{extract::State, http::HeaderMap, response::IntoResponse, Json};
+use serde::Serialize;
+
+// OpenID Connect UserInfo, read with an access token issued by /token
+pub async fn userinfo(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = bearer_token(&headers).ok_or(AuthAPIError::MissingToken)?;
+
+    let claims = validate_access_token(
+        token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Only tokens issued for OpenID Connect may read the user's profile
+    if !scope_includes(&claims.scope, "openid") {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let email_scope = scope_includes(&claims.scope, "email");
+
+    Ok(Json(UserInfoResponse {
+        sub: claims.sub,
+        email: email_scope.then(|| user.email.as_ref().to_owned()),
+        email_verified: email_scope.then_some(user.email_verified),
+    }))
+}
+
+// Field names follow OpenID Connect Core rather than the camelCase used elsewhere
+#[derive(Debug, Serialize)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
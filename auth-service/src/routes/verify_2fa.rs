@@ -1,56 +1,197 @@
 use crate::{
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode, UserStoreError},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, AuthenticationCredential, Email, LoginAttemptId, PasskeyCeremony,
+        RecoveryCode, TwoFACode, TwoFACodeStoreError, TwoFAMethod, UserStoreError,
+    },
+    routes::{is_same_client, start_session, verify_passkey_assertion, verify_totp_code},
+    utils::auth::MAX_TWO_FA_ATTEMPTS,
     AppState,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use axum_extra::extract::CookieJar;
 use serde::Deserialize;
+use std::net::SocketAddr;
 
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    let user_store = state.user_store.read().await;
-
-    if let (Ok(email), Ok(login_attempt_id), Ok(two_fa_code)) = (
+    let (Ok(email), Ok(login_attempt_id)) = (
         Email::parse(&request.email),
         LoginAttemptId::parse(&request.login_attempt_id),
-        TwoFACode::parse(&request.two_fa_code),
-    ) {
-        let code_tuple = two_fa_code_store.get_code(&email).await;
-        let login_tuple = (login_attempt_id, two_fa_code);
-        let user = user_store.get_user(&email).await.map_err(|e| match e {
+    ) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+
+    // A recovery code may be given in place of the 2FA code
+    let second_factor = match request.second_factor {
+        SecondFactorRequest::Passkey { passkey } => SecondFactor::Passkey(passkey),
+        SecondFactorRequest::Code { two_fa_code } => match (
+            TwoFACode::parse(&two_fa_code),
+            RecoveryCode::parse(&two_fa_code),
+        ) {
+            (Ok(two_fa_code), _) => SecondFactor::TwoFACode(two_fa_code),
+            (_, Ok(recovery_code)) => SecondFactor::RecoveryCode(recovery_code),
+            _ => return Err(AuthAPIError::InvalidCredentials),
+        },
+    };
+
+    // The challenge is checked under read locks so wrong guesses do not hold up logins.
+    // The attempt has to be one this client started for this user.
+    let challenge = match state
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&login_attempt_id)
+        .await
+    {
+        Ok(challenge) if challenge.email == email && is_same_client(&challenge, &jar) => challenge,
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    };
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| match e {
             UserStoreError::UserNotFound => AuthAPIError::IncorrectCredentials,
             _ => AuthAPIError::UnexpectedError,
         })?;
 
-        match code_tuple {
-            Ok(code_tuple) if code_tuple == login_tuple => {
-                two_fa_code_store
-                    .remove_code(&email)
-                    .await
-                    .map_err(|_| AuthAPIError::UnexpectedError)?;
+    // Only the checks that use something up take the user store's write lock
+    let verified = match second_factor {
+        // The attempt is consumed before the code is burned, so a request that loses the
+        // race for the attempt cannot use up a code. A wrong code ends the attempt.
+        SecondFactor::RecoveryCode(recovery_code) => {
+            consume_attempt(&login_attempt_id, &state).await?;
+
+            let mut user_store = state.user_store.write().await;
+            match user_store.use_recovery_code(&email, &recovery_code).await {
+                Ok(()) => (),
+                Err(UserStoreError::InvalidRecoveryCode) => {
+                    return Err(AuthAPIError::IncorrectCredentials)
+                }
+                Err(_) => return Err(AuthAPIError::UnexpectedError),
+            }
+            drop(user_store);
+
+            let jar = start_session(&user.email, &headers, address, &state, jar).await?;
+            return Ok((jar, StatusCode::OK.into_response()));
+        }
+        SecondFactor::Passkey(credential) => {
+            let mut user_store = state.user_store.write().await;
+            let (ceremony, owner) =
+                verify_passkey_assertion(&credential, &mut *user_store, &state).await?;
+
+            // The challenge must have been issued for this login attempt and answered
+            // by one of the user's own passkeys
+            owner == email
+                && ceremony
+                    == PasskeyCeremony::SecondFactor {
+                        email: email.as_ref().to_owned(),
+                        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+                    }
+        }
+        // Passkey users are never sent the code recorded for their login attempt
+        SecondFactor::TwoFACode(_) if user.two_fa_method == TwoFAMethod::Passkey => false,
+        SecondFactor::TwoFACode(two_fa_code) if user.two_fa_method == TwoFAMethod::Totp => {
+            let mut user_store = state.user_store.write().await;
+            let secret = user_store
+                .get_totp_secret(&email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            verify_totp_code(&email, &secret, &two_fa_code, &mut *user_store).await?
+        }
+        SecondFactor::TwoFACode(two_fa_code) => challenge.code == two_fa_code,
+    };
 
-                let auth_cookie =
-                    generate_auth_cookie(&user.email).map_err(|_| AuthAPIError::UnexpectedError)?;
+    match verified {
+        true => {
+            consume_attempt(&login_attempt_id, &state).await?;
 
-                Ok((jar.add(auth_cookie), StatusCode::OK.into_response()))
+            let jar = start_session(&user.email, &headers, address, &state, jar).await?;
+            Ok((jar, StatusCode::OK.into_response()))
+        }
+        // Once there are too many wrong guesses the attempt's code can no longer be used
+        false => {
+            let mut two_fa_code_store = state.two_fa_code_store.write().await;
+            let attempts = match two_fa_code_store
+                .record_failed_attempt(&login_attempt_id)
+                .await
+            {
+                Ok(attempts) => attempts,
+                Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+                    return Err(AuthAPIError::IncorrectCredentials)
+                }
+                Err(_) => return Err(AuthAPIError::UnexpectedError),
+            };
+
+            if attempts < MAX_TWO_FA_ATTEMPTS {
+                return Err(AuthAPIError::IncorrectCredentials);
             }
-            Ok(_) | Err(_) => Err(AuthAPIError::IncorrectCredentials),
+
+            two_fa_code_store
+                .remove_code(&login_attempt_id)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            Err(AuthAPIError::TooManyTwoFAAttempts)
         }
-    } else {
-        Err(AuthAPIError::InvalidCredentials)
     }
 }
 
+// Consuming the attempt fails if a concurrent request already has, so it can only start
+// one session
+async fn consume_attempt(
+    login_attempt_id: &LoginAttemptId,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(login_attempt_id)
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+enum SecondFactor {
+    Passkey(AuthenticationCredential),
+    TwoFACode(TwoFACode),
+    RecoveryCode(RecoveryCode),
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Verify2FARequest {
-    #[serde(rename = "2FACode")]
-    pub two_fa_code: String,
     pub email: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    #[serde(flatten)]
+    pub second_factor: SecondFactorRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SecondFactorRequest {
+    Code {
+        #[serde(rename = "2FACode")]
+        two_fa_code: String,
+    },
+    // Passkey users answer a challenge from /passkeys/login/start instead
+    Passkey {
+        passkey: AuthenticationCredential,
+    },
 }
@@ -0,0 +1,145 @@
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, EmailMessage, EmailVerificationToken, UserStoreError},
+    utils::{constants::AUTH_SERVICE_URL, email_templates::verification_email},
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Query(request): Query<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (Ok(email), Ok(token)) = (
+        Email::parse(&request.email),
+        EmailVerificationToken::parse(&request.token),
+    ) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+
+    let mut email_verification_token_store = state.email_verification_token_store.write().await;
+
+    match email_verification_token_store.get_token(&email).await {
+        Ok(stored_token) if stored_token.matches(&token) => (),
+        Ok(_) | Err(_) => return Err(AuthAPIError::InvalidToken),
+    }
+
+    email_verification_token_store
+        .remove_token(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_email_verified(&email, true)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email verified successfully!".to_string(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Respond the same way whether or not the user exists to avoid leaking registered emails
+    let response = (
+        StatusCode::OK,
+        Json(VerifyEmailResponse {
+            message: "If the account needs verification, a new email has been sent".to_string(),
+        }),
+    );
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok(response),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if !user.email_verified {
+        send_verification_email(&email, &state).await?;
+    }
+
+    Ok(response)
+}
+
+// Store a fresh verification token, replacing any previous one, and email the link to the user
+pub(crate) async fn send_verification_email(
+    email: &Email,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = EmailVerificationToken::default();
+    let message = verification_message(email, &token)?;
+
+    add_verification_token(email, token, state).await?;
+
+    state
+        .email_outbox
+        .write()
+        .await
+        .enqueue(email, &message)
+        .await
+        .map(|_| ())
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+pub(crate) async fn add_verification_token(
+    email: &Email,
+    token: EmailVerificationToken,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    state
+        .email_verification_token_store
+        .write()
+        .await
+        .add_token(email.clone(), token)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// The email with the link that verifies the given token
+pub(crate) fn verification_message(
+    email: &Email,
+    token: &EmailVerificationToken,
+) -> Result<EmailMessage, AuthAPIError> {
+    let query = serde_urlencoded::to_string(VerifyEmailRequest {
+        email: email.as_ref().to_owned(),
+        token: token.as_ref().to_owned(),
+    })
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let link = format!("{}/verify-email?{}", AUTH_SERVICE_URL.as_str(), query);
+
+    verification_email(&link).map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct VerifyEmailRequest {
+    pub email: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
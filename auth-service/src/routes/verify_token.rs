@@ -6,7 +6,13 @@ pub async fn verify_token(
     State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    match validate_token(&request.token, state.banned_token_store.clone()).await {
+    match validate_token(
+        &request.token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+    )
+    .await
+    {
         Ok(_) => Ok(StatusCode::OK.into_response()),
         Err(_) => Err(AuthAPIError::InvalidToken),
     }
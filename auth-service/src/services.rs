@@ -1,5 +1,13 @@
 mod data_stores;
 mod mock_email_client;
+mod oidc_provider;
+mod outbox_worker;
+mod rate_limiter;
+mod smtp_email_client;
 
 pub use data_stores::*;
 pub use mock_email_client::*;
+pub use oidc_provider::*;
+pub use outbox_worker::*;
+pub use rate_limiter::*;
+pub use smtp_email_client::*;
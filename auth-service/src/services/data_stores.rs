@@ -1,13 +1,61 @@
+mod hashmap_authorization_code_store;
+mod hashmap_client_store;
+mod hashmap_email_outbox;
+mod hashmap_email_verification_token_store;
+mod hashmap_failed_login_store;
+mod hashmap_magic_link_store;
+mod hashmap_oidc_login_store;
+mod hashmap_passkey_challenge_store;
+mod hashmap_password_reset_token_store;
+mod hashmap_rate_limit_store;
+mod hashmap_refresh_token_store;
+mod hashmap_session_store;
 mod hashmap_two_fa_code_store;
 mod hashmap_user_store;
 mod hashset_banned_token_store;
+mod postgres_client_store;
+mod postgres_email_outbox;
 mod postgres_user_store;
+mod redis_authorization_code_store;
 mod redis_banned_token_store;
+mod redis_email_verification_token_store;
+mod redis_failed_login_store;
+mod redis_magic_link_store;
+mod redis_oidc_login_store;
+mod redis_passkey_challenge_store;
+mod redis_password_reset_token_store;
+mod redis_rate_limit_store;
+mod redis_refresh_token_store;
+mod redis_session_store;
 mod redis_two_fa_code_store;
 
+pub use hashmap_authorization_code_store::HashmapAuthorizationCodeStore;
+pub use hashmap_client_store::HashmapClientStore;
+pub use hashmap_email_outbox::HashmapEmailOutbox;
+pub use hashmap_email_verification_token_store::HashmapEmailVerificationTokenStore;
+pub use hashmap_failed_login_store::HashmapFailedLoginStore;
+pub use hashmap_magic_link_store::HashmapMagicLinkStore;
+pub use hashmap_oidc_login_store::HashmapOidcLoginStore;
+pub use hashmap_passkey_challenge_store::HashmapPasskeyChallengeStore;
+pub use hashmap_password_reset_token_store::HashmapPasswordResetTokenStore;
+pub use hashmap_rate_limit_store::HashmapRateLimitStore;
+pub use hashmap_refresh_token_store::HashmapRefreshTokenStore;
+pub use hashmap_session_store::HashmapSessionStore;
 pub use hashmap_two_fa_code_store::HashmapTwoFACodeStore;
 pub use hashmap_user_store::HashmapUserStore;
 pub use hashset_banned_token_store::HashsetBannedTokenStore;
+pub use postgres_client_store::PostgresClientStore;
+pub use postgres_email_outbox::PostgresEmailOutbox;
 pub use postgres_user_store::PostgresUserStore;
+pub use redis_authorization_code_store::RedisAuthorizationCodeStore;
 pub use redis_banned_token_store::RedisBannedTokenStore;
+pub use redis_email_verification_token_store::RedisEmailVerificationTokenStore;
+pub use redis_failed_login_store::RedisFailedLoginStore;
+pub use redis_magic_link_store::RedisMagicLinkStore;
+pub use redis_oidc_login_store::RedisOidcLoginStore;
+pub use redis_passkey_challenge_store::RedisPasskeyChallengeStore;
+pub use redis_password_reset_token_store::RedisPasswordResetTokenStore;
+pub use redis_rate_limit_store::RedisRateLimitStore;
+pub use redis_refresh_token_store::RedisRefreshTokenStore;
+pub use redis_session_store::RedisSessionStore;
 pub use redis_two_fa_code_store::RedisTwoFACodeStore;
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use crate::domain::data_stores::{
+    AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+};
+
+#[derive(Default)]
+pub struct HashmapAuthorizationCodeStore {
+    codes: HashMap<AuthorizationCode, AuthorizationGrant>,
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashmapAuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        self.codes.insert(code, grant);
+        Ok(())
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        self.codes
+            .remove(code)
+            .ok_or(AuthorizationCodeStoreError::CodeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ClientId, Email, RedirectUri, SessionId};
+
+    fn grant() -> AuthorizationGrant {
+        AuthorizationGrant {
+            client_id: ClientId::parse("client").unwrap(),
+            redirect_uri: RedirectUri::parse("https://example.com/callback").unwrap(),
+            email: Email::parse("test@example.com").unwrap(),
+            session_id: SessionId::default(),
+            scope: "openid".to_owned(),
+            code_challenge: "challenge".to_owned(),
+            nonce: Some("nonce".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_code() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+        let grant = grant();
+
+        store.add_code(code.clone(), grant.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&code).await, Ok(grant));
+    }
+
+    #[tokio::test]
+    async fn test_code_can_only_be_taken_once() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+
+        store.add_code(code.clone(), grant()).await.unwrap();
+        store.take_code(&code).await.unwrap();
+
+        assert_eq!(
+            store.take_code(&code).await,
+            Err(AuthorizationCodeStoreError::CodeNotFound)
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::domain::{Client, ClientId, ClientSecret, ClientStore, ClientStoreError};
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct HashmapClientStore {
+    clients: HashMap<ClientId, (Client, ClientSecret)>,
+}
+
+#[async_trait::async_trait]
+impl ClientStore for HashmapClientStore {
+    async fn set_client(
+        &mut self,
+        client: Client,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        let client = match self.clients.get(&client.id) {
+            Some((existing, _)) => Client {
+                scopes: existing.scopes.clone(),
+                disabled: existing.disabled,
+                ..client
+            },
+            None => client,
+        };
+        self.clients.insert(client.id.clone(), (client, secret));
+        Ok(())
+    }
+
+    async fn add_client(
+        &mut self,
+        client: Client,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        match self.clients.contains_key(&client.id) {
+            true => Err(ClientStoreError::ClientAlreadyExists),
+            false => {
+                self.clients.insert(client.id.clone(), (client, secret));
+                Ok(())
+            }
+        }
+    }
+
+    async fn get_client(&self, client_id: &ClientId) -> Result<Client, ClientStoreError> {
+        self.clients
+            .get(client_id)
+            .map(|(client, _)| client.clone())
+            .ok_or(ClientStoreError::ClientNotFound)
+    }
+
+    async fn rotate_secret(
+        &mut self,
+        client_id: &ClientId,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        let (_, stored) = self
+            .clients
+            .get_mut(client_id)
+            .ok_or(ClientStoreError::ClientNotFound)?;
+        *stored = secret;
+        Ok(())
+    }
+
+    async fn disable_client(&mut self, client_id: &ClientId) -> Result<(), ClientStoreError> {
+        let (client, _) = self
+            .clients
+            .get_mut(client_id)
+            .ok_or(ClientStoreError::ClientNotFound)?;
+        client.disabled = true;
+        Ok(())
+    }
+
+    async fn authenticate_client(
+        &self,
+        client_id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        match self.clients.get(client_id) {
+            Some((client, _)) if client.disabled => Err(ClientStoreError::ClientDisabled),
+            Some((_, stored)) if stored == secret => Ok(()),
+            Some(_) => Err(ClientStoreError::InvalidCredentials),
+            None => Err(ClientStoreError::ClientNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::RedirectUri;
+
+    fn client() -> Client {
+        Client::new(
+            ClientId::parse("web-app").unwrap(),
+            vec![RedirectUri::parse("https://example.com/callback").unwrap()],
+            vec!["reports:read".to_owned()],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_client() {
+        let mut store = HashmapClientStore::default();
+
+        store
+            .set_client(client(), ClientSecret::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_client(&client().id).await, Ok(client()));
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_client() {
+        let mut store = HashmapClientStore::default();
+        let secret = ClientSecret::default();
+
+        store.set_client(client(), secret.clone()).await.unwrap();
+
+        assert_eq!(
+            store.authenticate_client(&client().id, &secret).await,
+            Ok(())
+        );
+        assert_eq!(
+            store
+                .authenticate_client(&client().id, &ClientSecret::default())
+                .await,
+            Err(ClientStoreError::InvalidCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_unknown_client() {
+        let store = HashmapClientStore::default();
+
+        let result = store
+            .authenticate_client(&client().id, &ClientSecret::default())
+            .await;
+
+        assert_eq!(result, Err(ClientStoreError::ClientNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_add_existing_client_returns_err() {
+        let mut store = HashmapClientStore::default();
+
+        store
+            .add_client(client(), ClientSecret::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.add_client(client(), ClientSecret::default()).await,
+            Err(ClientStoreError::ClientAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotate_secret() {
+        let mut store = HashmapClientStore::default();
+        let old_secret = ClientSecret::default();
+        let new_secret = ClientSecret::default();
+
+        store
+            .add_client(client(), old_secret.clone())
+            .await
+            .unwrap();
+        store
+            .rotate_secret(&client().id, new_secret.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.authenticate_client(&client().id, &old_secret).await,
+            Err(ClientStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            store.authenticate_client(&client().id, &new_secret).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disabled_client_cannot_authenticate() {
+        let mut store = HashmapClientStore::default();
+        let secret = ClientSecret::default();
+
+        store.add_client(client(), secret.clone()).await.unwrap();
+        store.disable_client(&client().id).await.unwrap();
+
+        assert_eq!(
+            store.authenticate_client(&client().id, &secret).await,
+            Err(ClientStoreError::ClientDisabled)
+        );
+        assert!(store.get_client(&client().id).await.unwrap().disabled);
+
+        // Registering the client again from configuration does not re-enable it
+        store.set_client(client(), secret.clone()).await.unwrap();
+        assert!(store.get_client(&client().id).await.unwrap().disabled);
+    }
+
+    #[tokio::test]
+    async fn test_set_client_replaces_secret() {
+        let mut store = HashmapClientStore::default();
+        let old_secret = ClientSecret::default();
+        let new_secret = ClientSecret::default();
+
+        store
+            .set_client(client(), old_secret.clone())
+            .await
+            .unwrap();
+        store
+            .set_client(client(), new_secret.clone())
+            .await
+            .unwrap();
+
+        assert!(store
+            .authenticate_client(&client().id, &old_secret)
+            .await
+            .is_err());
+        assert!(store
+            .authenticate_client(&client().id, &new_secret)
+            .await
+            .is_ok());
+    }
+}
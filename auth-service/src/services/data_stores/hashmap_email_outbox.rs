@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{EmailOutbox, EmailOutboxError, OutboxEmail, OutboxEmailId},
+    Email, EmailMessage,
+};
+
+#[derive(Default)]
+pub struct HashmapEmailOutbox {
+    emails: HashMap<OutboxEmailId, QueuedEmail>,
+    queued: u64,
+}
+
+struct QueuedEmail {
+    email: OutboxEmail,
+    next_attempt_at: usize,
+    // Keeps emails queued within the same second in the order they were queued
+    position: u64,
+}
+
+#[async_trait::async_trait]
+impl EmailOutbox for HashmapEmailOutbox {
+    async fn enqueue(
+        &mut self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<OutboxEmailId, EmailOutboxError> {
+        let id = OutboxEmailId::default();
+        let now = Utc::now()
+            .timestamp()
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        self.emails.insert(
+            id.clone(),
+            QueuedEmail {
+                email: OutboxEmail {
+                    id: id.clone(),
+                    recipient: recipient.clone(),
+                    message: message.clone(),
+                    attempts: 0,
+                    last_error: None,
+                    created_at: now,
+                    failed_at: None,
+                },
+                next_attempt_at: now,
+                position: self.queued,
+            },
+        );
+        self.queued += 1;
+        Ok(id)
+    }
+
+    async fn claim_due(
+        &mut self,
+        now: usize,
+        lease_until: usize,
+        limit: usize,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+        let mut due: Vec<&mut QueuedEmail> = self
+            .emails
+            .values_mut()
+            .filter(|queued| queued.email.failed_at.is_none() && queued.next_attempt_at <= now)
+            .collect();
+        due.sort_by_key(|queued| (queued.next_attempt_at, queued.position));
+
+        Ok(due
+            .into_iter()
+            .take(limit)
+            .map(|queued| {
+                queued.email.attempts += 1;
+                queued.next_attempt_at = lease_until;
+                queued.email.clone()
+            })
+            .collect())
+    }
+
+    async fn mark_sent(&mut self, id: &OutboxEmailId) -> Result<(), EmailOutboxError> {
+        self.emails
+            .remove(id)
+            .map(|_| ())
+            .ok_or(EmailOutboxError::EmailNotFound)
+    }
+
+    async fn retry_later(
+        &mut self,
+        id: &OutboxEmailId,
+        error: &str,
+        retry_at: usize,
+    ) -> Result<(), EmailOutboxError> {
+        let queued = self
+            .emails
+            .get_mut(id)
+            .ok_or(EmailOutboxError::EmailNotFound)?;
+
+        queued.email.last_error = Some(error.to_owned());
+        queued.next_attempt_at = retry_at;
+        Ok(())
+    }
+
+    async fn dead_letter(
+        &mut self,
+        id: &OutboxEmailId,
+        error: &str,
+        failed_at: usize,
+    ) -> Result<(), EmailOutboxError> {
+        let queued = self
+            .emails
+            .get_mut(id)
+            .ok_or(EmailOutboxError::EmailNotFound)?;
+
+        queued.email.last_error = Some(error.to_owned());
+        queued.email.failed_at = Some(failed_at);
+        Ok(())
+    }
+
+    async fn get_failed(&self) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+        let mut failed: Vec<&QueuedEmail> = self
+            .emails
+            .values()
+            .filter(|queued| queued.email.failed_at.is_some())
+            .collect();
+        failed.sort_by_key(|queued| queued.position);
+
+        Ok(failed
+            .into_iter()
+            .map(|queued| queued.email.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Far enough ahead that every queued email is due
+    const LATER: usize = usize::MAX / 2;
+
+    fn recipient() -> Email {
+        Email::parse("test@example.com").unwrap()
+    }
+
+    fn message() -> EmailMessage {
+        EmailMessage::plain("Subject", "Content")
+    }
+
+    #[tokio::test]
+    async fn test_claim_due_counts_attempt_and_leases_email() {
+        let mut outbox = HashmapEmailOutbox::default();
+        let id = outbox.enqueue(&recipient(), &message()).await.unwrap();
+
+        let claimed = outbox.claim_due(LATER, LATER + 60, 10).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, id);
+        assert_eq!(claimed[0].attempts, 1);
+        assert_eq!(claimed[0].message, message());
+
+        // Leased emails are not handed out again until the lease runs out
+        assert_eq!(outbox.claim_due(LATER, LATER + 60, 10).await, Ok(vec![]));
+        assert_eq!(
+            outbox.claim_due(LATER + 60, LATER + 120, 10).await.unwrap()[0].attempts,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_claim_due_respects_limit() {
+        let mut outbox = HashmapEmailOutbox::default();
+        for _ in 0..3 {
+            outbox.enqueue(&recipient(), &message()).await.unwrap();
+        }
+
+        assert_eq!(
+            outbox.claim_due(LATER, LATER + 60, 2).await.unwrap().len(),
+            2
+        );
+        assert_eq!(
+            outbox.claim_due(LATER, LATER + 60, 2).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mark_sent_removes_email() {
+        let mut outbox = HashmapEmailOutbox::default();
+        let id = outbox.enqueue(&recipient(), &message()).await.unwrap();
+
+        assert_eq!(outbox.mark_sent(&id).await, Ok(()));
+        assert_eq!(
+            outbox.mark_sent(&id).await,
+            Err(EmailOutboxError::EmailNotFound)
+        );
+        assert_eq!(outbox.claim_due(LATER, LATER + 60, 10).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_retry_later_reschedules_email() {
+        let mut outbox = HashmapEmailOutbox::default();
+        let id = outbox.enqueue(&recipient(), &message()).await.unwrap();
+        outbox.claim_due(LATER, LATER + 60, 10).await.unwrap();
+
+        outbox
+            .retry_later(&id, "Connection refused", LATER + 300)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outbox.claim_due(LATER + 299, LATER + 400, 10).await,
+            Ok(vec![])
+        );
+        let claimed = outbox
+            .claim_due(LATER + 300, LATER + 400, 10)
+            .await
+            .unwrap();
+        assert_eq!(claimed[0].last_error.as_deref(), Some("Connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_keeps_email_for_admins() {
+        let mut outbox = HashmapEmailOutbox::default();
+        let id = outbox.enqueue(&recipient(), &message()).await.unwrap();
+        outbox.claim_due(LATER, LATER + 60, 10).await.unwrap();
+
+        outbox
+            .dead_letter(&id, "Mailbox unavailable", LATER)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outbox.claim_due(usize::MAX, usize::MAX, 10).await,
+            Ok(vec![])
+        );
+        let failed = outbox.get_failed().await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, id);
+        assert_eq!(failed[0].failed_at, Some(LATER));
+        assert_eq!(failed[0].last_error.as_deref(), Some("Mailbox unavailable"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{
+        EmailVerificationToken, EmailVerificationTokenStore, EmailVerificationTokenStoreError,
+    },
+    email::Email,
+};
+
+#[derive(Default)]
+pub struct HashmapEmailVerificationTokenStore {
+    tokens: HashMap<Email, EmailVerificationToken>,
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for HashmapEmailVerificationTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        self.tokens.insert(email, token);
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<EmailVerificationToken, EmailVerificationTokenStoreError> {
+        self.tokens
+            .get(email)
+            .cloned()
+            .ok_or(EmailVerificationTokenStoreError::TokenNotFound)
+    }
+
+    async fn remove_token(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        self.tokens
+            .remove(email)
+            .map(|_| ()) // discard returned value
+            .ok_or(EmailVerificationTokenStoreError::TokenNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = EmailVerificationToken::default();
+
+        assert!(store.add_token(email.clone(), token.clone()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = EmailVerificationToken::default();
+
+        assert!(store.add_token(email.clone(), token.clone()).await.is_ok());
+
+        let stored_token = store.get_token(&email).await.unwrap();
+        assert_eq!(stored_token, token);
+    }
+
+    #[tokio::test]
+    async fn test_add_token_replaces_previous_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let first_token = EmailVerificationToken::default();
+        let second_token = EmailVerificationToken::default();
+
+        store.add_token(email.clone(), first_token).await.unwrap();
+        store
+            .add_token(email.clone(), second_token.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_token(&email).await.unwrap(), second_token);
+    }
+
+    #[tokio::test]
+    async fn test_remove_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = EmailVerificationToken::default();
+
+        assert!(store.add_token(email.clone(), token.clone()).await.is_ok());
+
+        store.remove_token(&email).await.unwrap();
+        assert_eq!(
+            store.get_token(&email).await,
+            Err(EmailVerificationTokenStoreError::TokenNotFound)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::domain::data_stores::{
+    FailedLoginKey, FailedLoginStore, FailedLoginStoreError, FailedLogins,
+};
+
+#[derive(Default)]
+pub struct HashmapFailedLoginStore {
+    failures: HashMap<FailedLoginKey, FailedLogins>,
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for HashmapFailedLoginStore {
+    async fn record_failure(
+        &mut self,
+        key: &FailedLoginKey,
+        failed_at: usize,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        let failures = self.failures.entry(key.clone()).or_default();
+        failures.count += 1;
+        failures.last_failed_at = failed_at;
+        Ok(*failures)
+    }
+
+    async fn get_failures(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        Ok(self.failures.get(key).copied().unwrap_or_default())
+    }
+
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        self.failures.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::domain::Email;
+
+    fn account() -> FailedLoginKey {
+        FailedLoginKey::Account(Email::parse("test@example.com").unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_record_failure() {
+        let mut store = HashmapFailedLoginStore::default();
+
+        store.record_failure(&account(), 1_000).await.unwrap();
+        let failures = store.record_failure(&account(), 1_010).await.unwrap();
+
+        assert_eq!(
+            failures,
+            FailedLogins {
+                count: 2,
+                last_failed_at: 1_010
+            }
+        );
+        assert_eq!(store.get_failures(&account()).await, Ok(failures));
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_counted_per_key() {
+        let mut store = HashmapFailedLoginStore::default();
+        let from_ip = FailedLoginKey::AccountFromIp(
+            Email::parse("test@example.com").unwrap(),
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+        );
+
+        store.record_failure(&account(), 1_000).await.unwrap();
+
+        assert_eq!(store.get_failures(&from_ip).await.unwrap().count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_clear_failures() {
+        let mut store = HashmapFailedLoginStore::default();
+
+        store.record_failure(&account(), 1_000).await.unwrap();
+        store.clear_failures(&account()).await.unwrap();
+
+        assert_eq!(
+            store.get_failures(&account()).await,
+            Ok(FailedLogins::default())
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{MagicLinkId, MagicLinkStore, MagicLinkStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapMagicLinkStore {
+    links: HashMap<MagicLinkId, Email>,
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for HashmapMagicLinkStore {
+    async fn add_link(&mut self, id: MagicLinkId, email: Email) -> Result<(), MagicLinkStoreError> {
+        self.links.insert(id, email);
+        Ok(())
+    }
+
+    async fn take_link(&mut self, id: &MagicLinkId) -> Result<Email, MagicLinkStoreError> {
+        self.links
+            .remove(id)
+            .ok_or(MagicLinkStoreError::LinkNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_link() {
+        let mut store = HashmapMagicLinkStore::default();
+        let id = MagicLinkId::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        store.add_link(id.clone(), email.clone()).await.unwrap();
+
+        assert_eq!(store.take_link(&id).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_link_can_only_be_taken_once() {
+        let mut store = HashmapMagicLinkStore::default();
+        let id = MagicLinkId::default();
+
+        store
+            .add_link(id.clone(), Email::parse("test@example.com").unwrap())
+            .await
+            .unwrap();
+        store.take_link(&id).await.unwrap();
+
+        assert_eq!(
+            store.take_link(&id).await,
+            Err(MagicLinkStoreError::LinkNotFound)
+        );
+    }
+}
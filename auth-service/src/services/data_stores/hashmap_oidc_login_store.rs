@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use crate::domain::data_stores::{OidcLogin, OidcLoginState, OidcLoginStore, OidcLoginStoreError};
+
+#[derive(Default)]
+pub struct HashmapOidcLoginStore {
+    logins: HashMap<OidcLoginState, OidcLogin>,
+}
+
+#[async_trait::async_trait]
+impl OidcLoginStore for HashmapOidcLoginStore {
+    async fn add_login(
+        &mut self,
+        state: OidcLoginState,
+        login: OidcLogin,
+    ) -> Result<(), OidcLoginStoreError> {
+        self.logins.insert(state, login);
+        Ok(())
+    }
+
+    async fn take_login(
+        &mut self,
+        state: &OidcLoginState,
+    ) -> Result<OidcLogin, OidcLoginStoreError> {
+        self.logins
+            .remove(state)
+            .ok_or(OidcLoginStoreError::LoginNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_login() {
+        let mut store = HashmapOidcLoginStore::default();
+        let state = OidcLoginState::default();
+        let login = OidcLogin::new("corp".to_owned(), None);
+
+        store.add_login(state.clone(), login.clone()).await.unwrap();
+
+        assert_eq!(store.take_login(&state).await, Ok(login));
+    }
+
+    #[tokio::test]
+    async fn test_login_can_only_be_taken_once() {
+        let mut store = HashmapOidcLoginStore::default();
+        let state = OidcLoginState::default();
+
+        store
+            .add_login(state.clone(), OidcLogin::new("corp".to_owned(), None))
+            .await
+            .unwrap();
+        store.take_login(&state).await.unwrap();
+
+        assert_eq!(
+            store.take_login(&state).await,
+            Err(OidcLoginStoreError::LoginNotFound)
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use crate::domain::data_stores::{
+    PasskeyCeremony, PasskeyChallenge, PasskeyChallengeStore, PasskeyChallengeStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapPasskeyChallengeStore {
+    challenges: HashMap<PasskeyChallenge, PasskeyCeremony>,
+}
+
+#[async_trait::async_trait]
+impl PasskeyChallengeStore for HashmapPasskeyChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: PasskeyChallenge,
+        ceremony: PasskeyCeremony,
+    ) -> Result<(), PasskeyChallengeStoreError> {
+        self.challenges.insert(challenge, ceremony);
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &mut self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PasskeyCeremony, PasskeyChallengeStoreError> {
+        self.challenges
+            .remove(challenge)
+            .ok_or(PasskeyChallengeStoreError::ChallengeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_challenge() {
+        let mut store = HashmapPasskeyChallengeStore::default();
+        let challenge = PasskeyChallenge::default();
+        let ceremony = PasskeyCeremony::Registration {
+            email: "test@example.com".to_owned(),
+        };
+
+        store
+            .add_challenge(challenge.clone(), ceremony.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.take_challenge(&challenge).await, Ok(ceremony));
+    }
+
+    #[tokio::test]
+    async fn test_challenge_can_only_be_taken_once() {
+        let mut store = HashmapPasskeyChallengeStore::default();
+        let challenge = PasskeyChallenge::default();
+
+        store
+            .add_challenge(challenge.clone(), PasskeyCeremony::Login)
+            .await
+            .unwrap();
+        store.take_challenge(&challenge).await.unwrap();
+
+        assert_eq!(
+            store.take_challenge(&challenge).await,
+            Err(PasskeyChallengeStoreError::ChallengeNotFound)
+        );
+    }
+}
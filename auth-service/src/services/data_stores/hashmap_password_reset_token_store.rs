@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{
+        data_stores::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+        email::Email,
+    },
+    utils::auth::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+// Tokens are stored with the time they were issued so they expire like the Redis ones
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    tokens: HashMap<Email, (PasswordResetToken, i64)>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        self.tokens.insert(email, (token, Utc::now().timestamp()));
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<PasswordResetToken, PasswordResetTokenStoreError> {
+        match self.tokens.get(email) {
+            Some((token, issued_at))
+                if Utc::now().timestamp() < issued_at + PASSWORD_RESET_TOKEN_TTL_SECONDS =>
+            {
+                Ok(token.clone())
+            }
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        self.tokens
+            .remove(email)
+            .map(|_| ()) // discard returned value
+            .ok_or(PasswordResetTokenStoreError::TokenNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = PasswordResetToken::default();
+
+        assert!(store.add_token(email.clone(), token.clone()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = PasswordResetToken::default();
+
+        assert!(store.add_token(email.clone(), token.clone()).await.is_ok());
+
+        let stored_token = store.get_token(&email).await.unwrap();
+        assert_eq!(stored_token, token);
+    }
+
+    #[tokio::test]
+    async fn test_get_expired_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let issued_at = Utc::now().timestamp() - PASSWORD_RESET_TOKEN_TTL_SECONDS;
+
+        store
+            .tokens
+            .insert(email.clone(), (PasswordResetToken::default(), issued_at));
+
+        assert_eq!(
+            store.get_token(&email).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_token_replaces_previous_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let first_token = PasswordResetToken::default();
+        let second_token = PasswordResetToken::default();
+
+        store.add_token(email.clone(), first_token).await.unwrap();
+        store
+            .add_token(email.clone(), second_token.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_token(&email).await.unwrap(), second_token);
+    }
+
+    #[tokio::test]
+    async fn test_remove_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = PasswordResetToken::default();
+
+        assert!(store.add_token(email.clone(), token.clone()).await.is_ok());
+
+        store.remove_token(&email).await.unwrap();
+        assert_eq!(
+            store.get_token(&email).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::domain::data_stores::{
+    RateLimit, RateLimitDecision, RateLimitKey, RateLimitStore, RateLimitStoreError, TokenBucket,
+};
+
+// Counts requests in this process only, so limits are per replica
+#[derive(Default)]
+pub struct HashmapRateLimitStore {
+    buckets: HashMap<RateLimitKey, TokenBucket>,
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for HashmapRateLimitStore {
+    async fn take_token(
+        &mut self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+        now_ms: u64,
+    ) -> Result<RateLimitDecision, RateLimitStoreError> {
+        let bucket = self
+            .buckets
+            .entry(key.clone())
+            .or_insert_with(|| TokenBucket::full(limit, now_ms));
+
+        Ok(bucket.take(limit, now_ms))
+    }
+
+    async fn return_token(
+        &mut self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<(), RateLimitStoreError> {
+        if let Some(bucket) = self.buckets.get_mut(key) {
+            bucket.put_back(limit);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        capacity: 1,
+        period_seconds: 60,
+    };
+
+    #[tokio::test]
+    async fn test_take_token() {
+        let mut store = HashmapRateLimitStore::default();
+        let key = RateLimitKey::new("/login", "ip", "127.0.0.1");
+
+        assert_eq!(
+            store.take_token(&key, &LIMIT, 0).await,
+            Ok(RateLimitDecision::Allowed)
+        );
+        assert_eq!(
+            store.take_token(&key, &LIMIT, 1_000).await,
+            Ok(RateLimitDecision::Limited {
+                retry_after_seconds: 59
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keys_have_separate_buckets() {
+        let mut store = HashmapRateLimitStore::default();
+        let first = RateLimitKey::new("/login", "ip", "127.0.0.1");
+        let second = RateLimitKey::new("/login", "ip", "127.0.0.2");
+
+        store.take_token(&first, &LIMIT, 0).await.unwrap();
+
+        assert_eq!(
+            store.take_token(&second, &LIMIT, 0).await,
+            Ok(RateLimitDecision::Allowed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_return_token() {
+        let mut store = HashmapRateLimitStore::default();
+        let key = RateLimitKey::new("/login", "ip", "127.0.0.1");
+
+        store.take_token(&key, &LIMIT, 0).await.unwrap();
+        store.return_token(&key, &LIMIT).await.unwrap();
+
+        assert_eq!(
+            store.take_token(&key, &LIMIT, 0).await,
+            Ok(RateLimitDecision::Allowed)
+        );
+    }
+}
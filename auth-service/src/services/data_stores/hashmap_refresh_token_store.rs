@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::domain::data_stores::{
+    RefreshToken, RefreshTokenFamily, RefreshTokenStore, RefreshTokenStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    tokens: HashMap<RefreshToken, RefreshTokenEntry>,
+    revoked_families: HashSet<Uuid>,
+}
+
+struct RefreshTokenEntry {
+    family: RefreshTokenFamily,
+    rotated: bool,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        family: RefreshTokenFamily,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.insert(
+            token,
+            RefreshTokenEntry {
+                family,
+                rotated: false,
+            },
+        );
+        Ok(())
+    }
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<RefreshTokenFamily, RefreshTokenStoreError> {
+        let entry = self
+            .tokens
+            .get_mut(token)
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if self.revoked_families.contains(&entry.family.id) {
+            return Err(RefreshTokenStoreError::FamilyRevoked);
+        }
+
+        if entry.rotated {
+            self.revoked_families.insert(entry.family.id);
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+
+        entry.rotated = true;
+        let family = entry.family.clone();
+        self.add_token(new_token, family.clone()).await?;
+
+        Ok(family)
+    }
+
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let entry = self
+            .tokens
+            .get(token)
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        self.revoked_families.insert(entry.family.id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Email, SessionId};
+
+    fn family() -> RefreshTokenFamily {
+        RefreshTokenFamily::new(
+            Email::parse("test@example.com").unwrap(),
+            SessionId::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn rotate_token_returns_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let family = family();
+        let token = RefreshToken::default();
+
+        store
+            .add_token(token.clone(), family.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.rotate_token(&token, RefreshToken::default()).await,
+            Ok(family)
+        );
+    }
+
+    #[tokio::test]
+    async fn rotate_unknown_token_returns_err() {
+        let mut store = HashmapRefreshTokenStore::default();
+
+        assert_eq!(
+            store
+                .rotate_token(&RefreshToken::default(), RefreshToken::default())
+                .await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn reusing_rotated_token_revokes_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let new_token = RefreshToken::default();
+
+        store.add_token(token.clone(), family()).await.unwrap();
+        store.rotate_token(&token, new_token.clone()).await.unwrap();
+
+        assert_eq!(
+            store.rotate_token(&token, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenReused)
+        );
+        assert_eq!(
+            store
+                .rotate_token(&new_token, RefreshToken::default())
+                .await,
+            Err(RefreshTokenStoreError::FamilyRevoked)
+        );
+    }
+
+    #[tokio::test]
+    async fn revoke_family_prevents_rotation() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+
+        store.add_token(token.clone(), family()).await.unwrap();
+        store.revoke_family(&token).await.unwrap();
+
+        assert_eq!(
+            store.rotate_token(&token, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::FamilyRevoked)
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{Session, SessionId, SessionStore, SessionStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<SessionId, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        Ok(self
+            .sessions
+            .values()
+            .filter(|session| &session.email == email)
+            .cloned()
+            .collect())
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        last_seen: usize,
+    ) -> Result<Session, SessionStoreError> {
+        match self.sessions.get_mut(id) {
+            Some(session) => {
+                session.last_seen = last_seen;
+                Ok(session.clone())
+            }
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        match self.sessions.remove(id) {
+            Some(_) => Ok(()),
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(email: &str) -> Session {
+        Session::new(
+            Email::parse(email).unwrap(),
+            Some("test-agent".to_owned()),
+            Some("127.0.0.1".to_owned()),
+        )
+    }
+
+    #[tokio::test]
+    async fn get_sessions_returns_only_users_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let first = session("first@example.com");
+        let second = session("second@example.com");
+
+        store.add_session(first.clone()).await.unwrap();
+        store.add_session(second).await.unwrap();
+
+        assert_eq!(store.get_sessions(&first.email).await, Ok(vec![first]));
+    }
+
+    #[tokio::test]
+    async fn touch_session_updates_last_seen() {
+        let mut store = HashmapSessionStore::default();
+        let session = session("test@example.com");
+
+        store.add_session(session.clone()).await.unwrap();
+
+        let touched = store
+            .touch_session(&session.id, session.last_seen + 60)
+            .await
+            .unwrap();
+        assert_eq!(touched.last_seen, session.last_seen + 60);
+    }
+
+    #[tokio::test]
+    async fn removed_session_is_not_found() {
+        let mut store = HashmapSessionStore::default();
+        let session = session("test@example.com");
+
+        store.add_session(session.clone()).await.unwrap();
+        store.remove_session(&session.id).await.unwrap();
+
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(
+            store.touch_session(&session.id, 0).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+}
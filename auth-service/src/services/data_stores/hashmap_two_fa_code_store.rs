@@ -1,60 +1,200 @@
 use std::collections::HashMap;
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    email::Email,
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, TwoFAChallenge, TwoFACode, TwoFACodeResends, TwoFACodeStore,
+            TwoFACodeStoreError,
+        },
+        email::Email,
+    },
+    utils::auth::MAX_PENDING_LOGIN_ATTEMPTS,
 };
 
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<LoginAttemptId, TwoFAChallenge>,
+    // Each user's pending login attempts, oldest first
+    pending: HashMap<Email, Vec<LoginAttemptId>>,
+    failed_attempts: HashMap<LoginAttemptId, u32>,
+    resends: HashMap<LoginAttemptId, TwoFACodeResends>,
+    // Each user's re-authentication code and the wrong guesses made against it
+    reauth_codes: HashMap<Email, (TwoFAChallenge, u32)>,
+}
+
+impl HashmapTwoFACodeStore {
+    fn forget(&mut self, login_attempt_id: &LoginAttemptId) -> Option<TwoFAChallenge> {
+        self.failed_attempts.remove(login_attempt_id);
+        self.resends.remove(login_attempt_id);
+        self.codes.remove(login_attempt_id)
+    }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
         &mut self,
-        email: Email,
         login_attempt_id: LoginAttemptId,
-        code: TwoFACode,
+        challenge: TwoFAChallenge,
+        _started_at: usize,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let pending = self.pending.entry(challenge.email.clone()).or_default();
+        pending.push(login_attempt_id.clone());
+        let dropped: Vec<LoginAttemptId> = pending
+            .drain(..pending.len().saturating_sub(MAX_PENDING_LOGIN_ATTEMPTS))
+            .collect();
+
+        for id in &dropped {
+            self.forget(id);
+        }
+        self.codes.insert(login_attempt_id, challenge);
         Ok(())
     }
 
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAChallenge, TwoFACodeStoreError> {
         self.codes
-            .get(email)
+            .get(login_attempt_id)
             .cloned()
             .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        self.codes
+    async fn get_pending(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<(LoginAttemptId, TwoFAChallenge)>, TwoFACodeStoreError> {
+        let pending = self
+            .pending
+            .get(email)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        Ok(pending
+            .iter()
+            .filter_map(|id| Some((id.clone(), self.codes.get(id)?.clone())))
+            .collect())
+    }
+
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let challenge = self
+            .forget(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if let Some(pending) = self.pending.get_mut(&challenge.email) {
+            pending.retain(|id| id != login_attempt_id);
+        }
+        Ok(())
+    }
+
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        if !self.codes.contains_key(login_attempt_id) {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        let attempts = self
+            .failed_attempts
+            .entry(login_attempt_id.clone())
+            .or_default();
+        *attempts += 1;
+        Ok(*attempts)
+    }
+
+    async fn resend_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        sent_at: usize,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let challenge = self
+            .codes
+            .get_mut(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        challenge.code = code;
+
+        let resends = self.resends.entry(login_attempt_id.clone()).or_default();
+        resends.count += 1;
+        resends.last_sent_at = sent_at;
+        Ok(())
+    }
+
+    async fn get_resends(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFACodeResends, TwoFACodeStoreError> {
+        if !self.codes.contains_key(login_attempt_id) {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        Ok(self
+            .resends
+            .get(login_attempt_id)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn add_reauth_code(
+        &mut self,
+        challenge: TwoFAChallenge,
+    ) -> Result<(), TwoFACodeStoreError> {
+        self.reauth_codes
+            .insert(challenge.email.clone(), (challenge, 0));
+        Ok(())
+    }
+
+    async fn get_reauth_code(&self, email: &Email) -> Result<TwoFAChallenge, TwoFACodeStoreError> {
+        self.reauth_codes
+            .get(email)
+            .map(|(challenge, _)| challenge.clone())
+            .ok_or(TwoFACodeStoreError::ReauthCodeNotFound)
+    }
+
+    async fn record_failed_reauth_attempt(
+        &mut self,
+        email: &Email,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let (_, attempts) = self
+            .reauth_codes
+            .get_mut(email)
+            .ok_or(TwoFACodeStoreError::ReauthCodeNotFound)?;
+        *attempts += 1;
+        Ok(*attempts)
+    }
+
+    async fn remove_reauth_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        self.reauth_codes
             .remove(email)
-            .map(|_| ()) // discard returned value
-            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+            .map(|_| ())
+            .ok_or(TwoFACodeStoreError::ReauthCodeNotFound)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use uuid::Uuid;
-
     use super::*;
+    use crate::domain::LoginAttemptBinding;
+
+    fn challenge(code: &str) -> TwoFAChallenge {
+        TwoFAChallenge {
+            email: Email::parse("test@example.com").unwrap(),
+            code: TwoFACode::parse(code).unwrap(),
+            client_binding: LoginAttemptBinding::default(),
+        }
+    }
 
     #[tokio::test]
     async fn test_add_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse("test@example.com").unwrap();
-        let login_attempt_id = LoginAttemptId::parse(&Uuid::new_v4().to_string()).unwrap();
-        let code = TwoFACode::parse("123456").unwrap();
 
         assert!(store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .add_code(LoginAttemptId::default(), challenge("123456"), 0)
             .await
             .is_ok());
     }
@@ -62,35 +202,188 @@ mod tests {
     #[tokio::test]
     async fn test_get_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse("test@example.com").unwrap();
-        let login_attempt_id = LoginAttemptId::parse(&Uuid::new_v4().to_string()).unwrap();
-        let code = TwoFACode::parse("123456").unwrap();
+        let login_attempt_id = LoginAttemptId::default();
 
-        assert!(store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+        store
+            .add_code(login_attempt_id.clone(), challenge("123456"), 0)
             .await
-            .is_ok());
+            .unwrap();
 
-        let stored_code = store.get_code(&email).await.unwrap();
-        assert_eq!(stored_code, (login_attempt_id, code));
+        let stored_challenge = store.get_code(&login_attempt_id).await.unwrap();
+        assert_eq!(stored_challenge.code, TwoFACode::parse("123456").unwrap());
     }
 
     #[tokio::test]
     async fn test_remove_code() {
         let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt_id = LoginAttemptId::default();
         let email = Email::parse("test@example.com").unwrap();
-        let login_attempt_id = LoginAttemptId::parse(&Uuid::new_v4().to_string()).unwrap();
-        let code = TwoFACode::parse("123456").unwrap();
 
-        assert!(store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+        store
+            .add_code(login_attempt_id.clone(), challenge("123456"), 0)
             .await
-            .is_ok());
+            .unwrap();
+
+        store.remove_code(&login_attempt_id).await.unwrap();
+        assert!(store.get_code(&login_attempt_id).await.is_err());
+        assert_eq!(store.get_pending(&email).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_login_attempts() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let first_attempt_id = LoginAttemptId::default();
+        let second_attempt_id = LoginAttemptId::default();
+
+        store
+            .add_code(first_attempt_id.clone(), challenge("111111"), 0)
+            .await
+            .unwrap();
+        store
+            .add_code(second_attempt_id.clone(), challenge("222222"), 1)
+            .await
+            .unwrap();
+
+        let pending: Vec<(LoginAttemptId, String)> = store
+            .get_pending(&email)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(id, challenge)| (id, challenge.code.as_ref().to_owned()))
+            .collect();
+        assert_eq!(
+            pending,
+            vec![
+                (first_attempt_id, "111111".to_owned()),
+                (second_attempt_id, "222222".to_owned())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oldest_login_attempt_is_dropped() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let oldest_attempt_id = LoginAttemptId::default();
+
+        store
+            .add_code(oldest_attempt_id.clone(), challenge("123456"), 0)
+            .await
+            .unwrap();
+        for started_at in 1..=MAX_PENDING_LOGIN_ATTEMPTS {
+            store
+                .add_code(LoginAttemptId::default(), challenge("123456"), started_at)
+                .await
+                .unwrap();
+        }
+
+        assert!(store.get_code(&oldest_attempt_id).await.is_err());
+        assert_eq!(
+            store.get_pending(&email).await.unwrap().len(),
+            MAX_PENDING_LOGIN_ATTEMPTS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt_id = LoginAttemptId::default();
+
+        assert_eq!(
+            store.record_failed_attempt(&login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store
+            .add_code(login_attempt_id.clone(), challenge("123456"), 0)
+            .await
+            .unwrap();
+        assert_eq!(store.record_failed_attempt(&login_attempt_id).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&login_attempt_id).await, Ok(2));
+
+        // Other login attempts are counted separately
+        let other_attempt_id = LoginAttemptId::default();
+        store
+            .add_code(other_attempt_id.clone(), challenge("123456"), 0)
+            .await
+            .unwrap();
+        assert_eq!(store.record_failed_attempt(&other_attempt_id).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_resend_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::parse("654321").unwrap();
+
+        assert_eq!(
+            store
+                .resend_code(&login_attempt_id, code.clone(), 1_000)
+                .await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store
+            .add_code(login_attempt_id.clone(), challenge("123456"), 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_resends(&login_attempt_id).await,
+            Ok(TwoFACodeResends::default())
+        );
+
+        store
+            .resend_code(&login_attempt_id, code.clone(), 1_000)
+            .await
+            .unwrap();
+        assert_eq!(store.get_code(&login_attempt_id).await.unwrap().code, code);
+        assert_eq!(
+            store.get_resends(&login_attempt_id).await,
+            Ok(TwoFACodeResends {
+                count: 1,
+                last_sent_at: 1_000
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reauth_codes_are_kept_apart_from_login_attempts() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        store.add_reauth_code(challenge("111111")).await.unwrap();
+        store.add_reauth_code(challenge("222222")).await.unwrap();
+
+        assert_eq!(store.get_pending(&email).await, Ok(vec![]));
+        assert_eq!(
+            store.get_reauth_code(&email).await.unwrap().code,
+            TwoFACode::parse("222222").unwrap()
+        );
+
+        store.remove_reauth_code(&email).await.unwrap();
+        assert_eq!(
+            store.get_reauth_code(&email).await,
+            Err(TwoFACodeStoreError::ReauthCodeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_reauth_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        assert_eq!(
+            store.record_failed_reauth_attempt(&email).await,
+            Err(TwoFACodeStoreError::ReauthCodeNotFound)
+        );
 
-        let stored_code = store.get_code(&email).await.unwrap();
-        assert_eq!(stored_code, (login_attempt_id, code));
+        store.add_reauth_code(challenge("123456")).await.unwrap();
+        assert_eq!(store.record_failed_reauth_attempt(&email).await, Ok(1));
+        assert_eq!(store.record_failed_reauth_attempt(&email).await, Ok(2));
 
-        store.remove_code(&email).await.unwrap();
-        assert!(store.get_code(&email).await.is_err());
+        // A new code starts its count again
+        store.add_reauth_code(challenge("654321")).await.unwrap();
+        assert_eq!(store.record_failed_reauth_attempt(&email).await, Ok(1));
     }
 }
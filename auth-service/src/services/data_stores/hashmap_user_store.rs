@@ -1,17 +1,45 @@
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
-use std::collections::HashMap;
+use crate::domain::{
+    Email, EmailMessage, Passkey, Password, RecoveryCode, TotpSecret, TwoFAMethod, User, UserStore,
+    UserStoreError,
+};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Default)]
 pub struct HashmapUserStore {
     users: HashMap<Email, User>,
+    totp_secrets: HashMap<Email, TotpSecret>,
+    totp_last_steps: HashMap<Email, u64>,
+    recovery_codes: HashMap<Email, HashSet<RecoveryCode>>,
+    tokens_revoked_before: HashMap<Email, usize>,
+    // Keyed by credential id
+    passkeys: HashMap<String, (Email, Passkey)>,
+    // Stands in for the email outbox, oldest first
+    notifications: Vec<(Email, EmailMessage)>,
+}
+
+impl HashmapUserStore {
+    pub fn notifications(&self) -> &[(Email, EmailMessage)] {
+        &self.notifications
+    }
+
+    fn notify(&mut self, email: &Email, notification: Option<&EmailMessage>) {
+        if let Some(message) = notification {
+            self.notifications.push((email.clone(), message.clone()));
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(
+        &mut self,
+        user: User,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
         match self.users.get(&user.email) {
             Some(_) => Err(UserStoreError::UserAlreadyExists),
             None => {
+                self.notify(&user.email, notification);
                 self.users.insert(user.email.clone(), user);
                 Ok(())
             }
@@ -19,7 +47,7 @@ impl UserStore for HashmapUserStore {
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        match self.users.get(&email) {
+        match self.users.get(email) {
             Some(user) => Ok(user.clone()),
             None => Err(UserStoreError::UserNotFound),
         }
@@ -30,7 +58,7 @@ impl UserStore for HashmapUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        match self.users.get(&email) {
+        match self.users.get(email) {
             Some(user) => {
                 if &user.password == password {
                     Ok(())
@@ -41,6 +69,214 @@ impl UserStore for HashmapUserStore {
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_email_verified(
+        &mut self,
+        email: &Email,
+        email_verified: bool,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.email_verified = email_verified;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_password_login_disabled(
+        &mut self,
+        email: &Email,
+        password_login_disabled: bool,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password_login_disabled = password_login_disabled;
+                self.notify(email, notification);
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.two_fa_method = two_fa_method;
+                self.notify(email, notification);
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        match self.users.contains_key(email) {
+            true => {
+                self.totp_secrets.insert(email.clone(), secret);
+                Ok(())
+            }
+            false => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        self.totp_secrets
+            .get(email)
+            .cloned()
+            .ok_or(UserStoreError::TotpSecretNotFound)
+    }
+
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        match self.totp_last_steps.get(email) {
+            Some(last_step) if *last_step >= step => Err(UserStoreError::TotpCodeAlreadyUsed),
+            _ => {
+                self.totp_last_steps.insert(email.clone(), step);
+                Ok(())
+            }
+        }
+    }
+
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), UserStoreError> {
+        match self.users.contains_key(email) {
+            true => {
+                self.recovery_codes
+                    .insert(email.clone(), codes.into_iter().collect());
+                Ok(())
+            }
+            false => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn use_recovery_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), UserStoreError> {
+        match self.recovery_codes.get_mut(email) {
+            Some(codes) if codes.contains(code) => {
+                codes.remove(code);
+                Ok(())
+            }
+            _ => Err(UserStoreError::InvalidRecoveryCode),
+        }
+    }
+
+    async fn set_tokens_revoked_before(
+        &mut self,
+        email: &Email,
+        issued_before: usize,
+    ) -> Result<(), UserStoreError> {
+        match self.users.contains_key(email) {
+            true => {
+                self.tokens_revoked_before
+                    .insert(email.clone(), issued_before);
+                Ok(())
+            }
+            false => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn get_tokens_revoked_before(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, UserStoreError> {
+        match self.users.contains_key(email) {
+            true => Ok(self.tokens_revoked_before.get(email).copied()),
+            false => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn add_passkey(
+        &mut self,
+        email: &Email,
+        passkey: Passkey,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        match self.passkeys.contains_key(&passkey.credential_id) {
+            true => Err(UserStoreError::PasskeyAlreadyExists),
+            false => {
+                self.passkeys
+                    .insert(passkey.credential_id.clone(), (email.clone(), passkey));
+                self.notify(email, notification);
+                Ok(())
+            }
+        }
+    }
+
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, UserStoreError> {
+        match self.users.contains_key(email) {
+            true => Ok(self
+                .passkeys
+                .values()
+                .filter(|(owner, _)| owner == email)
+                .map(|(_, passkey)| passkey.clone())
+                .collect()),
+            false => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn get_passkey(&self, credential_id: &str) -> Result<(Email, Passkey), UserStoreError> {
+        self.passkeys
+            .get(credential_id)
+            .cloned()
+            .ok_or(UserStoreError::PasskeyNotFound)
+    }
+
+    async fn set_passkey_sign_count(
+        &mut self,
+        credential_id: &str,
+        sign_count: u32,
+    ) -> Result<(), UserStoreError> {
+        match self.passkeys.get_mut(credential_id) {
+            Some((_, passkey)) => {
+                passkey.sign_count = sign_count;
+                Ok(())
+            }
+            None => Err(UserStoreError::PasskeyNotFound),
+        }
+    }
+
+    async fn remove_passkeys(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        self.passkeys.retain(|_, (owner, _)| owner != email);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -54,15 +290,15 @@ mod tests {
         let user = User::new(
             Email::parse("mreynolds@serenity.co").unwrap(),
             Password::parse("N0thingInTheverse!").unwrap(),
-            false,
+            TwoFAMethod::Disabled,
         );
 
         // assert that the user was added
-        assert_eq!(user_store.add_user(user.clone()).await, Ok(()));
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
 
         // assert re-adding the same user the failes with an error
         assert_eq!(
-            user_store.add_user(user).await,
+            user_store.add_user(user, None).await,
             Err(UserStoreError::UserAlreadyExists)
         );
     }
@@ -73,11 +309,11 @@ mod tests {
         let user = User::new(
             Email::parse("mreynolds@serenity.co").unwrap(),
             Password::parse("N0thingInTheverse!").unwrap(),
-            false,
+            TwoFAMethod::Disabled,
         );
 
         // add the user to the store
-        assert_eq!(user_store.add_user(user.clone()).await, Ok(()));
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
         // assert that the user can be retrieved
         assert_eq!(
             user_store
@@ -94,15 +330,382 @@ mod tests {
         let user = User::new(
             Email::parse("mreynolds@serenity.co").unwrap(),
             Password::parse("N0thingInTheverse!").unwrap(),
-            false,
+            TwoFAMethod::Disabled,
         );
 
         // add the user to the store
-        assert_eq!(user_store.add_user(user.clone()).await, Ok(()));
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
         // assert that the user validates
         assert_eq!(
             user_store.validate_user(&user.email, &user.password).await,
             Ok(())
         );
     }
+
+    #[tokio::test]
+    async fn update_password() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+        let new_password = Password::parse("Sh1nyL1ttleSh1p").unwrap();
+
+        // add the user to the store
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        // assert the password can be changed
+        assert_eq!(
+            user_store
+                .update_password(&user.email, new_password.clone())
+                .await,
+            Ok(())
+        );
+        // assert only the new password validates
+        assert_eq!(
+            user_store.validate_user(&user.email, &user.password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            user_store.validate_user(&user.email, &new_password).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn set_email_verified() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+
+        // add the user to the store
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        // assert new users start out unverified
+        assert!(
+            !user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .email_verified
+        );
+        // assert the user can be marked verified
+        assert_eq!(
+            user_store.set_email_verified(&user.email, true).await,
+            Ok(())
+        );
+        assert!(
+            user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .email_verified
+        );
+    }
+
+    #[tokio::test]
+    async fn set_password_login_disabled() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+
+        // add the user to the store
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        // assert new users can sign in with their password
+        assert!(
+            !user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .password_login_disabled
+        );
+        // assert password login can be turned off
+        assert_eq!(
+            user_store
+                .set_password_login_disabled(&user.email, true, None)
+                .await,
+            Ok(())
+        );
+        assert!(
+            user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .password_login_disabled
+        );
+    }
+
+    #[tokio::test]
+    async fn set_two_fa_method() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store
+                .set_two_fa_method(&user.email, TwoFAMethod::Totp, None)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store
+                .get_user(&user.email)
+                .await
+                .unwrap()
+                .two_fa_method,
+            TwoFAMethod::Totp
+        );
+    }
+
+    #[tokio::test]
+    async fn notification_is_only_queued_with_change() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+        let message = EmailMessage::plain("Settings Changed", "Your settings changed.");
+
+        // assert nothing is queued when the change fails
+        assert_eq!(
+            user_store
+                .set_two_fa_method(&user.email, TwoFAMethod::Email, Some(&message))
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert!(user_store.notifications().is_empty());
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store
+                .set_two_fa_method(&user.email, TwoFAMethod::Email, Some(&message))
+                .await,
+            Ok(())
+        );
+        assert_eq!(user_store.notifications(), &[(user.email, message)]);
+    }
+
+    #[tokio::test]
+    async fn set_and_get_totp_secret() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+        let secret = TotpSecret::default();
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store.get_totp_secret(&user.email).await,
+            Err(UserStoreError::TotpSecretNotFound)
+        );
+        assert_eq!(
+            user_store
+                .set_totp_secret(&user.email, secret.clone())
+                .await,
+            Ok(())
+        );
+        assert_eq!(user_store.get_totp_secret(&user.email).await, Ok(secret));
+    }
+
+    #[tokio::test]
+    async fn totp_step_can_only_be_used_once() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Totp,
+        );
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(user_store.use_totp_step(&user.email, 100).await, Ok(()));
+        assert_eq!(
+            user_store.use_totp_step(&user.email, 100).await,
+            Err(UserStoreError::TotpCodeAlreadyUsed)
+        );
+        assert_eq!(
+            user_store.use_totp_step(&user.email, 99).await,
+            Err(UserStoreError::TotpCodeAlreadyUsed)
+        );
+        assert_eq!(user_store.use_totp_step(&user.email, 101).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn recovery_code_can_only_be_used_once() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Email,
+        );
+        let codes = RecoveryCode::generate_set();
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store
+                .set_recovery_codes(&user.email, codes.clone())
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store.use_recovery_code(&user.email, &codes[0]).await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store.use_recovery_code(&user.email, &codes[0]).await,
+            Err(UserStoreError::InvalidRecoveryCode)
+        );
+    }
+
+    #[tokio::test]
+    async fn set_recovery_codes_replaces_existing_codes() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Email,
+        );
+        let old_codes = RecoveryCode::generate_set();
+        let new_codes = RecoveryCode::generate_set();
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store
+                .set_recovery_codes(&user.email, old_codes.clone())
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store
+                .set_recovery_codes(&user.email, new_codes.clone())
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store
+                .use_recovery_code(&user.email, &old_codes[0])
+                .await,
+            Err(UserStoreError::InvalidRecoveryCode)
+        );
+        assert_eq!(
+            user_store
+                .use_recovery_code(&user.email, &new_codes[0])
+                .await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn set_tokens_revoked_before() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store.get_tokens_revoked_before(&user.email).await,
+            Ok(None)
+        );
+
+        assert_eq!(
+            user_store
+                .set_tokens_revoked_before(&user.email, 1_700_000_000)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store.get_tokens_revoked_before(&user.email).await,
+            Ok(Some(1_700_000_000))
+        );
+    }
+
+    #[tokio::test]
+    async fn add_and_get_passkey() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+        let passkey = Passkey {
+            credential_id: "credential".to_owned(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+        };
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store
+                .add_passkey(&user.email, passkey.clone(), None)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store
+                .add_passkey(&user.email, passkey.clone(), None)
+                .await,
+            Err(UserStoreError::PasskeyAlreadyExists)
+        );
+        assert_eq!(
+            user_store.get_passkeys(&user.email).await,
+            Ok(vec![passkey.clone()])
+        );
+
+        assert_eq!(
+            user_store.set_passkey_sign_count("credential", 3).await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store.get_passkey("credential").await,
+            Ok((
+                user.email.clone(),
+                Passkey {
+                    sign_count: 3,
+                    ..passkey
+                }
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_passkeys() {
+        let mut user_store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse("mreynolds@serenity.co").unwrap(),
+            Password::parse("N0thingInTheverse!").unwrap(),
+            TwoFAMethod::Disabled,
+        );
+        let passkey = Passkey {
+            credential_id: "credential".to_owned(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+        };
+
+        assert_eq!(user_store.add_user(user.clone(), None).await, Ok(()));
+        assert_eq!(
+            user_store.add_passkey(&user.email, passkey, None).await,
+            Ok(())
+        );
+        assert_eq!(user_store.remove_passkeys(&user.email).await, Ok(()));
+
+        assert_eq!(user_store.get_passkeys(&user.email).await, Ok(Vec::new()));
+        assert_eq!(
+            user_store.get_passkey("credential").await,
+            Err(UserStoreError::PasskeyNotFound)
+        );
+    }
 }
@@ -1,9 +1,10 @@
-use crate::domain::{BannedTokenStore, BannedTokenStoreError};
-use std::collections::HashSet;
+use crate::domain::{BannedTokenStore, BannedTokenStoreError, Email};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
     tokens: HashSet<String>,
+    banned_users: HashMap<Email, usize>,
 }
 
 #[async_trait::async_trait]
@@ -16,6 +17,22 @@ impl BannedTokenStore for HashsetBannedTokenStore {
     async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
         Ok(self.tokens.contains(token))
     }
+
+    async fn ban_user_tokens(
+        &mut self,
+        email: &Email,
+        issued_before: usize,
+    ) -> Result<(), BannedTokenStoreError> {
+        self.banned_users.insert(email.clone(), issued_before);
+        Ok(())
+    }
+
+    async fn user_tokens_banned_before(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, BannedTokenStoreError> {
+        Ok(self.banned_users.get(email).copied())
+    }
 }
 
 #[cfg(test)]
@@ -42,4 +59,19 @@ mod tests {
 
         assert!(result.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_ban_user_tokens() {
+        let mut store = HashsetBannedTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        assert_eq!(store.user_tokens_banned_before(&email).await.unwrap(), None);
+
+        store.ban_user_tokens(&email, 1000).await.unwrap();
+
+        assert_eq!(
+            store.user_tokens_banned_before(&email).await.unwrap(),
+            Some(1000)
+        );
+    }
 }
@@ -0,0 +1,170 @@
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Row};
+
+use crate::domain::{Client, ClientId, ClientSecret, ClientStore, ClientStoreError, RedirectUri};
+
+pub struct PostgresClientStore {
+    pool: PgPool,
+}
+
+impl PostgresClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ClientStore for PostgresClientStore {
+    async fn set_client(
+        &mut self,
+        client: Client,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO oauth_clients (client_id, secret_hash, redirect_uris, scopes)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (client_id) DO UPDATE
+            SET secret_hash = EXCLUDED.secret_hash, redirect_uris = EXCLUDED.redirect_uris
+            "#,
+        )
+        .bind(client.id.as_ref())
+        .bind(hash_client_secret(&secret))
+        .bind(redirect_uri_strings(&client))
+        .bind(&client.scopes)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| ClientStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn add_client(
+        &mut self,
+        client: Client,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO oauth_clients (client_id, secret_hash, redirect_uris, scopes, disabled)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (client_id) DO NOTHING
+            "#,
+        )
+        .bind(client.id.as_ref())
+        .bind(hash_client_secret(&secret))
+        .bind(redirect_uri_strings(&client))
+        .bind(&client.scopes)
+        .bind(client.disabled)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| ClientStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(ClientStoreError::ClientAlreadyExists),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_client(&self, client_id: &ClientId) -> Result<Client, ClientStoreError> {
+        let row = sqlx::query(
+            "SELECT redirect_uris, scopes, disabled FROM oauth_clients WHERE client_id = $1",
+        )
+        .bind(client_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| ClientStoreError::UnexpectedError)?
+        .ok_or(ClientStoreError::ClientNotFound)?;
+
+        let redirect_uris = row
+            .try_get::<Vec<String>, _>("redirect_uris")
+            .map_err(|_| ClientStoreError::UnexpectedError)?
+            .iter()
+            .map(|redirect_uri| RedirectUri::parse(redirect_uri))
+            .collect::<Result<_, _>>()
+            .map_err(|_| ClientStoreError::UnexpectedError)?;
+
+        Ok(Client {
+            id: client_id.clone(),
+            redirect_uris,
+            scopes: row
+                .try_get("scopes")
+                .map_err(|_| ClientStoreError::UnexpectedError)?,
+            disabled: row
+                .try_get("disabled")
+                .map_err(|_| ClientStoreError::UnexpectedError)?,
+        })
+    }
+
+    async fn rotate_secret(
+        &mut self,
+        client_id: &ClientId,
+        secret: ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        let result = sqlx::query("UPDATE oauth_clients SET secret_hash = $2 WHERE client_id = $1")
+            .bind(client_id.as_ref())
+            .bind(hash_client_secret(&secret))
+            .execute(&self.pool)
+            .await
+            .map_err(|_| ClientStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(ClientStoreError::ClientNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn disable_client(&mut self, client_id: &ClientId) -> Result<(), ClientStoreError> {
+        let result = sqlx::query("UPDATE oauth_clients SET disabled = TRUE WHERE client_id = $1")
+            .bind(client_id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| ClientStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(ClientStoreError::ClientNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn authenticate_client(
+        &self,
+        client_id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<(), ClientStoreError> {
+        let row =
+            sqlx::query("SELECT secret_hash, disabled FROM oauth_clients WHERE client_id = $1")
+                .bind(client_id.as_ref())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|_| ClientStoreError::UnexpectedError)?
+                .ok_or(ClientStoreError::ClientNotFound)?;
+
+        let secret_hash: String = row
+            .try_get("secret_hash")
+            .map_err(|_| ClientStoreError::UnexpectedError)?;
+        let disabled: bool = row
+            .try_get("disabled")
+            .map_err(|_| ClientStoreError::UnexpectedError)?;
+
+        match (disabled, secret_hash == hash_client_secret(secret)) {
+            (true, _) => Err(ClientStoreError::ClientDisabled),
+            (false, true) => Ok(()),
+            (false, false) => Err(ClientStoreError::InvalidCredentials),
+        }
+    }
+}
+
+fn redirect_uri_strings(client: &Client) -> Vec<&str> {
+    client
+        .redirect_uris
+        .iter()
+        .map(|redirect_uri| redirect_uri.as_ref())
+        .collect()
+}
+
+// Client secrets are long random strings, so a fast hash is enough to keep them
+// out of the database without the cost of Argon2 on every API call
+fn hash_client_secret(secret: &ClientSecret) -> String {
+    format!("{:x}", Sha256::digest(secret.as_ref().as_bytes()))
+}
@@ -0,0 +1,210 @@
+use chrono::Utc;
+use sqlx::{postgres::PgRow, PgConnection, PgPool, Row};
+
+use crate::domain::{
+    data_stores::{EmailOutbox, EmailOutboxError, OutboxEmail, OutboxEmailId},
+    Email, EmailMessage,
+};
+
+pub struct PostgresEmailOutbox {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutbox {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutbox for PostgresEmailOutbox {
+    async fn enqueue(
+        &mut self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<OutboxEmailId, EmailOutboxError> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        insert_email(&mut conn, recipient, message)
+            .await
+            .map_err(|_| EmailOutboxError::UnexpectedError)
+    }
+
+    async fn claim_due(
+        &mut self,
+        now: usize,
+        lease_until: usize,
+        limit: usize,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+        let now: i64 = now
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+        let lease_until: i64 = lease_until
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+        let limit: i64 = limit
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        // SKIP LOCKED lets workers on several replicas claim different emails at once
+        let rows = sqlx::query(
+            r#"
+            WITH claimed AS (
+                UPDATE email_outbox
+                SET attempts = attempts + 1, next_attempt_at = $2
+                WHERE id IN (
+                    SELECT id FROM email_outbox
+                    WHERE failed_at IS NULL AND next_attempt_at <= $1
+                    ORDER BY next_attempt_at, position
+                    LIMIT $3
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *
+            )
+            SELECT * FROM claimed ORDER BY position
+            "#,
+        )
+        .bind(now)
+        .bind(lease_until)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        rows.iter().map(outbox_email_from_row).collect()
+    }
+
+    async fn mark_sent(&mut self, id: &OutboxEmailId) -> Result<(), EmailOutboxError> {
+        let result = sqlx::query("DELETE FROM email_outbox WHERE id = $1")
+            .bind(id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(EmailOutboxError::EmailNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn retry_later(
+        &mut self,
+        id: &OutboxEmailId,
+        error: &str,
+        retry_at: usize,
+    ) -> Result<(), EmailOutboxError> {
+        let retry_at: i64 = retry_at
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        let result = sqlx::query(
+            "UPDATE email_outbox SET next_attempt_at = $2, last_error = $3 WHERE id = $1",
+        )
+        .bind(id.as_ref())
+        .bind(retry_at)
+        .bind(error)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(EmailOutboxError::EmailNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn dead_letter(
+        &mut self,
+        id: &OutboxEmailId,
+        error: &str,
+        failed_at: usize,
+    ) -> Result<(), EmailOutboxError> {
+        let failed_at: i64 = failed_at
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        let result =
+            sqlx::query("UPDATE email_outbox SET failed_at = $2, last_error = $3 WHERE id = $1")
+                .bind(id.as_ref())
+                .bind(failed_at)
+                .bind(error)
+                .execute(&self.pool)
+                .await
+                .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(EmailOutboxError::EmailNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_failed(&self) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+        let rows =
+            sqlx::query("SELECT * FROM email_outbox WHERE failed_at IS NOT NULL ORDER BY position")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|_| EmailOutboxError::UnexpectedError)?;
+
+        rows.iter().map(outbox_email_from_row).collect()
+    }
+}
+
+// Queue an email on a connection that may be inside another store's transaction, so
+// the email is only kept if that transaction commits
+pub(crate) async fn insert_email(
+    conn: &mut PgConnection,
+    recipient: &Email,
+    message: &EmailMessage,
+) -> Result<OutboxEmailId, sqlx::Error> {
+    let id = OutboxEmailId::default();
+    let now = Utc::now().timestamp();
+
+    sqlx::query(
+        r#"
+        INSERT INTO email_outbox (id, recipient, subject, text_body, html_body, next_attempt_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $6)
+        "#,
+    )
+    .bind(id.as_ref())
+    .bind(recipient.as_ref())
+    .bind(&message.subject)
+    .bind(&message.text)
+    .bind(message.html.as_deref())
+    .bind(now)
+    .execute(conn)
+    .await?;
+
+    Ok(id)
+}
+
+fn outbox_email_from_row(row: &PgRow) -> Result<OutboxEmail, EmailOutboxError> {
+    let attempts: i32 = row.get("attempts");
+    let created_at: i64 = row.get("created_at");
+    let failed_at: Option<i64> = row.get("failed_at");
+
+    Ok(OutboxEmail {
+        id: OutboxEmailId::parse(row.get("id")).map_err(|_| EmailOutboxError::UnexpectedError)?,
+        recipient: Email::parse(row.get("recipient"))
+            .map_err(|_| EmailOutboxError::UnexpectedError)?,
+        message: EmailMessage {
+            subject: row.get("subject"),
+            text: row.get("text_body"),
+            html: row.get("html_body"),
+        },
+        attempts: attempts
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?,
+        last_error: row.get("last_error"),
+        created_at: created_at
+            .try_into()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?,
+        failed_at: failed_at
+            .map(|failed_at| failed_at.try_into())
+            .transpose()
+            .map_err(|_| EmailOutboxError::UnexpectedError)?,
+    })
+}
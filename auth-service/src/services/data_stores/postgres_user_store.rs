@@ -1,15 +1,24 @@
 use std::error::Error;
 
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
 use argon2::{
     password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
     PasswordVerifier, Version,
 };
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgRow, PgPool, Postgres, Row, Transaction};
 
-use sqlx::{PgPool, Row};
-
-use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+use super::postgres_email_outbox::insert_email;
+use crate::{
+    domain::{
+        data_stores::{UserStore, UserStoreError},
+        Email, EmailMessage, Passkey, Password, RecoveryCode, TotpSecret, TwoFAMethod, User,
+    },
+    utils::constants::TOTP_ENCRYPTION_KEY,
 };
 
 pub struct PostgresUserStore {
@@ -24,25 +33,40 @@ impl PostgresUserStore {
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(
+        &mut self,
+        user: User,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
         let hashed_password = compute_password_hash(user.password.as_ref())
             .map_err(|_| UserStoreError::UnexpectedError)?;
-
-        sqlx::query("INSERT INTO users (email, password_hash, requires_2fa) VALUES ($1, $2, $3)")
-            .bind(user.email.as_ref())
-            .bind(hashed_password)
-            .bind(user.requires_2fa)
-            .execute(&self.pool)
+        let mut transaction = self
+            .pool
+            .begin()
             .await
-            .map_err(|e| {
-                print!("Error: {:?}", &e);
-                match e.into_database_error().unwrap().is_unique_violation() {
-                    true => UserStoreError::UserAlreadyExists,
-                    false => UserStoreError::UnexpectedError,
-                }
-            })?;
+            .map_err(|_| UserStoreError::UnexpectedError)?;
 
-        Ok(())
+        sqlx::query(
+            r#"
+            INSERT INTO users (email, password_hash, two_fa_method, email_verified)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(user.email.as_ref())
+        .bind(hashed_password)
+        .bind(user.two_fa_method.as_ref())
+        .bind(user.email_verified)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            print!("Error: {:?}", &e);
+            match e.into_database_error().unwrap().is_unique_violation() {
+                true => UserStoreError::UserAlreadyExists,
+                false => UserStoreError::UnexpectedError,
+            }
+        })?;
+
+        notify(transaction, &user.email, notification).await
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
@@ -55,12 +79,15 @@ impl UserStore for PostgresUserStore {
                 _ => UserStoreError::UnexpectedError,
             })?;
 
-        let user = User::new(
-            Email::parse(row.get("email")).map_err(|_| UserStoreError::UserNotFound)?,
-            Password::parse(row.get("password_hash"))
+        let user = User {
+            email: Email::parse(row.get("email")).map_err(|_| UserStoreError::UserNotFound)?,
+            password: Password::parse(row.get("password_hash"))
+                .map_err(|_| UserStoreError::UnexpectedError)?,
+            two_fa_method: TwoFAMethod::parse(row.get("two_fa_method"))
                 .map_err(|_| UserStoreError::UnexpectedError)?,
-            row.get("requires_2fa"),
-        );
+            email_verified: row.get("email_verified"),
+            password_login_disabled: row.get("password_login_disabled"),
+        };
         Ok(user)
     }
 
@@ -69,16 +96,381 @@ impl UserStore for PostgresUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        let user = self
-            .get_user(email)
-            .await
-            .map_err(|_| UserStoreError::UnexpectedError)?;
+        let user = self.get_user(email).await?;
 
         verify_password_hash(user.password.as_ref(), password.as_ref())
             .map_err(|_| UserStoreError::InvalidCredentials)?;
 
         Ok(())
     }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let hashed_password = compute_password_hash(password.as_ref())
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET password_hash = $2 WHERE email = $1")
+            .bind(email.as_ref())
+            .bind(hashed_password)
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn set_email_verified(
+        &mut self,
+        email: &Email,
+        email_verified: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET email_verified = $2 WHERE email = $1")
+            .bind(email.as_ref())
+            .bind(email_verified)
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn set_password_login_disabled(
+        &mut self,
+        email: &Email,
+        password_login_disabled: bool,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET password_login_disabled = $2 WHERE email = $1")
+            .bind(email.as_ref())
+            .bind(password_login_disabled)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        notify(transaction, email, notification).await
+    }
+
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET two_fa_method = $2 WHERE email = $1")
+            .bind(email.as_ref())
+            .bind(two_fa_method.as_ref())
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        notify(transaction, email, notification).await
+    }
+
+    async fn set_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let encrypted_secret =
+            encrypt_totp_secret(&secret).map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET totp_secret = $2 WHERE email = $1")
+            .bind(email.as_ref())
+            .bind(encrypted_secret)
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        let encrypted_secret: Option<String> =
+            sqlx::query_scalar("SELECT totp_secret FROM users WHERE email = $1")
+                .bind(email.as_ref())
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| match e {
+                    sqlx::Error::RowNotFound => UserStoreError::UserNotFound,
+                    _ => UserStoreError::UnexpectedError,
+                })?;
+
+        let encrypted_secret = encrypted_secret.ok_or(UserStoreError::TotpSecretNotFound)?;
+        decrypt_totp_secret(&encrypted_secret).map_err(|_| UserStoreError::UnexpectedError)
+    }
+
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<(), UserStoreError> {
+        let step = i64::try_from(step).map_err(|_| UserStoreError::UnexpectedError)?;
+
+        // The condition makes concurrent uses of the same code race for a single update
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET totp_last_step = $2
+            WHERE email = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)
+            "#,
+        )
+        .bind(email.as_ref())
+        .bind(step)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::TotpCodeAlreadyUsed),
+            _ => Ok(()),
+        }
+    }
+
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), UserStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE email = $1")
+            .bind(email.as_ref())
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        for code in codes {
+            sqlx::query("INSERT INTO recovery_codes (email, code_hash) VALUES ($1, $2)")
+                .bind(email.as_ref())
+                .bind(compute_recovery_code_hash(&code))
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| match e.as_database_error() {
+                    Some(e) if e.is_foreign_key_violation() => UserStoreError::UserNotFound,
+                    _ => UserStoreError::UnexpectedError,
+                })?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)
+    }
+
+    async fn use_recovery_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), UserStoreError> {
+        // A single DELETE consumes the code atomically, so concurrent requests
+        // cannot both redeem it
+        let result = sqlx::query("DELETE FROM recovery_codes WHERE email = $1 AND code_hash = $2")
+            .bind(email.as_ref())
+            .bind(compute_recovery_code_hash(code))
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::InvalidRecoveryCode),
+            _ => Ok(()),
+        }
+    }
+
+    async fn set_tokens_revoked_before(
+        &mut self,
+        email: &Email,
+        issued_before: usize,
+    ) -> Result<(), UserStoreError> {
+        let issued_before: i64 = issued_before
+            .try_into()
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET tokens_revoked_before = $2 WHERE email = $1")
+            .bind(email.as_ref())
+            .bind(issued_before)
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_tokens_revoked_before(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, UserStoreError> {
+        let issued_before: Option<i64> =
+            sqlx::query_scalar("SELECT tokens_revoked_before FROM users WHERE email = $1")
+                .bind(email.as_ref())
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| match e {
+                    sqlx::Error::RowNotFound => UserStoreError::UserNotFound,
+                    _ => UserStoreError::UnexpectedError,
+                })?;
+
+        issued_before
+            .map(|issued_before| issued_before.try_into())
+            .transpose()
+            .map_err(|_| UserStoreError::UnexpectedError)
+    }
+
+    async fn add_passkey(
+        &mut self,
+        email: &Email,
+        passkey: Passkey,
+        notification: Option<&EmailMessage>,
+    ) -> Result<(), UserStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO passkeys (credential_id, email, public_key, sign_count)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(&passkey.credential_id)
+        .bind(email.as_ref())
+        .bind(&passkey.public_key)
+        .bind(i64::from(passkey.sign_count))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(e) if e.is_unique_violation() => UserStoreError::PasskeyAlreadyExists,
+            Some(e) if e.is_foreign_key_violation() => UserStoreError::UserNotFound,
+            _ => UserStoreError::UnexpectedError,
+        })?;
+
+        notify(transaction, email, notification).await
+    }
+
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, UserStoreError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT credential_id, public_key, sign_count FROM passkeys
+            WHERE email = $1 ORDER BY created_at
+            "#,
+        )
+        .bind(email.as_ref())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        rows.iter().map(passkey_from_row).collect()
+    }
+
+    async fn get_passkey(&self, credential_id: &str) -> Result<(Email, Passkey), UserStoreError> {
+        let row = sqlx::query(
+            "SELECT credential_id, email, public_key, sign_count FROM passkeys WHERE credential_id = $1",
+        )
+        .bind(credential_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => UserStoreError::PasskeyNotFound,
+            _ => UserStoreError::UnexpectedError,
+        })?;
+
+        let email = Email::parse(row.get("email")).map_err(|_| UserStoreError::UnexpectedError)?;
+        Ok((email, passkey_from_row(&row)?))
+    }
+
+    async fn set_passkey_sign_count(
+        &mut self,
+        credential_id: &str,
+        sign_count: u32,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE passkeys SET sign_count = $2 WHERE credential_id = $1")
+            .bind(credential_id)
+            .bind(i64::from(sign_count))
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::PasskeyNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn remove_passkeys(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        sqlx::query("DELETE FROM passkeys WHERE email = $1")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+// Queue the email telling the user about a change, then commit the change with it
+async fn notify(
+    mut transaction: Transaction<'_, Postgres>,
+    email: &Email,
+    notification: Option<&EmailMessage>,
+) -> Result<(), UserStoreError> {
+    if let Some(message) = notification {
+        insert_email(&mut transaction, email, message)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)
+}
+
+fn passkey_from_row(row: &PgRow) -> Result<Passkey, UserStoreError> {
+    let sign_count: i64 = row.get("sign_count");
+
+    Ok(Passkey {
+        credential_id: row.get("credential_id"),
+        public_key: row.get("public_key"),
+        sign_count: sign_count
+            .try_into()
+            .map_err(|_| UserStoreError::UnexpectedError)?,
+    })
 }
 
 // Helper function to verify if a given password matches an expected hash
@@ -115,6 +507,46 @@ fn compute_password_hash(password: &str) -> Result<String, Box<dyn Error>> {
     Ok(password_hash)
 }
 
+// Recovery codes are high entropy random values, so a fast unsalted hash is
+// enough to keep them unusable if the table leaks
+fn compute_recovery_code_hash(code: &RecoveryCode) -> String {
+    format!("{:x}", Sha256::digest(code.as_ref().as_bytes()))
+}
+
+// TOTP secrets are stored encrypted with AES-256-GCM as base64(nonce || ciphertext),
+// using a key derived from the TOTP_ENCRYPTION_KEY secret
+fn totp_cipher() -> Aes256Gcm {
+    let key = Sha256::digest(TOTP_ENCRYPTION_KEY.as_bytes());
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+fn encrypt_totp_secret(secret: &TotpSecret) -> Result<String, Box<dyn Error>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = totp_cipher()
+        .encrypt(&nonce, secret.as_ref())
+        .map_err(|e| e.to_string())?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(BASE64.encode(payload))
+}
+
+fn decrypt_totp_secret(encrypted_secret: &str) -> Result<TotpSecret, Box<dyn Error>> {
+    let payload = BASE64.decode(encrypted_secret)?;
+    if payload.len() < NONCE_LENGTH {
+        return Err("Encrypted TOTP secret is too short".into());
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+    let secret = totp_cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|e| e.to_string())?;
+
+    Ok(TotpSecret::parse(secret)?)
+}
+
+const NONCE_LENGTH: usize = 12;
+
 // #[cfg(test)]
 // mod tests {
 //     use crate::{get_postgres_pool, utils::constants::DATABASE_URL};
@@ -129,7 +561,7 @@ fn compute_password_hash(password: &str) -> Result<String, Box<dyn Error>> {
 //         let user = User::new(
 //             Email::parse("mreynolds@serenity.co").unwrap(),
 //             Password::parse("N0thingInTheverse!").unwrap(),
-//             false,
+//             TwoFAMethod::Disabled,
 //         );
 
 //         // assert that the user was added
@@ -149,7 +581,7 @@ fn compute_password_hash(password: &str) -> Result<String, Box<dyn Error>> {
 //         let user = User::new(
 //             Email::parse("mreynolds@serenity.co").unwrap(),
 //             Password::parse("N0thingInTheverse!").unwrap(),
-//             false,
+//             TwoFAMethod::Disabled,
 //         );
 
 //         // add the user to the store
@@ -171,7 +603,7 @@ fn compute_password_hash(password: &str) -> Result<String, Box<dyn Error>> {
 //         let user = User::new(
 //             Email::parse("mreynolds@serenity.co").unwrap(),
 //             Password::parse("N0thingInTheverse!").unwrap(),
-//             false,
+//             TwoFAMethod::Disabled,
 //         );
 
 //         // add the user to the store
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{
+            AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError,
+            AuthorizationGrant, SessionId,
+        },
+        ClientId, Email, RedirectUri,
+    },
+    utils::auth::AUTHORIZATION_CODE_TTL_SECONDS,
+};
+
+pub struct RedisAuthorizationCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisAuthorizationCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for RedisAuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let entry = AuthorizationGrantEntry {
+            client_id: grant.client_id.as_ref().to_owned(),
+            redirect_uri: grant.redirect_uri,
+            email: grant.email.as_ref().to_owned(),
+            session_id: grant.session_id.as_ref().to_owned(),
+            scope: grant.scope,
+            code_challenge: grant.code_challenge,
+            nonce: grant.nonce,
+        };
+        let value = serde_json::to_string(&entry)
+            .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?;
+
+        self.conn
+            .write()
+            .await
+            .set_ex::<_, _, ()>(get_key(&code), value, AUTHORIZATION_CODE_TTL_SECONDS as u64)
+            .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        // GETDEL makes sure two concurrent requests cannot both redeem the code
+        let value = match self
+            .conn
+            .write()
+            .await
+            .get_del::<_, Option<String>>(get_key(code))
+        {
+            Ok(Some(value)) => value,
+            Ok(None) => return Err(AuthorizationCodeStoreError::CodeNotFound),
+            Err(_) => return Err(AuthorizationCodeStoreError::UnexpectedError),
+        };
+
+        let entry: AuthorizationGrantEntry = serde_json::from_str(&value)
+            .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(AuthorizationGrant {
+            client_id: ClientId::parse(&entry.client_id)
+                .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?,
+            redirect_uri: entry.redirect_uri,
+            email: Email::parse(&entry.email)
+                .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?,
+            session_id: SessionId::parse(&entry.session_id)
+                .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?,
+            scope: entry.scope,
+            code_challenge: entry.code_challenge,
+            nonce: entry.nonce,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthorizationGrantEntry {
+    client_id: String,
+    redirect_uri: RedirectUri,
+    email: String,
+    session_id: String,
+    scope: String,
+    code_challenge: String,
+    nonce: Option<String>,
+}
+
+const AUTHORIZATION_CODE_PREFIX: &str = "authorization_code:";
+
+fn get_key(code: &AuthorizationCode) -> String {
+    format!("{}{}", AUTHORIZATION_CODE_PREFIX, code.as_ref())
+}
@@ -4,7 +4,10 @@ use redis::{Commands, Connection};
 use tokio::sync::RwLock;
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        Email,
+    },
     utils::auth::TOKEN_TTL_SECONDS,
 };
 
@@ -24,7 +27,7 @@ impl BannedTokenStore for RedisBannedTokenStore {
         self.conn
             .write()
             .await
-            .set_ex(get_key(&token), true, TOKEN_TTL_SECONDS as u64)
+            .set_ex::<_, _, ()>(get_key(&token), true, TOKEN_TTL_SECONDS as u64)
             .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
         Ok(())
     }
@@ -41,11 +44,41 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         Ok(is_banned)
     }
+
+    async fn ban_user_tokens(
+        &mut self,
+        email: &Email,
+        issued_before: usize,
+    ) -> Result<(), BannedTokenStoreError> {
+        // Once every token issued before the ban has expired the ban is no longer needed
+        self.conn
+            .write()
+            .await
+            .set_ex::<_, _, ()>(get_user_key(email), issued_before, TOKEN_TTL_SECONDS as u64)
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn user_tokens_banned_before(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, BannedTokenStoreError> {
+        self.conn
+            .write()
+            .await
+            .get(get_user_key(email))
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)
+    }
 }
 
 // We are using a key prefix to prevent collisions and organize data!
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const BANNED_USER_KEY_PREFIX: &str = "banned_user:";
 
 fn get_key(token: &str) -> String {
     format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
 }
+
+fn get_user_key(email: &Email) -> String {
+    format!("{}{}", BANNED_USER_KEY_PREFIX, email.as_ref())
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{
+        EmailVerificationToken, EmailVerificationTokenStore, EmailVerificationTokenStoreError,
+    },
+    Email,
+};
+
+pub struct RedisEmailVerificationTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisEmailVerificationTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for RedisEmailVerificationTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let key = get_key(&email);
+        self.conn
+            .write()
+            .await
+            .set_ex::<_, _, ()>(key, token.as_ref(), ONE_DAY_IN_SECONDS)
+            .map_err(|_| EmailVerificationTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn remove_token(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let key = get_key(email);
+        self.conn
+            .write()
+            .await
+            .del::<_, ()>(key)
+            .map_err(|_| EmailVerificationTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<EmailVerificationToken, EmailVerificationTokenStoreError> {
+        let key = get_key(email);
+
+        match self.conn.write().await.get::<_, String>(&key) {
+            Ok(value) => EmailVerificationToken::parse(&value)
+                .map_err(|_| EmailVerificationTokenStoreError::UnexpectedError),
+            Err(_) => Err(EmailVerificationTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+const ONE_DAY_IN_SECONDS: u64 = 86400;
+const EMAIL_VERIFICATION_TOKEN_PREFIX: &str = "email_verification_token:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", EMAIL_VERIFICATION_TOKEN_PREFIX, email.as_ref())
+}
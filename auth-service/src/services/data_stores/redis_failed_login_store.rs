@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::data_stores::{FailedLoginKey, FailedLoginStore, FailedLoginStoreError, FailedLogins},
+    utils::auth::FAILED_LOGIN_TTL_SECONDS,
+};
+
+pub struct RedisFailedLoginStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisFailedLoginStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for RedisFailedLoginStore {
+    async fn record_failure(
+        &mut self,
+        key: &FailedLoginKey,
+        failed_at: usize,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        let key = get_key(key);
+
+        // Each failure pushes back when the count is forgotten
+        let (count,): (u32,) = redis::pipe()
+            .atomic()
+            .hincr(&key, COUNT_FIELD, 1)
+            .hset(&key, LAST_FAILED_AT_FIELD, failed_at)
+            .ignore()
+            .expire(&key, FAILED_LOGIN_TTL_SECONDS)
+            .ignore()
+            .query(&mut *self.conn.write().await)
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(FailedLogins {
+            count,
+            last_failed_at: failed_at,
+        })
+    }
+
+    async fn get_failures(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<FailedLogins, FailedLoginStoreError> {
+        let (count, last_failed_at): (Option<u32>, Option<usize>) = self
+            .conn
+            .write()
+            .await
+            .hget(get_key(key), &[COUNT_FIELD, LAST_FAILED_AT_FIELD])
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(FailedLogins {
+            count: count.unwrap_or_default(),
+            last_failed_at: last_failed_at.unwrap_or_default(),
+        })
+    }
+
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        self.conn
+            .write()
+            .await
+            .del::<_, ()>(get_key(key))
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)
+    }
+}
+
+const FAILED_LOGIN_PREFIX: &str = "failed_login:";
+const COUNT_FIELD: &str = "count";
+const LAST_FAILED_AT_FIELD: &str = "last_failed_at";
+
+fn get_key(key: &FailedLoginKey) -> String {
+    match key {
+        FailedLoginKey::Account(email) => format!("{}{}", FAILED_LOGIN_PREFIX, email.as_ref()),
+        FailedLoginKey::AccountFromIp(email, ip) => {
+            format!("{}{}:{}", FAILED_LOGIN_PREFIX, email.as_ref(), ip)
+        }
+    }
+}
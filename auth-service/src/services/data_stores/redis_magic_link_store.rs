@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{MagicLinkId, MagicLinkStore, MagicLinkStoreError},
+        Email,
+    },
+    utils::auth::MAGIC_LINK_TTL_SECONDS,
+};
+
+pub struct RedisMagicLinkStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisMagicLinkStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for RedisMagicLinkStore {
+    async fn add_link(&mut self, id: MagicLinkId, email: Email) -> Result<(), MagicLinkStoreError> {
+        self.conn
+            .write()
+            .await
+            .set_ex::<_, _, ()>(get_key(&id), email.as_ref(), MAGIC_LINK_TTL_SECONDS as u64)
+            .map_err(|_| MagicLinkStoreError::UnexpectedError)
+    }
+
+    async fn take_link(&mut self, id: &MagicLinkId) -> Result<Email, MagicLinkStoreError> {
+        // GETDEL makes sure two concurrent requests cannot both use the link
+        match self
+            .conn
+            .write()
+            .await
+            .get_del::<_, Option<String>>(get_key(id))
+        {
+            Ok(Some(value)) => {
+                Email::parse(&value).map_err(|_| MagicLinkStoreError::UnexpectedError)
+            }
+            Ok(None) => Err(MagicLinkStoreError::LinkNotFound),
+            Err(_) => Err(MagicLinkStoreError::UnexpectedError),
+        }
+    }
+}
+
+const MAGIC_LINK_PREFIX: &str = "magic_link:";
+
+fn get_key(id: &MagicLinkId) -> String {
+    format!("{}{}", MAGIC_LINK_PREFIX, id.as_ref())
+}
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::data_stores::{OidcLogin, OidcLoginState, OidcLoginStore, OidcLoginStoreError},
+    utils::auth::OIDC_LOGIN_TTL_SECONDS,
+};
+
+pub struct RedisOidcLoginStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisOidcLoginStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl OidcLoginStore for RedisOidcLoginStore {
+    async fn add_login(
+        &mut self,
+        state: OidcLoginState,
+        login: OidcLogin,
+    ) -> Result<(), OidcLoginStoreError> {
+        let value =
+            serde_json::to_string(&login).map_err(|_| OidcLoginStoreError::UnexpectedError)?;
+
+        self.conn
+            .write()
+            .await
+            .set_ex::<_, _, ()>(get_key(&state), value, OIDC_LOGIN_TTL_SECONDS as u64)
+            .map_err(|_| OidcLoginStoreError::UnexpectedError)
+    }
+
+    async fn take_login(
+        &mut self,
+        state: &OidcLoginState,
+    ) -> Result<OidcLogin, OidcLoginStoreError> {
+        let value = match self
+            .conn
+            .write()
+            .await
+            .get_del::<_, Option<String>>(get_key(state))
+        {
+            Ok(Some(value)) => value,
+            Ok(None) => return Err(OidcLoginStoreError::LoginNotFound),
+            Err(_) => return Err(OidcLoginStoreError::UnexpectedError),
+        };
+
+        serde_json::from_str(&value).map_err(|_| OidcLoginStoreError::UnexpectedError)
+    }
+}
+
+const OIDC_LOGIN_PREFIX: &str = "oidc_login:";
+
+fn get_key(state: &OidcLoginState) -> String {
+    format!("{}{}", OIDC_LOGIN_PREFIX, state.as_ref())
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::data_stores::{
+        PasskeyCeremony, PasskeyChallenge, PasskeyChallengeStore, PasskeyChallengeStoreError,
+    },
+    utils::auth::PASSKEY_CHALLENGE_TTL_SECONDS,
+};
+
+pub struct RedisPasskeyChallengeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasskeyChallengeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyChallengeStore for RedisPasskeyChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: PasskeyChallenge,
+        ceremony: PasskeyCeremony,
+    ) -> Result<(), PasskeyChallengeStoreError> {
+        let value = serde_json::to_string(&ceremony)
+            .map_err(|_| PasskeyChallengeStoreError::UnexpectedError)?;
+
+        self.conn
+            .write()
+            .await
+            .set_ex::<_, _, ()>(
+                get_key(&challenge),
+                value,
+                PASSKEY_CHALLENGE_TTL_SECONDS as u64,
+            )
+            .map_err(|_| PasskeyChallengeStoreError::UnexpectedError)
+    }
+
+    async fn take_challenge(
+        &mut self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PasskeyCeremony, PasskeyChallengeStoreError> {
+        let value = match self
+            .conn
+            .write()
+            .await
+            .get_del::<_, Option<String>>(get_key(challenge))
+        {
+            Ok(Some(value)) => value,
+            Ok(None) => return Err(PasskeyChallengeStoreError::ChallengeNotFound),
+            Err(_) => return Err(PasskeyChallengeStoreError::UnexpectedError),
+        };
+
+        serde_json::from_str(&value).map_err(|_| PasskeyChallengeStoreError::UnexpectedError)
+    }
+}
+
+const PASSKEY_CHALLENGE_PREFIX: &str = "passkey_challenge:";
+
+fn get_key(challenge: &PasskeyChallenge) -> String {
+    format!("{}{}", PASSKEY_CHALLENGE_PREFIX, challenge.as_ref())
+}
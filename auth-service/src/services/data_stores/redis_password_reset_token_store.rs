@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+        Email,
+    },
+    utils::auth::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(&email);
+        self.conn
+            .write()
+            .await
+            .set_ex::<_, _, ()>(key, token.as_ref(), PASSWORD_RESET_TOKEN_TTL_SECONDS as u64)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(email);
+        self.conn
+            .write()
+            .await
+            .del::<_, ()>(key)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        email: &Email,
+    ) -> Result<PasswordResetToken, PasswordResetTokenStoreError> {
+        let key = get_key(email);
+
+        match self.conn.write().await.get::<_, String>(&key) {
+            Ok(value) => PasswordResetToken::parse(&value)
+                .map_err(|_| PasswordResetTokenStoreError::UnexpectedError),
+            Err(_) => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_PREFIX, email.as_ref())
+}
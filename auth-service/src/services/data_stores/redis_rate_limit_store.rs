@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::domain::data_stores::{
+    RateLimit, RateLimitDecision, RateLimitKey, RateLimitStore, RateLimitStoreError, TokenBucket,
+};
+
+// Shares buckets between replicas, so limits hold however requests are balanced
+pub struct RedisRateLimitStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRateLimitStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    async fn take_token(
+        &mut self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+        now_ms: u64,
+    ) -> Result<RateLimitDecision, RateLimitStoreError> {
+        let key = get_key(key);
+        let mut conn = self.conn.write().await;
+
+        // WATCH makes the transaction fail and be retried if another replica updates
+        // the bucket between reading and writing it
+        redis::transaction(&mut *conn, &[&key], |conn, pipe| {
+            let mut bucket = conn
+                .get::<_, Option<String>>(&key)?
+                .and_then(|value| parse_bucket(&value))
+                .unwrap_or_else(|| TokenBucket::full(limit, now_ms));
+
+            let decision = bucket.take(limit, now_ms);
+
+            // A bucket left alone for a whole period is full again, so it can be dropped
+            let result: Option<()> = pipe
+                .set_ex(
+                    &key,
+                    format!("{} {}", bucket.tokens, bucket.updated_at_ms),
+                    limit.period_seconds.max(1),
+                )
+                .ignore()
+                .query(conn)?;
+
+            Ok(result.map(|()| decision))
+        })
+        .map_err(|_| RateLimitStoreError::UnexpectedError)
+    }
+
+    async fn return_token(
+        &mut self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<(), RateLimitStoreError> {
+        let key = get_key(key);
+        let mut conn = self.conn.write().await;
+
+        redis::transaction(&mut *conn, &[&key], |conn, pipe| {
+            // A bucket that has expired is full again, so there is nothing to put back
+            let Some(mut bucket) = conn
+                .get::<_, Option<String>>(&key)?
+                .and_then(|value| parse_bucket(&value))
+            else {
+                return Ok(Some(()));
+            };
+
+            bucket.put_back(limit);
+
+            pipe.set_ex(
+                &key,
+                format!("{} {}", bucket.tokens, bucket.updated_at_ms),
+                limit.period_seconds.max(1),
+            )
+            .ignore()
+            .query(conn)
+        })
+        .map_err(|_| RateLimitStoreError::UnexpectedError)
+    }
+}
+
+const RATE_LIMIT_PREFIX: &str = "rate_limit:";
+
+fn get_key(key: &RateLimitKey) -> String {
+    format!("{}{}", RATE_LIMIT_PREFIX, key.as_ref())
+}
+
+fn parse_bucket(value: &str) -> Option<TokenBucket> {
+    let (tokens, updated_at_ms) = value.split_once(' ')?;
+
+    Some(TokenBucket {
+        tokens: tokens.parse().ok()?,
+        updated_at_ms: updated_at_ms.parse().ok()?,
+    })
+}
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        data_stores::{
+            RefreshToken, RefreshTokenFamily, RefreshTokenStore, RefreshTokenStoreError, SessionId,
+        },
+        Email,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        family: RefreshTokenFamily,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let entry = RefreshTokenEntry::new(&family, false);
+        set_entry(&mut *self.conn.write().await, &token, &entry)
+    }
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<RefreshTokenFamily, RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+
+        // A token always belongs to the same family, so it can be looked up before WATCH
+        let family = get_entry(&mut conn, token)?.family()?;
+        let token_key = get_token_key(token);
+        let family_key = get_family_key(&family.id);
+        let rotated_entry = serialize_entry(&RefreshTokenEntry::new(&family, true))?;
+        let new_entry = serialize_entry(&RefreshTokenEntry::new(&family, false))?;
+
+        // WATCH makes the transaction fail and be retried if another replica rotates the
+        // token or revokes the family between reading and writing, so a token can only be
+        // rotated once
+        redis::transaction(&mut *conn, &[&token_key, &family_key], |conn, pipe| {
+            let revoked: bool = conn.exists(&family_key)?;
+            if revoked {
+                return Ok(Some(Err(RefreshTokenStoreError::FamilyRevoked)));
+            }
+
+            let Some(entry) = conn
+                .get::<_, Option<String>>(&token_key)?
+                .and_then(|value| serde_json::from_str::<RefreshTokenEntry>(&value).ok())
+            else {
+                return Ok(Some(Err(RefreshTokenStoreError::TokenNotFound)));
+            };
+
+            if entry.rotated {
+                let result: Option<()> = pipe
+                    .set_ex(&family_key, true, REFRESH_TOKEN_TTL_SECONDS as u64)
+                    .ignore()
+                    .query(conn)?;
+                return Ok(result.map(|()| Err(RefreshTokenStoreError::TokenReused)));
+            }
+
+            let result: Option<()> = pipe
+                .set_ex(&token_key, &rotated_entry, REFRESH_TOKEN_TTL_SECONDS as u64)
+                .ignore()
+                .set_ex(
+                    get_token_key(&new_token),
+                    &new_entry,
+                    REFRESH_TOKEN_TTL_SECONDS as u64,
+                )
+                .ignore()
+                .query(conn)?;
+            Ok(result.map(|()| Ok(())))
+        })
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)??;
+
+        Ok(family)
+    }
+
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let family = get_entry(&mut conn, token)?.family()?;
+        revoke(&mut conn, &family.id)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenEntry {
+    family_id: String,
+    email: String,
+    session_id: String,
+    issued_at: usize,
+    rotated: bool,
+}
+
+impl RefreshTokenEntry {
+    fn new(family: &RefreshTokenFamily, rotated: bool) -> Self {
+        Self {
+            family_id: family.id.to_string(),
+            email: family.email.as_ref().to_owned(),
+            session_id: family.session_id.as_ref().to_owned(),
+            issued_at: family.issued_at,
+            rotated,
+        }
+    }
+
+    fn family(&self) -> Result<RefreshTokenFamily, RefreshTokenStoreError> {
+        Ok(RefreshTokenFamily {
+            id: Uuid::parse_str(&self.family_id)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+            email: Email::parse(&self.email)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+            session_id: SessionId::parse(&self.session_id)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+            issued_at: self.issued_at,
+        })
+    }
+}
+
+fn get_entry(
+    conn: &mut Connection,
+    token: &RefreshToken,
+) -> Result<RefreshTokenEntry, RefreshTokenStoreError> {
+    match conn.get::<_, String>(get_token_key(token)) {
+        Ok(value) => {
+            serde_json::from_str(&value).map_err(|_| RefreshTokenStoreError::UnexpectedError)
+        }
+        Err(_) => Err(RefreshTokenStoreError::TokenNotFound),
+    }
+}
+
+fn serialize_entry(entry: &RefreshTokenEntry) -> Result<String, RefreshTokenStoreError> {
+    serde_json::to_string(entry).map_err(|_| RefreshTokenStoreError::UnexpectedError)
+}
+
+fn set_entry(
+    conn: &mut Connection,
+    token: &RefreshToken,
+    entry: &RefreshTokenEntry,
+) -> Result<(), RefreshTokenStoreError> {
+    let value = serialize_entry(entry)?;
+    conn.set_ex::<_, _, ()>(
+        get_token_key(token),
+        value,
+        REFRESH_TOKEN_TTL_SECONDS as u64,
+    )
+    .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+}
+
+// The revocation marker outlives every token issued before it was set
+fn revoke(conn: &mut Connection, family_id: &Uuid) -> Result<(), RefreshTokenStoreError> {
+    conn.set_ex::<_, _, ()>(
+        get_family_key(family_id),
+        true,
+        REFRESH_TOKEN_TTL_SECONDS as u64,
+    )
+    .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+}
+
+const REFRESH_TOKEN_PREFIX: &str = "refresh_token:";
+const REVOKED_FAMILY_PREFIX: &str = "revoked_refresh_token_family:";
+
+fn get_token_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_PREFIX, token.as_ref())
+}
+
+fn get_family_key(family_id: &Uuid) -> String {
+    format!("{}{}", REVOKED_FAMILY_PREFIX, family_id)
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{Session, SessionId, SessionStore, SessionStoreError},
+        Email,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisSessionStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        set_session(&mut conn, &session)?;
+
+        let user_key = get_user_key(&session.email);
+        conn.sadd::<_, _, ()>(&user_key, session.id.as_ref())
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+        conn.expire::<_, ()>(&user_key, REFRESH_TOKEN_TTL_SECONDS)
+            .map_err(|_| SessionStoreError::UnexpectedError)
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        get_session(&mut *self.conn.write().await, id)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let user_key = get_user_key(email);
+        let ids: Vec<String> = conn
+            .smembers(&user_key)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = Vec::new();
+        for id in ids {
+            let session_id =
+                SessionId::parse(&id).map_err(|_| SessionStoreError::UnexpectedError)?;
+            match get_session(&mut conn, &session_id) {
+                Ok(session) => sessions.push(session),
+                // The session expired, so drop it from the user's index
+                Err(SessionStoreError::SessionNotFound) => conn
+                    .srem::<_, _, ()>(&user_key, &id)
+                    .map_err(|_| SessionStoreError::UnexpectedError)?,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        last_seen: usize,
+    ) -> Result<Session, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let mut session = get_session(&mut conn, id)?;
+        session.last_seen = last_seen;
+        set_session(&mut conn, &session)?;
+
+        // The user's index has to outlive every session it lists
+        conn.expire::<_, ()>(get_user_key(&session.email), REFRESH_TOKEN_TTL_SECONDS)
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(session)
+    }
+
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let session = get_session(&mut conn, id)?;
+        conn.del::<_, ()>(get_session_key(id))
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+        conn.srem::<_, _, ()>(get_user_key(&session.email), id.as_ref())
+            .map_err(|_| SessionStoreError::UnexpectedError)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionEntry {
+    email: String,
+    created_at: usize,
+    last_seen: usize,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+fn get_session(conn: &mut Connection, id: &SessionId) -> Result<Session, SessionStoreError> {
+    let value = match conn.get::<_, Option<String>>(get_session_key(id)) {
+        Ok(Some(value)) => value,
+        Ok(None) => return Err(SessionStoreError::SessionNotFound),
+        Err(_) => return Err(SessionStoreError::UnexpectedError),
+    };
+
+    let entry: SessionEntry =
+        serde_json::from_str(&value).map_err(|_| SessionStoreError::UnexpectedError)?;
+
+    Ok(Session {
+        id: id.clone(),
+        email: Email::parse(&entry.email).map_err(|_| SessionStoreError::UnexpectedError)?,
+        created_at: entry.created_at,
+        last_seen: entry.last_seen,
+        user_agent: entry.user_agent,
+        ip_address: entry.ip_address,
+    })
+}
+
+// Every write pushes the expiry back, so a session lives as long as its refresh tokens
+fn set_session(conn: &mut Connection, session: &Session) -> Result<(), SessionStoreError> {
+    let entry = SessionEntry {
+        email: session.email.as_ref().to_owned(),
+        created_at: session.created_at,
+        last_seen: session.last_seen,
+        user_agent: session.user_agent.clone(),
+        ip_address: session.ip_address.clone(),
+    };
+    let value = serde_json::to_string(&entry).map_err(|_| SessionStoreError::UnexpectedError)?;
+
+    conn.set_ex::<_, _, ()>(
+        get_session_key(&session.id),
+        value,
+        REFRESH_TOKEN_TTL_SECONDS as u64,
+    )
+    .map_err(|_| SessionStoreError::UnexpectedError)
+}
+
+const SESSION_PREFIX: &str = "session:";
+const USER_SESSIONS_PREFIX: &str = "user_sessions:";
+
+fn get_session_key(id: &SessionId) -> String {
+    format!("{}{}", SESSION_PREFIX, id.as_ref())
+}
+
+fn get_user_key(email: &Email) -> String {
+    format!("{}{}", USER_SESSIONS_PREFIX, email.as_ref())
+}
@@ -4,9 +4,15 @@ use redis::{Commands, Connection};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptBinding, LoginAttemptId, TwoFAChallenge, TwoFACode, TwoFACodeResends,
+            TwoFACodeStore, TwoFACodeStoreError,
+        },
+        Email,
+    },
+    utils::auth::{LOGIN_ATTEMPT_TTL_SECONDS, MAX_PENDING_LOGIN_ATTEMPTS},
 };
 
 pub struct RedisTwoFACodeStore {
@@ -23,65 +29,349 @@ impl RedisTwoFACodeStore {
 impl TwoFACodeStore for RedisTwoFACodeStore {
     async fn add_code(
         &mut self,
-        email: Email,
         login_attempt_id: LoginAttemptId,
-        code: TwoFACode,
+        challenge: TwoFAChallenge,
+        started_at: usize,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
-        let two_fa_tuple = TwoFATuple(
-            login_attempt_id.as_ref().to_string(),
-            code.as_ref().to_string(),
-        );
-        let value = serde_json::to_string(&two_fa_tuple)
+        let pending_key = get_pending_key(&challenge.email);
+        let value = serde_json::to_string(&StoredChallenge::from(&challenge))
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-        self.conn
-            .write()
-            .await
-            .set_ex(key, value, TEN_MINUTES_IN_SECONDS)
+        let mut conn = self.conn.write().await;
+
+        let (pending,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .set_ex(
+                get_key(&login_attempt_id),
+                value,
+                LOGIN_ATTEMPT_TTL_SECONDS as u64,
+            )
+            .ignore()
+            .zadd(&pending_key, login_attempt_id.as_ref(), started_at)
+            .ignore()
+            .expire(&pending_key, LOGIN_ATTEMPT_TTL_SECONDS)
+            .ignore()
+            .zrange(&pending_key, 0, -1)
+            .query(&mut *conn)
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // Drop the oldest attempts beyond the limit, never the one just added
+        let excess = pending.len().saturating_sub(MAX_PENDING_LOGIN_ATTEMPTS);
+        let dropped: Vec<&String> = pending
+            .iter()
+            .filter(|id| id.as_str() != login_attempt_id.as_ref())
+            .take(excess)
+            .collect();
+
+        if !dropped.is_empty() {
+            let mut pipe = redis::pipe();
+            pipe.atomic().zrem(&pending_key, &dropped).ignore();
+            for id in dropped {
+                pipe.del(&[
+                    format!("{}{}", TWO_FA_CODE_PREFIX, id),
+                    format!("{}{}", TWO_FA_FAILED_ATTEMPTS_PREFIX, id),
+                    format!("{}{}", TWO_FA_RESENDS_PREFIX, id),
+                ])
+                .ignore();
+            }
+            pipe.query::<()>(&mut *conn)
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        }
+
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
-        self.conn
-            .write()
-            .await
-            .del(key)
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let challenge = self.get_code(login_attempt_id).await?;
+
+        // Only the caller whose DEL removed the code has consumed the attempt, so two
+        // replicas cannot both use it
+        let (removed,): (u32,) = redis::pipe()
+            .atomic()
+            .del(get_key(login_attempt_id))
+            .del(&[
+                get_failed_attempts_key(login_attempt_id),
+                get_resends_key(login_attempt_id),
+            ])
+            .ignore()
+            .zrem(get_pending_key(&challenge.email), login_attempt_id.as_ref())
+            .ignore()
+            .query(&mut *self.conn.write().await)
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-        Ok(())
+
+        match removed {
+            0 => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Ok(()),
+        }
     }
 
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let key = get_key(email);
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAChallenge, TwoFACodeStoreError> {
+        let key = get_key(login_attempt_id);
 
         match self.conn.write().await.get::<_, String>(&key) {
             Ok(value) => {
-                let data: TwoFATuple = serde_json::from_str(&value)
+                let data: StoredChallenge = serde_json::from_str(&value)
                     .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
-                let login_attempt_id = LoginAttemptId::parse(&data.0)
-                    .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+                data.try_into()
+            }
+            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+    }
 
-                let email_code =
-                    TwoFACode::parse(&data.1).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+    async fn get_pending(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<(LoginAttemptId, TwoFAChallenge)>, TwoFACodeStoreError> {
+        let ids: Vec<String> = self
+            .conn
+            .write()
+            .await
+            .zrange(get_pending_key(email), 0, -1)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
-                Ok((login_attempt_id, email_code))
+        let mut pending = Vec::new();
+        for id in ids {
+            let login_attempt_id =
+                LoginAttemptId::parse(&id).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+            // Attempts that expired are still listed until the list itself expires
+            match self.get_code(&login_attempt_id).await {
+                Ok(challenge) => pending.push((login_attempt_id, challenge)),
+                Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => (),
+                Err(e) => return Err(e),
             }
-            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+
+        Ok(pending)
+    }
+
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        count_failed_attempt(
+            &mut *self.conn.write().await,
+            &get_key(login_attempt_id),
+            &get_failed_attempts_key(login_attempt_id),
+        )?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn resend_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+        sent_at: usize,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut challenge = self.get_code(login_attempt_id).await?;
+        challenge.code = code;
+        let value = serde_json::to_string(&StoredChallenge::from(&challenge))
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let resends_key = get_resends_key(login_attempt_id);
+        redis::pipe()
+            .atomic()
+            .set_ex(
+                get_key(login_attempt_id),
+                value,
+                LOGIN_ATTEMPT_TTL_SECONDS as u64,
+            )
+            .ignore()
+            .hincr(&resends_key, RESENDS_COUNT_FIELD, 1)
+            .ignore()
+            .hset(&resends_key, RESENDS_LAST_SENT_AT_FIELD, sent_at)
+            .ignore()
+            .expire(&resends_key, LOGIN_ATTEMPT_TTL_SECONDS)
+            .ignore()
+            .query::<()>(&mut *self.conn.write().await)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_resends(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFACodeResends, TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let exists: bool = conn
+            .exists(get_key(login_attempt_id))
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        if !exists {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        let (count, last_sent_at): (Option<u32>, Option<usize>) = conn
+            .hget(
+                get_resends_key(login_attempt_id),
+                &[RESENDS_COUNT_FIELD, RESENDS_LAST_SENT_AT_FIELD],
+            )
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(TwoFACodeResends {
+            count: count.unwrap_or_default(),
+            last_sent_at: last_sent_at.unwrap_or_default(),
+        })
+    }
+
+    async fn add_reauth_code(
+        &mut self,
+        challenge: TwoFAChallenge,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let value = serde_json::to_string(&StoredChallenge::from(&challenge))
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        redis::pipe()
+            .atomic()
+            .set_ex(
+                get_reauth_key(&challenge.email),
+                value,
+                LOGIN_ATTEMPT_TTL_SECONDS as u64,
+            )
+            .ignore()
+            .del(get_reauth_failed_attempts_key(&challenge.email))
+            .ignore()
+            .query::<()>(&mut *self.conn.write().await)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+
+    async fn get_reauth_code(&self, email: &Email) -> Result<TwoFAChallenge, TwoFACodeStoreError> {
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(get_reauth_key(email))
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let value = value.ok_or(TwoFACodeStoreError::ReauthCodeNotFound)?;
+
+        let data: StoredChallenge =
+            serde_json::from_str(&value).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        data.try_into()
+    }
+
+    async fn record_failed_reauth_attempt(
+        &mut self,
+        email: &Email,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        count_failed_attempt(
+            &mut *self.conn.write().await,
+            &get_reauth_key(email),
+            &get_reauth_failed_attempts_key(email),
+        )?
+        .ok_or(TwoFACodeStoreError::ReauthCodeNotFound)
+    }
+
+    async fn remove_reauth_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let (removed,): (u32,) = redis::pipe()
+            .atomic()
+            .del(get_reauth_key(email))
+            .del(get_reauth_failed_attempts_key(email))
+            .ignore()
+            .query(&mut *self.conn.write().await)
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        match removed {
+            0 => Err(TwoFACodeStoreError::ReauthCodeNotFound),
+            _ => Ok(()),
         }
     }
 }
 
+// Count a wrong guess against the code stored at `code_key`, or return None when there
+// is no such code. WATCH makes the transaction fail and be retried if the code is removed
+// in between, so a count is never left behind for a code another replica consumed.
+fn count_failed_attempt(
+    conn: &mut Connection,
+    code_key: &str,
+    failed_attempts_key: &str,
+) -> Result<Option<u32>, TwoFACodeStoreError> {
+    redis::transaction(conn, &[code_key], |conn, pipe| {
+        let exists: bool = conn.exists(code_key)?;
+        if !exists {
+            return Ok(Some(None));
+        }
+
+        let result: Option<(u32,)> = pipe
+            .incr(failed_attempts_key, 1)
+            .expire(failed_attempts_key, LOGIN_ATTEMPT_TTL_SECONDS)
+            .ignore()
+            .query(conn)?;
+
+        Ok(result.map(|(attempts,)| Some(attempts)))
+    })
+    .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+}
+
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct StoredChallenge {
+    email: String,
+    code: String,
+    client_binding: String,
+}
+
+impl From<&TwoFAChallenge> for StoredChallenge {
+    fn from(challenge: &TwoFAChallenge) -> Self {
+        Self {
+            email: challenge.email.as_ref().to_owned(),
+            code: challenge.code.as_ref().to_owned(),
+            client_binding: challenge.client_binding.as_ref().to_owned(),
+        }
+    }
+}
+
+impl TryFrom<StoredChallenge> for TwoFAChallenge {
+    type Error = TwoFACodeStoreError;
+
+    fn try_from(data: StoredChallenge) -> Result<Self, Self::Error> {
+        Ok(Self {
+            email: Email::parse(&data.email).map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            code: TwoFACode::parse(&data.code).map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            client_binding: LoginAttemptBinding::parse(&data.client_binding)
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+        })
+    }
+}
 
-const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_PENDING_PREFIX: &str = "two_fa_pending:";
+const TWO_FA_FAILED_ATTEMPTS_PREFIX: &str = "two_fa_failed_attempts:";
+const TWO_FA_RESENDS_PREFIX: &str = "two_fa_resends:";
+const TWO_FA_REAUTH_CODE_PREFIX: &str = "two_fa_reauth_code:";
+const TWO_FA_REAUTH_FAILED_ATTEMPTS_PREFIX: &str = "two_fa_reauth_failed_attempts:";
+const RESENDS_COUNT_FIELD: &str = "count";
+const RESENDS_LAST_SENT_AT_FIELD: &str = "last_sent_at";
+
+fn get_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id.as_ref())
+}
+
+fn get_pending_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_PENDING_PREFIX, email.as_ref())
+}
+
+fn get_failed_attempts_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!(
+        "{}{}",
+        TWO_FA_FAILED_ATTEMPTS_PREFIX,
+        login_attempt_id.as_ref()
+    )
+}
+
+fn get_resends_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_RESENDS_PREFIX, login_attempt_id.as_ref())
+}
+
+fn get_reauth_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_REAUTH_CODE_PREFIX, email.as_ref())
+}
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
+fn get_reauth_failed_attempts_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_REAUTH_FAILED_ATTEMPTS_PREFIX, email.as_ref())
 }
@@ -0,0 +1,441 @@
+use std::{collections::HashMap, fs, time::Duration};
+
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::OnceCell;
+
+use crate::{
+    domain::{OidcLogin, OidcLoginState},
+    utils::constants::{AUTH_SERVICE_URL, OIDC_PROVIDERS_PATH},
+};
+
+const REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+// The upstream OpenID Connect providers users can sign in with, by name
+#[derive(Default)]
+pub struct OidcProviders {
+    providers: HashMap<String, OidcProvider>,
+}
+
+impl OidcProviders {
+    pub fn new(providers: Vec<OidcProvider>) -> Self {
+        let providers = providers
+            .into_iter()
+            .map(|provider| (provider.config.name.clone(), provider))
+            .collect();
+
+        Self { providers }
+    }
+
+    // Load the providers file named by OIDC_PROVIDERS_PATH, or none when it is not set
+    pub fn from_config() -> Result<Self, OidcProviderError> {
+        match OIDC_PROVIDERS_PATH.as_str() {
+            "" => Ok(Self::default()),
+            path => Self::from_file(path),
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, OidcProviderError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| OidcProviderError::InvalidConfig(format!("{path}: {e}")))?;
+        let config: OidcProvidersConfig = serde_json::from_str(&contents)
+            .map_err(|e| OidcProviderError::InvalidConfig(format!("{path}: {e}")))?;
+
+        let providers = config
+            .providers
+            .into_iter()
+            .map(OidcProvider::new)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self::new(providers))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.providers.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+#[derive(Deserialize)]
+struct OidcProvidersConfig {
+    providers: Vec<OidcProviderConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcProviderConfig {
+    // Names the provider in the /oidc/{name}/login and /oidc/{name}/callback routes
+    pub name: String,
+    // Discovery document is read from {issuer}/.well-known/openid-configuration
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["openid".to_owned(), "email".to_owned()]
+}
+
+// An upstream provider we are registered with as a confidential client
+pub struct OidcProvider {
+    config: OidcProviderConfig,
+    http_client: reqwest::Client,
+    // Fetched on first use, since the provider may not be reachable at startup
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OidcProviderError {
+    InvalidConfig(String),
+    InvalidIdToken(String),
+    RequestFailed(String),
+}
+
+// The user an upstream provider vouched for in a verified ID token
+#[derive(Debug, PartialEq)]
+pub struct UpstreamIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+}
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcProviderConfig) -> Result<Self, OidcProviderError> {
+        let valid_name = !config.name.is_empty()
+            && config
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+        if !valid_name {
+            return Err(OidcProviderError::InvalidConfig(format!(
+                "Invalid provider name: {}",
+                config.name
+            )));
+        }
+        if !config.scopes.iter().any(|scope| scope == "openid") {
+            return Err(OidcProviderError::InvalidConfig(format!(
+                "{} must request the openid scope",
+                config.name
+            )));
+        }
+
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+            .build()
+            .map_err(|e| OidcProviderError::InvalidConfig(e.to_string()))?;
+
+        Ok(Self {
+            config,
+            http_client,
+            metadata: OnceCell::new(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    // Where the provider sends the user back to, which must be registered with it
+    pub fn redirect_uri(&self) -> String {
+        format!(
+            "{}/oidc/{}/callback",
+            AUTH_SERVICE_URL.as_str(),
+            self.config.name
+        )
+    }
+
+    // Build the URL that starts the authorization code flow at the provider
+    pub async fn authorization_url(
+        &self,
+        state: &OidcLoginState,
+        login: &OidcLogin,
+    ) -> Result<String, OidcProviderError> {
+        let metadata = self.metadata().await?;
+        let scope = self.config.scopes.join(" ");
+        let code_challenge = login.code_challenge();
+        let redirect_uri = self.redirect_uri();
+
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("scope", scope.as_str()),
+            ("state", state.as_ref()),
+            ("nonce", login.nonce.as_str()),
+            ("code_challenge", code_challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(|e| OidcProviderError::RequestFailed(e.to_string()))?;
+
+        let separator = match metadata.authorization_endpoint.contains('?') {
+            true => '&',
+            false => '?',
+        };
+
+        Ok(format!(
+            "{}{}{}",
+            metadata.authorization_endpoint, separator, query
+        ))
+    }
+
+    // Redeem the code the provider sent back and verify the ID token it returns
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        login: &OidcLogin,
+    ) -> Result<UpstreamIdentity, OidcProviderError> {
+        let metadata = self.metadata().await?;
+        let redirect_uri = self.redirect_uri();
+
+        let request = self
+            .http_client
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri.as_str()),
+                ("code_verifier", login.code_verifier.as_str()),
+            ]);
+        let response: TokenResponse = send(request).await?;
+
+        // Keys are fetched for every login so that rotated keys are picked up
+        let jwks: JwkSet = send(self.http_client.get(&metadata.jwks_uri)).await?;
+
+        self.verify_id_token(&response.id_token, &jwks, &login.nonce)
+    }
+
+    fn verify_id_token(
+        &self,
+        id_token: &str,
+        jwks: &JwkSet,
+        nonce: &str,
+    ) -> Result<UpstreamIdentity, OidcProviderError> {
+        let invalid_id_token =
+            |e: jsonwebtoken::errors::Error| OidcProviderError::InvalidIdToken(e.to_string());
+
+        let header = decode_header(id_token).map_err(invalid_id_token)?;
+        // Only the provider's published keys may sign, never the client secret
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(OidcProviderError::InvalidIdToken(format!(
+                "{:?} is not accepted",
+                header.alg
+            )));
+        }
+
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| OidcProviderError::InvalidIdToken("Unknown signing key".to_owned()))?;
+        let decoding_key = DecodingKey::from_jwk(jwk).map_err(invalid_id_token)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+            .map_err(invalid_id_token)?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcProviderError::InvalidIdToken(
+                "Nonce does not match".to_owned(),
+            ));
+        }
+
+        Ok(UpstreamIdentity {
+            subject: claims.sub,
+            email: claims.email,
+            email_verified: claims.email_verified,
+        })
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata, OidcProviderError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                let metadata: ProviderMetadata = send(self.http_client.get(url)).await?;
+
+                // OpenID Connect Discovery section 4.3
+                match metadata.issuer == self.config.issuer {
+                    true => Ok(metadata),
+                    false => Err(OidcProviderError::InvalidConfig(format!(
+                        "{} reports issuer {}",
+                        self.config.issuer, metadata.issuer
+                    ))),
+                }
+            })
+            .await
+    }
+}
+
+async fn send<T: DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+) -> Result<T, OidcProviderError> {
+    let request_failed = |e: reqwest::Error| OidcProviderError::RequestFailed(e.to_string());
+
+    request
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(request_failed)?
+        .json()
+        .await
+        .map_err(request_failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::signing_key::SigningKey;
+    use chrono::Utc;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    const RS256_PRIVATE: &[u8] = include_bytes!("../../tests/keys/rs256_private.pem");
+    const RS256_PUBLIC: &[u8] = include_bytes!("../../tests/keys/rs256_public.pem");
+
+    fn provider() -> OidcProvider {
+        OidcProvider::new(OidcProviderConfig {
+            name: "corp".to_owned(),
+            issuer: "https://idp.example.com".to_owned(),
+            client_id: "auth-service".to_owned(),
+            client_secret: "secret".to_owned(),
+            scopes: default_scopes(),
+        })
+        .unwrap()
+    }
+
+    fn jwks() -> JwkSet {
+        let key =
+            SigningKey::from_pem(Algorithm::RS256, Some("idp".to_owned()), None, RS256_PUBLIC)
+                .unwrap();
+
+        JwkSet {
+            keys: vec![key.jwk().unwrap().clone()],
+        }
+    }
+
+    fn claims() -> Value {
+        let now = Utc::now().timestamp();
+        json!({
+            "iss": "https://idp.example.com",
+            "aud": "auth-service",
+            "sub": "user-1",
+            "exp": now + 300,
+            "iat": now,
+            "nonce": "nonce",
+            "email": "test@example.com",
+            "email_verified": true
+        })
+    }
+
+    fn id_token(claims: &Value) -> String {
+        let header = Header {
+            kid: Some("idp".to_owned()),
+            ..Header::new(Algorithm::RS256)
+        };
+        encode(
+            &header,
+            claims,
+            &EncodingKey::from_rsa_pem(RS256_PRIVATE).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn valid_id_token_returns_identity() {
+        let identity = provider()
+            .verify_id_token(&id_token(&claims()), &jwks(), "nonce")
+            .unwrap();
+
+        assert_eq!(
+            identity,
+            UpstreamIdentity {
+                subject: "user-1".to_owned(),
+                email: Some("test@example.com".to_owned()),
+                email_verified: true,
+            }
+        );
+    }
+
+    #[test]
+    fn id_token_for_another_login_returns_err() {
+        let result = provider().verify_id_token(&id_token(&claims()), &jwks(), "other");
+        assert!(matches!(result, Err(OidcProviderError::InvalidIdToken(_))));
+    }
+
+    #[test]
+    fn id_token_for_another_client_or_issuer_returns_err() {
+        for (claim, value) in [("aud", "other-client"), ("iss", "https://evil.example.com")] {
+            let mut claims = claims();
+            claims[claim] = json!(value);
+
+            let result = provider().verify_id_token(&id_token(&claims), &jwks(), "nonce");
+            assert!(
+                matches!(result, Err(OidcProviderError::InvalidIdToken(_))),
+                "Failed for claim: {claim}"
+            );
+        }
+    }
+
+    #[test]
+    fn id_token_signed_with_client_secret_returns_err() {
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        let result = provider().verify_id_token(&token, &jwks(), "nonce");
+        assert!(matches!(result, Err(OidcProviderError::InvalidIdToken(_))));
+    }
+
+    #[test]
+    fn provider_without_openid_scope_returns_err() {
+        let result = OidcProvider::new(OidcProviderConfig {
+            scopes: vec!["email".to_owned()],
+            ..provider().config
+        });
+        assert!(matches!(result, Err(OidcProviderError::InvalidConfig(_))));
+    }
+}
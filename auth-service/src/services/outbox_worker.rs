@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+    app_state::{EmailClientType, EmailOutboxType},
+    domain::{EmailOutboxError, OutboxEmail},
+};
+
+// How many emails one pass of the worker claims
+const BATCH_SIZE: usize = 10;
+
+// A claimed email is handed out again if the worker has not finished with it by then,
+// for example because the replica holding it stopped
+const CLAIM_LEASE_SECONDS: usize = 300;
+
+// Emails that still fail after this many attempts are kept for admins to look at
+pub const MAX_EMAIL_DELIVERY_ATTEMPTS: u32 = 8;
+
+const FIRST_RETRY_DELAY_SECONDS: usize = 30;
+const MAX_RETRY_DELAY_SECONDS: usize = 3_600; // 1 hour
+
+// Delivers the emails queued in the outbox, retrying failures with exponential backoff
+pub struct OutboxWorker {
+    outbox: EmailOutboxType,
+    email_client: EmailClientType,
+    poll_interval: Duration,
+}
+
+impl OutboxWorker {
+    pub fn new(outbox: EmailOutboxType, email_client: EmailClientType) -> Self {
+        Self {
+            outbox,
+            email_client,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub async fn run(self) {
+        loop {
+            let Ok(now) = Utc::now().timestamp().try_into() else {
+                return;
+            };
+
+            if let Err(e) = self.deliver_due(now).await {
+                println!("failed to deliver queued emails: {:?}", e);
+            }
+
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    // Try every email that is due once, returning how many were sent
+    pub async fn deliver_due(&self, now: usize) -> Result<usize, EmailOutboxError> {
+        let emails = self
+            .outbox
+            .write()
+            .await
+            .claim_due(now, now.saturating_add(CLAIM_LEASE_SECONDS), BATCH_SIZE)
+            .await?;
+
+        let mut sent = 0;
+        for email in emails {
+            // The outbox is not locked while sending, which may be slow
+            match self
+                .email_client
+                .send_message(&email.recipient, &email.message)
+                .await
+            {
+                Ok(()) => {
+                    self.outbox.write().await.mark_sent(&email.id).await?;
+                    sent += 1;
+                }
+                Err(e) => self.record_failure(&email, &e, now).await?,
+            }
+        }
+
+        Ok(sent)
+    }
+
+    async fn record_failure(
+        &self,
+        email: &OutboxEmail,
+        error: &str,
+        now: usize,
+    ) -> Result<(), EmailOutboxError> {
+        let mut outbox = self.outbox.write().await;
+
+        if email.attempts >= MAX_EMAIL_DELIVERY_ATTEMPTS {
+            outbox.dead_letter(&email.id, error, now).await
+        } else {
+            let retry_at = now.saturating_add(retry_delay_seconds(email.attempts));
+            outbox.retry_later(&email.id, error, retry_at).await
+        }
+    }
+}
+
+// 30 seconds after the first failure, doubling with every further one up to an hour
+fn retry_delay_seconds(attempts: u32) -> usize {
+    let doublings = attempts.saturating_sub(1).min(usize::BITS - 1);
+
+    FIRST_RETRY_DELAY_SECONDS
+        .saturating_mul(1 << doublings)
+        .min(MAX_RETRY_DELAY_SECONDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::{
+        domain::{Email, EmailClient, EmailMessage, EmailOutbox},
+        services::HashmapEmailOutbox,
+    };
+
+    const LATER: usize = usize::MAX / 2;
+
+    #[derive(Default)]
+    struct FlakyEmailClient {
+        failing: AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailClient for FlakyEmailClient {
+        async fn send_email(&self, _: &Email, _: &str, _: &str) -> Result<(), String> {
+            match self.failing.load(Ordering::SeqCst) {
+                true => Err("Connection refused".to_owned()),
+                false => Ok(()),
+            }
+        }
+    }
+
+    async fn worker_with_email(
+        failing: bool,
+    ) -> (
+        OutboxWorker,
+        Arc<RwLock<HashmapEmailOutbox>>,
+        Arc<FlakyEmailClient>,
+    ) {
+        let outbox = Arc::new(RwLock::new(HashmapEmailOutbox::default()));
+        outbox
+            .write()
+            .await
+            .enqueue(
+                &Email::parse("test@example.com").unwrap(),
+                &EmailMessage::plain("Subject", "Content"),
+            )
+            .await
+            .unwrap();
+        let email_client = Arc::new(FlakyEmailClient::default());
+        email_client.failing.store(failing, Ordering::SeqCst);
+
+        let worker = OutboxWorker::new(outbox.clone(), email_client.clone());
+        (worker, outbox, email_client)
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_an_hour() {
+        assert_eq!(retry_delay_seconds(1), 30);
+        assert_eq!(retry_delay_seconds(2), 60);
+        assert_eq!(retry_delay_seconds(3), 120);
+        assert_eq!(retry_delay_seconds(7), 1_920);
+        assert_eq!(retry_delay_seconds(8), 3_600);
+        assert_eq!(retry_delay_seconds(u32::MAX), 3_600);
+    }
+
+    #[tokio::test]
+    async fn test_sent_emails_are_removed() {
+        let (worker, outbox, _) = worker_with_email(false).await;
+
+        assert_eq!(worker.deliver_due(LATER).await, Ok(1));
+        assert_eq!(
+            outbox
+                .write()
+                .await
+                .claim_due(usize::MAX, usize::MAX, 10)
+                .await,
+            Ok(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_emails_are_retried_after_backoff() {
+        let (worker, outbox, email_client) = worker_with_email(true).await;
+
+        assert_eq!(worker.deliver_due(LATER).await, Ok(0));
+
+        // Not retried before the first delay has passed
+        email_client.failing.store(false, Ordering::SeqCst);
+        assert_eq!(worker.deliver_due(LATER + 29).await, Ok(0));
+        assert_eq!(worker.deliver_due(LATER + 30).await, Ok(1));
+        assert_eq!(outbox.read().await.get_failed().await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_emails_are_dead_lettered_after_max_attempts() {
+        let (worker, outbox, _) = worker_with_email(true).await;
+
+        let mut now = LATER;
+        for attempt in 1..=MAX_EMAIL_DELIVERY_ATTEMPTS {
+            assert_eq!(worker.deliver_due(now).await, Ok(0));
+            now += retry_delay_seconds(attempt);
+        }
+
+        let failed = outbox.read().await.get_failed().await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempts, MAX_EMAIL_DELIVERY_ATTEMPTS);
+        assert_eq!(failed[0].last_error.as_deref(), Some("Connection refused"));
+        // Dead lettered emails are not tried again
+        assert_eq!(worker.deliver_due(usize::MAX).await, Ok(0));
+    }
+}
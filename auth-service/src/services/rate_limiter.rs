@@ -0,0 +1,460 @@
+use std::{fs, net::SocketAddr, sync::Arc};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::header::CONTENT_TYPE,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    app_state::RateLimitStoreType,
+    domain::{AuthAPIError, ClientId, RateLimit, RateLimitDecision, RateLimitKey},
+    utils::constants::RATE_LIMITS_PATH,
+};
+
+// Bodies are read to find the email a request is for, so only small ones are accepted
+// on routes limited by it
+const MAX_BUFFERED_BODY_BYTES: usize = 64 * 1024;
+
+// A token bucket limit on one route, counted separately for each value of the key
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitRule {
+    // Route path as registered with the router, such as /sessions/:id, or * for every route
+    pub route: String,
+    pub key: RateLimitKeyKind,
+    #[serde(flatten)]
+    pub limit: RateLimit,
+}
+
+impl RateLimitRule {
+    pub fn new(route: &str, key: RateLimitKeyKind, capacity: u32, period_seconds: u64) -> Self {
+        Self {
+            route: route.to_owned(),
+            key,
+            limit: RateLimit {
+                capacity,
+                period_seconds,
+            },
+        }
+    }
+
+    fn applies_to(&self, route: &str) -> bool {
+        self.route == "*" || self.route == route
+    }
+}
+
+// What requests are counted by. Requests without an email are not counted by rules
+// keyed on it. Client ids are only counted once the client has authenticated, since
+// otherwise anyone could use up a client's allowance by sending its id with a wrong
+// secret.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RateLimitKeyKind {
+    Ip,
+    Email,
+    ClientId,
+}
+
+impl AsRef<str> for RateLimitKeyKind {
+    fn as_ref(&self) -> &str {
+        match self {
+            RateLimitKeyKind::Ip => "ip",
+            RateLimitKeyKind::Email => "email",
+            RateLimitKeyKind::ClientId => "clientId",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RateLimiterError {
+    InvalidConfig(String),
+}
+
+#[derive(Deserialize)]
+struct RateLimitsConfig {
+    rules: Vec<RateLimitRule>,
+}
+
+pub struct RateLimiter {
+    rules: Vec<RateLimitRule>,
+    store: RateLimitStoreType,
+}
+
+impl RateLimiter {
+    pub fn new(
+        rules: Vec<RateLimitRule>,
+        store: RateLimitStoreType,
+    ) -> Result<Self, RateLimiterError> {
+        if let Some(rule) = rules
+            .iter()
+            .find(|rule| rule.limit.capacity == 0 || rule.limit.period_seconds == 0)
+        {
+            return Err(RateLimiterError::InvalidConfig(format!(
+                "{} {}: capacity and periodSeconds must be at least 1",
+                rule.route,
+                rule.key.as_ref()
+            )));
+        }
+
+        Ok(Self { rules, store })
+    }
+
+    // Load the rules file named by RATE_LIMITS_PATH, or the default rules when it is not set
+    pub fn from_config(store: RateLimitStoreType) -> Result<Self, RateLimiterError> {
+        match RATE_LIMITS_PATH.as_str() {
+            "" => Self::new(Self::default_rules(), store),
+            path => Self::from_file(path, store),
+        }
+    }
+
+    pub fn from_file(path: &str, store: RateLimitStoreType) -> Result<Self, RateLimiterError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| RateLimiterError::InvalidConfig(format!("{path}: {e}")))?;
+        let config: RateLimitsConfig = serde_json::from_str(&contents)
+            .map_err(|e| RateLimiterError::InvalidConfig(format!("{path}: {e}")))?;
+
+        Self::new(config.rules, store)
+    }
+
+    // Generous limits on everything, with tighter ones on the routes that check
+    // secrets or send email
+    pub fn default_rules() -> Vec<RateLimitRule> {
+        use RateLimitKeyKind::*;
+
+        vec![
+            RateLimitRule::new("*", Ip, 300, 60),
+            RateLimitRule::new("/login", Ip, 30, 60),
+            RateLimitRule::new("/login", Email, 20, 60),
+            RateLimitRule::new("/login/magic-link", Email, 5, 900),
+            RateLimitRule::new("/passkeys/login/start", Ip, 30, 60),
+            RateLimitRule::new("/password-reset/request", Email, 5, 900),
+            RateLimitRule::new("/resend-2fa", Ip, 10, 60),
+            RateLimitRule::new("/signup", Ip, 10, 60),
+            RateLimitRule::new("/introspect", ClientId, 600, 60),
+            RateLimitRule::new("/revoke", ClientId, 120, 60),
+            RateLimitRule::new("/token", ClientId, 120, 60),
+            RateLimitRule::new("/change-password", Ip, 10, 60),
+            RateLimitRule::new("/2fa/disable", Ip, 10, 60),
+            RateLimitRule::new("/2fa/email-code", Ip, 10, 60),
+            RateLimitRule::new("/2fa/enable", Ip, 10, 60),
+            RateLimitRule::new("/2fa/passkey/enable", Ip, 10, 60),
+            RateLimitRule::new("/2fa/recovery-codes", Ip, 10, 60),
+            RateLimitRule::new("/2fa/totp/confirm", Ip, 10, 60),
+            RateLimitRule::new("/passkeys/register/start", Ip, 10, 60),
+            RateLimitRule::new("/password-login/disable", Ip, 10, 60),
+            RateLimitRule::new("/password-login/enable", Ip, 10, 60),
+            RateLimitRule::new("/verify-2fa", Ip, 30, 60),
+            RateLimitRule::new("/verify-2fa", Email, 20, 60),
+            RateLimitRule::new("/verify-email/resend", Email, 5, 900),
+        ]
+    }
+
+    // Take a token from every bucket the request falls in, handing the request back
+    // when none of them are empty. Client buckets are left to check_client.
+    async fn check(&self, request: Request) -> Result<Request, AuthAPIError> {
+        let route = match request.extensions().get::<MatchedPath>() {
+            Some(path) => path.as_str().to_owned(),
+            None => request.uri().path().to_owned(),
+        };
+        let rules: Vec<&RateLimitRule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.key != RateLimitKeyKind::ClientId && rule.applies_to(&route))
+            .collect();
+
+        if rules.is_empty() {
+            return Ok(request);
+        }
+
+        let ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string());
+
+        // Only read the body when a rule needs something from it
+        let (request, email) = match rules.iter().any(|rule| rule.key == RateLimitKeyKind::Email) {
+            true => read_email(request).await?,
+            false => (request, None),
+        };
+
+        let buckets = rules.into_iter().filter_map(|rule| {
+            let value = match rule.key {
+                RateLimitKeyKind::Ip => ip.as_deref(),
+                RateLimitKeyKind::Email => email.as_deref(),
+                RateLimitKeyKind::ClientId => None,
+            };
+            Some((rule, value?))
+        });
+        self.take_tokens(buckets).await?;
+
+        Ok(request)
+    }
+
+    // Take a token from the client buckets of the route, once the client has authenticated
+    pub async fn check_client(
+        &self,
+        route: &str,
+        client_id: &ClientId,
+    ) -> Result<(), AuthAPIError> {
+        let buckets = self
+            .rules
+            .iter()
+            .filter(|rule| rule.key == RateLimitKeyKind::ClientId && rule.applies_to(route))
+            .map(|rule| (rule, client_id.as_ref()));
+
+        self.take_tokens(buckets).await
+    }
+
+    // Take a token from each bucket, stopping at the first that is empty. The tokens
+    // already taken are put back then, so a turned away request uses up no allowance.
+    async fn take_tokens<'a>(
+        &self,
+        buckets: impl Iterator<Item = (&'a RateLimitRule, &'a str)>,
+    ) -> Result<(), AuthAPIError> {
+        let now_ms: u64 = Utc::now()
+            .timestamp_millis()
+            .try_into()
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        let mut store = self.store.write().await;
+        let mut taken = Vec::new();
+
+        for (rule, value) in buckets {
+            let key = RateLimitKey::new(&rule.route, rule.key.as_ref(), value);
+            match store
+                .take_token(&key, &rule.limit, now_ms)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?
+            {
+                RateLimitDecision::Allowed => taken.push((key, rule)),
+                RateLimitDecision::Limited {
+                    retry_after_seconds,
+                } => {
+                    for (key, rule) in taken {
+                        store
+                            .return_token(&key, &rule.limit)
+                            .await
+                            .map_err(|_| AuthAPIError::UnexpectedError)?;
+                    }
+                    return Err(AuthAPIError::RateLimited(retry_after_seconds));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Middleware applying the rate limiter to every route
+pub async fn rate_limit(
+    State(rate_limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match rate_limiter.check(request).await {
+        Ok(request) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+// Find the email in the query or the JSON or form body, and rebuild the request around
+// the body that was read
+async fn read_email(request: Request) -> Result<(Request, Option<String>), AuthAPIError> {
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_BUFFERED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(AuthAPIError::PayloadTooLarge),
+    };
+
+    let is_json = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let body_params: Vec<(String, String)> = match is_json {
+        true => match serde_json::from_slice::<Value>(&bytes) {
+            Ok(Value::Object(fields)) => fields
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, value.as_str()?.to_owned())))
+                .collect(),
+            _ => vec![],
+        },
+        false => serde_urlencoded::from_bytes(&bytes).unwrap_or_default(),
+    };
+    let query_params: Vec<(String, String)> = parts
+        .uri
+        .query()
+        .and_then(|query| serde_urlencoded::from_str(query).ok())
+        .unwrap_or_default();
+    let param = |name: &str| {
+        body_params
+            .iter()
+            .chain(query_params.iter())
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.clone())
+    };
+
+    // Emails are compared case-insensitively so changing case does not get a new bucket
+    let email = param("email").map(|email| email.trim().to_lowercase());
+
+    Ok((Request::from_parts(parts, Body::from(bytes)), email))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::services::HashmapRateLimitStore;
+
+    fn rate_limiter(rules: Vec<RateLimitRule>) -> RateLimiter {
+        RateLimiter::new(
+            rules,
+            Arc::new(RwLock::new(HashmapRateLimitStore::default())),
+        )
+        .unwrap()
+    }
+
+    fn login_request(email: &str) -> Request {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/login")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({ "email": email, "password": "Password123!" }).to_string(),
+            ))
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 1234))));
+        request
+    }
+
+    #[tokio::test]
+    async fn request_over_limit_returns_err() {
+        let limiter = rate_limiter(vec![RateLimitRule::new(
+            "/login",
+            RateLimitKeyKind::Ip,
+            1,
+            60,
+        )]);
+
+        assert!(limiter.check(login_request("a@example.com")).await.is_ok());
+        assert!(matches!(
+            limiter.check(login_request("b@example.com")).await,
+            Err(AuthAPIError::RateLimited(60))
+        ));
+    }
+
+    #[tokio::test]
+    async fn emails_are_counted_separately_ignoring_case() {
+        let limiter = rate_limiter(vec![RateLimitRule::new(
+            "/login",
+            RateLimitKeyKind::Email,
+            1,
+            60,
+        )]);
+
+        assert!(limiter.check(login_request("a@example.com")).await.is_ok());
+        assert!(limiter.check(login_request("b@example.com")).await.is_ok());
+        assert!(limiter.check(login_request("A@example.com")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn limited_requests_do_not_use_up_other_buckets() {
+        let limiter = rate_limiter(vec![
+            RateLimitRule::new("/login", RateLimitKeyKind::Ip, 2, 60),
+            RateLimitRule::new("/login", RateLimitKeyKind::Email, 1, 60),
+        ]);
+
+        assert!(limiter.check(login_request("a@example.com")).await.is_ok());
+        assert!(limiter.check(login_request("a@example.com")).await.is_err());
+        assert!(limiter.check(login_request("b@example.com")).await.is_ok());
+        assert!(limiter.check(login_request("c@example.com")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn body_is_passed_on_unchanged() {
+        let limiter = rate_limiter(vec![RateLimitRule::new(
+            "*",
+            RateLimitKeyKind::Email,
+            1,
+            60,
+        )]);
+
+        let Ok(request) = limiter.check(login_request("a@example.com")).await else {
+            panic!("request should not be limited");
+        };
+        let body = to_bytes(request.into_body(), MAX_BUFFERED_BODY_BYTES)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap()["email"],
+            "a@example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn client_ids_are_only_counted_once_authenticated() {
+        let limiter = rate_limiter(vec![RateLimitRule::new(
+            "/token",
+            RateLimitKeyKind::ClientId,
+            1,
+            60,
+        )]);
+        let token_request = || {
+            Request::builder()
+                .method("POST")
+                .uri("/token")
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(
+                    "grant_type=client_credentials&client_id=report-job",
+                ))
+                .unwrap()
+        };
+        let client_id = ClientId::parse("report-job").unwrap();
+
+        assert!(limiter.check(token_request()).await.is_ok());
+        assert!(limiter.check(token_request()).await.is_ok());
+        assert!(limiter.check_client("/token", &client_id).await.is_ok());
+        assert!(matches!(
+            limiter.check_client("/token", &client_id).await,
+            Err(AuthAPIError::RateLimited(60))
+        ));
+    }
+
+    #[test]
+    fn rule_without_capacity_returns_err() {
+        let rules = vec![RateLimitRule::new("/login", RateLimitKeyKind::Ip, 0, 60)];
+
+        assert!(RateLimiter::new(
+            rules,
+            Arc::new(RwLock::new(HashmapRateLimitStore::default()))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rules_parse_from_config() {
+        let config: RateLimitsConfig = serde_json::from_str(
+            r#"{"rules": [{"route": "/token", "key": "clientId", "capacity": 10, "periodSeconds": 60}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.rules,
+            vec![RateLimitRule::new(
+                "/token",
+                RateLimitKeyKind::ClientId,
+                10,
+                60
+            )]
+        );
+    }
+}
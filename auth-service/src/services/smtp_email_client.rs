@@ -0,0 +1,225 @@
+use std::{str::FromStr, time::Duration};
+
+use lettre::{
+    message::{header::ContentType, Mailbox, MessageBuilder, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::{
+    domain::{Email, EmailClient, EmailMessage},
+    utils::constants::{EMAIL_FROM, SMTP_HOST, SMTP_PASSWORD, SMTP_PORT, SMTP_TLS, SMTP_USERNAME},
+};
+
+const SEND_TIMEOUT_SECONDS: u64 = 10;
+
+// How the connection to the SMTP server is secured
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmtpTls {
+    // Plain connection, only meant for local relays and tests
+    None,
+    // Plain connection upgraded with STARTTLS before authenticating
+    StartTls,
+    // TLS from the first byte, usually on port 465
+    Implicit,
+}
+
+impl SmtpTls {
+    fn default_port(&self) -> u16 {
+        match self {
+            SmtpTls::None => 25,
+            SmtpTls::StartTls => 587,
+            SmtpTls::Implicit => 465,
+        }
+    }
+}
+
+impl FromStr for SmtpTls {
+    type Err = SmtpEmailClientError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(SmtpTls::None),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Implicit),
+            _ => Err(SmtpEmailClientError::InvalidConfig(format!(
+                "Unknown SMTP TLS mode {value}, expected none, starttls or tls"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    // The usual port for the TLS mode when not set
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    // Username and password, when the server requires authentication
+    pub credentials: Option<(String, String)>,
+    // Mailbox emails are sent from, which may include a display name
+    pub from: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SmtpEmailClientError {
+    InvalidConfig(String),
+}
+
+pub struct SmtpEmailClient {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpEmailClient {
+    pub fn new(config: SmtpConfig) -> Result<Self, SmtpEmailClientError> {
+        let from = config.from.parse::<Mailbox>().map_err(|e| {
+            SmtpEmailClientError::InvalidConfig(format!(
+                "Invalid from address {}: {e}",
+                config.from
+            ))
+        })?;
+
+        let builder = match config.tls {
+            SmtpTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(config.host.as_str())
+            }
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|e| SmtpEmailClientError::InvalidConfig(e.to_string()))?,
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| SmtpEmailClientError::InvalidConfig(e.to_string()))?,
+        };
+        let mut builder = builder
+            .port(config.port.unwrap_or_else(|| config.tls.default_port()))
+            .timeout(Some(Duration::from_secs(SEND_TIMEOUT_SECONDS)));
+        if let Some((username, password)) = config.credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            from,
+            transport: builder.build(),
+        })
+    }
+
+    // Build the client from the SMTP_* and EMAIL_FROM settings
+    pub fn from_config() -> Result<Self, SmtpEmailClientError> {
+        let port = match SMTP_PORT.as_str() {
+            "" => None,
+            port => Some(port.parse().map_err(|_| {
+                SmtpEmailClientError::InvalidConfig(format!("Invalid SMTP port {port}"))
+            })?),
+        };
+        let credentials = match SMTP_USERNAME.as_str() {
+            "" => None,
+            username => Some((username.to_owned(), SMTP_PASSWORD.to_owned())),
+        };
+
+        Self::new(SmtpConfig {
+            host: SMTP_HOST.to_owned(),
+            port,
+            tls: SMTP_TLS.parse()?,
+            credentials,
+            from: EMAIL_FROM.to_owned(),
+        })
+    }
+
+    fn message_builder(&self, recipient: &Email, subject: &str) -> Result<MessageBuilder, String> {
+        let to = recipient
+            .as_ref()
+            .parse::<Mailbox>()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject))
+    }
+
+    async fn send(&self, message: Message) -> Result<(), String> {
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), String> {
+        let message = self
+            .message_builder(recipient, subject)?
+            .header(ContentType::TEXT_PLAIN)
+            .body(content.to_owned())
+            .map_err(|e| e.to_string())?;
+
+        self.send(message).await
+    }
+
+    async fn send_message(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        let Some(html) = &message.html else {
+            return self
+                .send_email(recipient, &message.subject, &message.text)
+                .await;
+        };
+
+        let message = self
+            .message_builder(recipient, &message.subject)?
+            .multipart(MultiPart::alternative_plain_html(
+                message.text.clone(),
+                html.clone(),
+            ))
+            .map_err(|e| e.to_string())?;
+
+        self.send(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(tls: SmtpTls) -> SmtpConfig {
+        SmtpConfig {
+            host: "localhost".to_owned(),
+            port: None,
+            tls,
+            credentials: None,
+            from: "Auth Service <no-reply@example.com>".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse_tls_mode() {
+        assert_eq!("none".parse(), Ok(SmtpTls::None));
+        assert_eq!("starttls".parse(), Ok(SmtpTls::StartTls));
+        assert_eq!("tls".parse(), Ok(SmtpTls::Implicit));
+        assert!("ssl".parse::<SmtpTls>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_new_accepts_every_tls_mode() {
+        for tls in [SmtpTls::None, SmtpTls::StartTls, SmtpTls::Implicit] {
+            assert!(SmtpEmailClient::new(config(tls)).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_rejects_invalid_from_address() {
+        let config = SmtpConfig {
+            from: "not an address".to_owned(),
+            ..config(SmtpTls::StartTls)
+        };
+
+        assert!(matches!(
+            SmtpEmailClient::new(config),
+            Err(SmtpEmailClientError::InvalidConfig(_))
+        ));
+    }
+}
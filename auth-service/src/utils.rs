@@ -1,2 +1,5 @@
 pub mod auth;
 pub mod constants;
+pub mod email_templates;
+pub mod key_ring;
+pub mod signing_key;
@@ -1,15 +1,40 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::{
+    extract::MatchedPath,
+    http::{header::AUTHORIZATION, HeaderMap},
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::Duration;
 
-use crate::{app_state::BannedTokenStoreType, domain::Email};
+use crate::{
+    app_state::{
+        AppState, BannedTokenStoreType, ClientStoreType, RefreshTokenStoreType, SessionStoreType,
+    },
+    domain::{
+        AuthAPIError, AuthorizationGrant, ClientId, ClientSecret, ClientStoreError, Email,
+        MagicLinkId, RefreshToken, RefreshTokenFamily, RefreshTokenStoreError, Session, SessionId,
+        SessionStoreError,
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    constants::{ADMIN_API_TOKEN, AUTH_SERVICE_URL, JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    key_ring::key_ring,
+};
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token for the given session
+pub fn generate_auth_cookie(
+    email: &Email,
+    session_id: &SessionId,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(email, session_id)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -24,69 +49,619 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Start a new refresh token family for the session and create a cookie for its first token
+pub async fn generate_refresh_cookie(
+    email: &Email,
+    session_id: &SessionId,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>, RefreshTokenStoreError> {
+    let token = RefreshToken::default();
+
+    refresh_token_store
+        .write()
+        .await
+        .add_token(
+            token.clone(),
+            RefreshTokenFamily::new(email.clone(), session_id.clone()),
+        )
+        .await?;
+
+    Ok(create_refresh_cookie(&token))
+}
+
+// Unlike the JWT cookie, the refresh cookie must survive the browser being closed
+pub fn create_refresh_cookie(token: &RefreshToken) -> Cookie<'static> {
+    Cookie::build((REFRESH_TOKEN_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        .build()
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
     UnexpectedError,
 }
 
+// Audience of the access tokens issued to OAuth clients, which only the OpenID Connect
+// and introspection endpoints accept
+pub const ACCESS_TOKEN_AUDIENCE: &str = "oauth-access-token";
+
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
+// Authorization codes are redeemed straight after the redirect, so keep them short lived
+pub const AUTHORIZATION_CODE_TTL_SECONDS: i64 = 60;
+
+// Magic links stand in for a password, so they only work for a short while
+pub const MAGIC_LINK_TTL_SECONDS: i64 = 600; // 10 minutes
+
+// How long a login attempt waits for its second factor
+pub const LOGIN_ATTEMPT_TTL_SECONDS: i64 = 600; // 10 minutes
+
+// Login attempts a user may have waiting for their second factor at once
+pub const MAX_PENDING_LOGIN_ATTEMPTS: usize = 5;
+
+// Wrong 2FA codes allowed for a login attempt before it has to be started again
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+
+// Extra 2FA codes that may be emailed for a login attempt, and how long to wait between them
+pub const MAX_TWO_FA_RESENDS: u32 = 3;
+pub const TWO_FA_RESEND_COOLDOWN_SECONDS: u64 = 30;
+
+// Failed logins are forgotten a day after the last one
+pub const FAILED_LOGIN_TTL_SECONDS: i64 = 86_400;
+
+// Lockouts double with every further failed login up to this limit
+pub const MAX_LOGIN_LOCKOUT_SECONDS: u64 = 3_600; // 1 hour
+
+// Users may take a while to sign in at an upstream identity provider
+pub const OIDC_LOGIN_TTL_SECONDS: i64 = 600; // 10 minutes
+
+// Reset tokens are emailed, so give the user a little while to find the message
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 900; // 15 minutes
+
+// Long enough for the user to find and unlock their authenticator
+pub const PASSKEY_CHALLENGE_TTL_SECONDS: i64 = 300; // 5 minutes
+
+// How stale a session's last_seen may get before a request updates it
+pub const SESSION_TOUCH_INTERVAL_SECONDS: usize = 60;
+
+// This value determines how long a refresh token can be used to renew the JWT
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 2_592_000; // 30 days
+
 // Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
-        .ok_or(GenerateTokenError::UnexpectedError)?;
+fn generate_auth_token(
+    email: &Email,
+    session_id: &SessionId,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS)?;
+
+    let sub = email.as_ref().to_owned();
+
+    let sid = session_id.as_ref().to_owned();
+
+    let claims = Claims { sub, exp, iat, sid };
+
+    create_token(&claims)
+}
+
+// Create a JWT for an OAuth client acting on the user's behalf, limited to the granted scope
+pub fn generate_access_token(
+    email: &Email,
+    session_id: &SessionId,
+    client_id: &ClientId,
+    scope: &str,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS)?;
+
+    let claims = AccessTokenClaims {
+        sub: email.as_ref().to_owned(),
+        aud: ACCESS_TOKEN_AUDIENCE.to_owned(),
+        client_id: client_id.as_ref().to_owned(),
+        exp,
+        iat,
+        sid: session_id.as_ref().to_owned(),
+        scope: scope.to_owned(),
+    };
+
+    create_token(&claims)
+}
+
+// Create an OpenID Connect ID token telling the client who signed in
+pub fn generate_id_token(
+    grant: &AuthorizationGrant,
+    auth_time: usize,
+    email_verified: bool,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS)?;
+    let email_scope = scope_includes(&grant.scope, "email");
+
+    let claims = IdTokenClaims {
+        iss: AUTH_SERVICE_URL.to_owned(),
+        sub: grant.email.as_ref().to_owned(),
+        aud: grant.client_id.as_ref().to_owned(),
+        exp,
+        iat,
+        auth_time,
+        nonce: grant.nonce.clone(),
+        sid: grant.session_id.as_ref().to_owned(),
+        email: email_scope.then(|| grant.email.as_ref().to_owned()),
+        email_verified: email_scope.then_some(email_verified),
+    };
+
+    create_token(&claims)
+}
+
+// Create a JWT for a client acting on its own behalf, as opposed to a user's
+pub fn generate_client_token(
+    client_id: &ClientId,
+    scope: &str,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS)?;
+
+    let claims = ClientClaims {
+        sub: client_id.as_ref().to_owned(),
+        client_id: client_id.as_ref().to_owned(),
+        exp,
+        iat,
+        scope: scope.to_owned(),
+    };
+
+    create_token(&claims)
+}
+
+// Create the signed token carried by a magic link. The link id must also be in the
+// magic link store, which lets each link be used only once.
+pub fn generate_magic_link_token(
+    email: &Email,
+    link_id: &MagicLinkId,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = token_lifetime(MAGIC_LINK_TTL_SECONDS)?;
+
+    let claims = MagicLinkClaims {
+        sub: email.as_ref().to_owned(),
+        exp,
+        iat,
+        jti: link_id.as_ref().to_owned(),
+    };
+
+    create_token(&claims)
+}
+
+// Check the signature and expiry of a magic link token and read its claims
+pub fn decode_magic_link_token(
+    token: &str,
+) -> Result<MagicLinkClaims, jsonwebtoken::errors::Error> {
+    decode_claims::<MagicLinkClaims>(token, None)
+}
+
+// Whether a space separated OAuth scope list contains the given scope
+pub fn scope_includes(scope: &str, wanted: &str) -> bool {
+    scope.split_whitespace().any(|scope| scope == wanted)
+}
+
+// The issued at and expiry times for a token created now
+fn token_lifetime(ttl_seconds: i64) -> Result<(usize, usize), GenerateTokenError> {
+    let delta =
+        chrono::Duration::try_seconds(ttl_seconds).ok_or(GenerateTokenError::UnexpectedError)?;
+
+    let now = Utc::now();
 
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
 
-    // Cast exp to a usize, which is what Claims expects
+    // Cast exp and iat to a usize, which is what Claims expects
     let exp: usize = exp
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
-    let sub = email.as_ref().to_owned();
+    Ok((iat, exp))
+}
+
+// Check if JWT auth token is valid by decoding it using the JWT secret. Access
+// tokens issued to OAuth clients are not accepted here.
+pub async fn validate_token(
+    token: &str,
+    banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let invalid_token =
+        || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken);
 
-    let claims = Claims { sub, exp };
+    let claims = decode_claims::<Claims>(token, None)?;
+    let (session_id, session) = check_user_token(
+        token,
+        &claims.sub,
+        &claims.sid,
+        claims.iat,
+        banned_token_store,
+        &session_store,
+    )
+    .await?;
+
+    // Record the activity, but only take the write lock once in a while
+    let now: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| invalid_token())?;
+
+    if now >= session.last_seen + SESSION_TOUCH_INTERVAL_SECONDS {
+        session_store
+            .write()
+            .await
+            .touch_session(&session_id, now)
+            .await
+            .map_err(|_| invalid_token())?;
+    }
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+    Ok(claims)
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
-pub async fn validate_token(
+// Check a JWT auth token the same way as validate_token, but leave the session's
+// last_seen alone, for when a resource server looks the token up rather than the user
+// using it
+pub async fn inspect_token(
     token: &str,
     banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let claims = decode_claims::<Claims>(token, None)?;
+    check_user_token(
+        token,
+        &claims.sub,
+        &claims.sid,
+        claims.iat,
+        banned_token_store,
+        &session_store,
+    )
+    .await?;
+
+    Ok(claims)
+}
+
+// Check an access token issued to an OAuth client by /token. These are only accepted
+// by the endpoints meant for clients, and leave the session's last_seen alone, since
+// a client using one is not the user being active.
+pub async fn validate_access_token(
+    token: &str,
+    banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
+) -> Result<AccessTokenClaims, jsonwebtoken::errors::Error> {
+    let claims = decode_claims::<AccessTokenClaims>(token, Some(ACCESS_TOKEN_AUDIENCE))?;
+    check_user_token(
+        token,
+        &claims.sub,
+        &claims.sid,
+        claims.iat,
+        banned_token_store,
+        &session_store,
+    )
+    .await?;
+
+    Ok(claims)
+}
+
+// Checks shared by every token issued for a user's session, returning the session
+async fn check_user_token(
+    token: &str,
+    sub: &str,
+    sid: &str,
+    iat: usize,
+    banned_token_store: BannedTokenStoreType,
+    session_store: &SessionStoreType,
+) -> Result<(SessionId, Session), jsonwebtoken::errors::Error> {
+    let invalid_token =
+        || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken);
+    let banned_token_store = banned_token_store.read().await;
+
+    match banned_token_store.contains_token(token).await {
+        Ok(false) => (),
+        Ok(true) | Err(_) => return Err(invalid_token()),
+    }
+
+    // Reject tokens issued before all of the user's tokens were revoked. The watermark is
+    // in whole seconds so that tokens issued right after it stay valid. Tokens from
+    // earlier in the same second belong to sessions that were ended with it.
+    let email = Email::parse(sub).map_err(|_| invalid_token())?;
+    match banned_token_store.user_tokens_banned_before(&email).await {
+        Ok(Some(issued_before)) if iat < issued_before => return Err(invalid_token()),
+        Ok(_) => (),
+        Err(_) => return Err(invalid_token()),
+    }
+
+    // Reject tokens whose session has been revoked
+    let session_id = SessionId::parse(sid).map_err(|_| invalid_token())?;
+    match session_store.read().await.get_session(&session_id).await {
+        Ok(session) if session.email == email => Ok((session_id, session)),
+        _ => Err(invalid_token()),
+    }
+}
+
+// Check a token issued to a client by the client_credentials grant, which stops
+// validating as soon as the client is disabled
+pub async fn validate_client_token(
+    token: &str,
+    banned_token_store: BannedTokenStoreType,
+    client_store: ClientStoreType,
+) -> Result<ClientClaims, jsonwebtoken::errors::Error> {
+    let invalid_token =
+        || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken);
+
     match banned_token_store.read().await.contains_token(token).await {
-        Ok(true) | Err(_) => Err(jsonwebtoken::errors::Error::from(
-            jsonwebtoken::errors::ErrorKind::InvalidToken,
-        )),
-        Ok(false) => decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-            &Validation::default(),
-        )
-        .map(|data| data.claims),
+        Ok(false) => (),
+        Ok(true) | Err(_) => return Err(invalid_token()),
+    }
+
+    let claims = decode_claims::<ClientClaims>(token, None)?;
+
+    let client_id = ClientId::parse(&claims.client_id).map_err(|_| invalid_token())?;
+    match client_store.read().await.get_client(&client_id).await {
+        Ok(client) if !client.disabled => Ok(claims),
+        _ => Err(invalid_token()),
     }
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+// Verify a JWT's signature and expiry and read its claims. User and client tokens
+// have different required claims, so each only decodes as its own kind. Tokens with
+// an audience only decode when that audience is expected.
+fn decode_claims<T: DeserializeOwned>(
+    token: &str,
+    audience: Option<&str>,
+) -> Result<T, jsonwebtoken::errors::Error> {
+    // Pick the verification key named by the token. Tokens signed before key ids
+    // were introduced carry no kid and can only match the current signing key.
+    let header = decode_header(token)?;
+    let key_ring = key_ring();
+    let now = Utc::now();
+    let verification_key = match header.kid {
+        Some(kid) => key_ring.verification_key(&kid, now),
+        None => key_ring.signing_key(now).ok(),
+    }
+    .ok_or_else(|| {
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)
+    })?;
+
+    let mut validation = Validation::new(verification_key.algorithm);
+    if let Some(audience) = audience {
+        validation.set_audience(&[audience]);
+        validation.set_required_spec_claims(&["exp", "aud"]);
+    }
+
+    decode::<T>(token, verification_key.decoding_key(), &validation).map(|data| data.claims)
+}
+
+// Validate the JWT auth cookie and return its claims
+pub async fn authenticated_claims(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<Claims, AuthAPIError> {
+    let auth_cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+
+    validate_token(
+        auth_cookie.value(),
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
     )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)
+}
+
+// Identify the signed in user from the JWT auth cookie
+pub async fn authenticated_email(jar: &CookieJar, state: &AppState) -> Result<Email, AuthAPIError> {
+    let claims = authenticated_claims(jar, state).await?;
+
+    Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)
+}
+
+// The token from an "Authorization: Bearer" header
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+// Check the bearer token presented to the /admin API. Digests are compared so the
+// time taken does not reveal how much of the token was right.
+pub fn authenticate_admin(headers: &HeaderMap) -> Result<(), AuthAPIError> {
+    let token = bearer_token(headers).ok_or(AuthAPIError::MissingToken)?;
+
+    match !ADMIN_API_TOKEN.is_empty()
+        && Sha256::digest(token.as_bytes()) == Sha256::digest(ADMIN_API_TOKEN.as_bytes())
+    {
+        true => Ok(()),
+        false => Err(AuthAPIError::InvalidToken),
+    }
 }
 
+// Authenticate an OAuth client from HTTP Basic credentials, or failing that from
+// the client_id and client_secret request parameters. The request then counts
+// against the client's rate limits on the route.
+pub async fn authenticate_client(
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+    route: &MatchedPath,
+    state: &AppState,
+) -> Result<ClientId, AuthAPIError> {
+    let basic_credentials = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|credentials| BASE64.decode(credentials.trim()).ok())
+        .and_then(|credentials| String::from_utf8(credentials).ok());
+
+    let (client_id, client_secret) = match (&basic_credentials, client_id, client_secret) {
+        (Some(credentials), _, _) => credentials
+            .split_once(':')
+            .ok_or(AuthAPIError::InvalidClient)?,
+        (None, Some(client_id), Some(client_secret)) => (client_id, client_secret),
+        _ => return Err(AuthAPIError::InvalidClient),
+    };
+
+    let client_id = ClientId::parse(client_id).map_err(|_| AuthAPIError::InvalidClient)?;
+    let client_secret =
+        ClientSecret::parse(client_secret).map_err(|_| AuthAPIError::InvalidClient)?;
+
+    match state
+        .client_store
+        .read()
+        .await
+        .authenticate_client(&client_id, &client_secret)
+        .await
+    {
+        Ok(()) => (),
+        Err(
+            ClientStoreError::ClientDisabled
+            | ClientStoreError::ClientNotFound
+            | ClientStoreError::InvalidCredentials,
+        ) => return Err(AuthAPIError::InvalidClient),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    state
+        .rate_limiter
+        .check_client(route.as_str(), &client_id)
+        .await?;
+
+    Ok(client_id)
+}
+
+// Invalidate every token issued to the user up to now and end all of their sessions
+pub async fn revoke_all_tokens(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let now: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Postgres keeps the watermark for as long as refresh tokens live, while Redis
+    // only needs it until the last JWT issued before it has expired
+    state
+        .user_store
+        .write()
+        .await
+        .set_tokens_revoked_before(email, now)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .ban_user_tokens(email, now)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let mut session_store = state.session_store.write().await;
+
+    let sessions = session_store
+        .get_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    for session in sessions {
+        match session_store.remove_session(&session.id).await {
+            Ok(()) | Err(SessionStoreError::SessionNotFound) => (),
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        }
+    }
+
+    Ok(())
+}
+
+// Create JWT auth token by encoding claims with the current signing key
+fn create_token<T: Serialize>(claims: &T) -> Result<String, GenerateTokenError> {
+    let key_ring = key_ring();
+    let signing_key = key_ring
+        .signing_key(Utc::now())
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+    let encoding_key = signing_key
+        .encoding_key()
+        .ok_or(GenerateTokenError::UnexpectedError)?;
+
+    let mut header = Header::new(signing_key.algorithm);
+    header.kid = Some(signing_key.kid.clone());
+
+    encode(&header, &claims, encoding_key).map_err(GenerateTokenError::TokenError)
+}
+
+// Claims of the token issued to a signed in user. Nothing else may be present, so
+// tokens issued to OAuth clients are never taken for one.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    pub sid: String,
+}
+
+// Claims of an access token issued to an OAuth client acting on the user's behalf
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    pub sub: String,
+    // Always ACCESS_TOKEN_AUDIENCE, which first party endpoints refuse
+    pub aud: String,
+    // The client the token was issued to
+    pub client_id: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub sid: String,
+    // Space separated OAuth scopes the user granted
+    pub scope: String,
+}
+
+// Claims of a token issued by the client_credentials grant. Resource servers tell
+// these apart from user tokens by the client_id claim and the absence of sid.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientClaims {
+    pub sub: String,
+    pub client_id: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub scope: String,
+}
+
+// Claims of the token in a magic link. Only these carry a jti, so a user or
+// client token cannot be passed off as a magic link.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    // Id of the link in the magic link store
+    pub jti: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    // The client the token was issued to
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    // When the user signed in, as opposed to when the token was issued
+    pub auth_time: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub sid: String,
+    // Only present when the client was granted the email scope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
 }
 
 #[cfg(test)]
@@ -94,14 +669,26 @@ mod tests {
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
-    use crate::{domain::BannedTokenStore, services::HashsetBannedTokenStore};
+    use crate::{
+        domain::{BannedTokenStore, Client, ClientSecret, ClientStore, Session, SessionStore},
+        services::{HashmapClientStore, HashmapSessionStore, HashsetBannedTokenStore},
+    };
 
     use super::*;
 
+    // Start a session for the user in a fresh store
+    async fn session_store(email: &Email) -> (SessionId, SessionStoreType) {
+        let session = Session::new(email.clone(), None, None);
+        let session_id = session.id.clone();
+        let mut store = HashmapSessionStore::default();
+        store.add_session(session).await.unwrap();
+        (session_id, Arc::new(RwLock::new(store)))
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com").unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&email, &SessionId::default()).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -123,16 +710,30 @@ mod tests {
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&email, &SessionId::default()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
+    #[tokio::test]
+    async fn test_generate_auth_token_sets_key_id() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, &SessionId::default()).unwrap();
+        let header = decode_header(&token).unwrap();
+        let key_ring = key_ring();
+        let signing_key = key_ring.signing_key(Utc::now()).unwrap();
+        assert_eq!(header.kid, Some(signing_key.kid.clone()));
+        assert_eq!(header.alg, signing_key.algorithm);
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let token = generate_auth_token(&email, &session_id).unwrap();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let result = validate_token(&token, banned_token_store, session_store)
+            .await
+            .unwrap();
         assert_eq!(result.sub, "test@example.com");
 
         let exp = Utc::now()
@@ -146,19 +747,257 @@ mod tests {
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
+        let (_, session_store) = session_store(&Email::parse("test@example.com").unwrap()).await;
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let result = validate_token(&token, banned_token_store, session_store).await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let token = generate_auth_token(&email, &session_id).unwrap();
         let mut hs = HashsetBannedTokenStore::default();
         hs.add_token(token.clone()).await.unwrap();
         let banned_token_store = Arc::new(RwLock::new(hs));
-        let result = validate_token(&token, banned_token_store).await;
+        let result = validate_token(&token, banned_token_store, session_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_user_tokens() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let token = generate_auth_token(&email, &session_id).unwrap();
+        let mut hs = HashsetBannedTokenStore::default();
+        let issued_after = Utc::now().timestamp() as usize + 1;
+        hs.ban_user_tokens(&email, issued_after).await.unwrap();
+        let banned_token_store = Arc::new(RwLock::new(hs));
+        let result = validate_token(&token, banned_token_store, session_store).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_after_user_tokens_revoked() {
+        let email = Email::parse("test@example.com").unwrap();
+        let mut hs = HashsetBannedTokenStore::default();
+        let issued_before = Utc::now().timestamp() as usize;
+        hs.ban_user_tokens(&email, issued_before).await.unwrap();
+        let banned_token_store = Arc::new(RwLock::new(hs));
+        let (session_id, session_store) = session_store(&email).await;
+        let token = generate_auth_token(&email, &session_id).unwrap();
+        let result = validate_token(&token, banned_token_store, session_store).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_session() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let token = generate_auth_token(&email, &session_id).unwrap();
+        session_store
+            .write()
+            .await
+            .remove_session(&session_id)
+            .await
+            .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_touches_stale_session_only() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let token = generate_auth_token(&email, &session_id).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let last_seen = |session_store: SessionStoreType| {
+            let session_id = session_id.clone();
+            async move {
+                let session_store = session_store.read().await;
+                session_store
+                    .get_session(&session_id)
+                    .await
+                    .unwrap()
+                    .last_seen
+            }
+        };
+
+        let recent = last_seen(session_store.clone()).await - 1;
+        session_store
+            .write()
+            .await
+            .touch_session(&session_id, recent)
+            .await
+            .unwrap();
+        validate_token(&token, banned_token_store.clone(), session_store.clone())
+            .await
+            .unwrap();
+        assert_eq!(last_seen(session_store.clone()).await, recent);
+
+        let stale = recent - SESSION_TOUCH_INTERVAL_SECONDS;
+        session_store
+            .write()
+            .await
+            .touch_session(&session_id, stale)
+            .await
+            .unwrap();
+        validate_token(&token, banned_token_store, session_store.clone())
+            .await
+            .unwrap();
+        assert!(last_seen(session_store).await > stale);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_unknown_key_id() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let claims = Claims {
+            sub: email.as_ref().to_owned(),
+            exp: Utc::now().timestamp() as usize + 600,
+            iat: Utc::now().timestamp() as usize,
+            sid: session_id.as_ref().to_owned(),
+        };
+        let header = Header {
+            kid: Some("unknown".to_owned()),
+            ..Default::default()
+        };
+        let token = encode(
+            &header,
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_token_store, session_store).await;
+        assert!(result.is_err());
+    }
+
+    // Register a client in a fresh store
+    async fn client_store(client_id: &ClientId) -> ClientStoreType {
+        let mut store = HashmapClientStore::default();
+        store
+            .add_client(
+                Client::new(
+                    client_id.clone(),
+                    Vec::new(),
+                    vec!["reports:read".to_owned()],
+                ),
+                ClientSecret::default(),
+            )
+            .await
+            .unwrap();
+        Arc::new(RwLock::new(store))
+    }
+
+    #[tokio::test]
+    async fn test_validate_client_token_with_valid_token() {
+        let client_id = ClientId::parse("report-job").unwrap();
+        let client_store = client_store(&client_id).await;
+        let token = generate_client_token(&client_id, "reports:read").unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_client_token(&token, banned_token_store, client_store)
+            .await
+            .unwrap();
+        assert_eq!(result.client_id, "report-job");
+        assert_eq!(result.scope, "reports:read");
+    }
+
+    #[tokio::test]
+    async fn test_validate_client_token_with_disabled_client() {
+        let client_id = ClientId::parse("report-job").unwrap();
+        let client_store = client_store(&client_id).await;
+        let token = generate_client_token(&client_id, "reports:read").unwrap();
+        client_store
+            .write()
+            .await
+            .disable_client(&client_id)
+            .await
+            .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_client_token(&token, banned_token_store, client_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_and_user_tokens_are_not_interchangeable() {
+        let client_id = ClientId::parse("report-job").unwrap();
+        let client_store = client_store(&client_id).await;
+        let email = Email::parse("test@example.com").unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let client_token = generate_client_token(&client_id, "reports:read").unwrap();
+        let result = validate_token(&client_token, banned_token_store.clone(), session_store).await;
+        assert!(result.is_err());
+
+        let user_token = generate_auth_token(&email, &session_id).unwrap();
+        let result = validate_client_token(&user_token, banned_token_store, client_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_access_and_user_tokens_are_not_interchangeable() {
+        let client_id = ClientId::parse("report-app").unwrap();
+        let email = Email::parse("test@example.com").unwrap();
+        let (session_id, session_store) = session_store(&email).await;
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let access_token =
+            generate_access_token(&email, &session_id, &client_id, "openid").unwrap();
+        let result = validate_token(
+            &access_token,
+            banned_token_store.clone(),
+            session_store.clone(),
+        )
+        .await;
+        assert!(result.is_err());
+        let claims = validate_access_token(
+            &access_token,
+            banned_token_store.clone(),
+            session_store.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(claims.client_id, "report-app");
+        assert_eq!(claims.scope, "openid");
+
+        let user_token = generate_auth_token(&email, &session_id).unwrap();
+        let result = validate_access_token(&user_token, banned_token_store, session_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_access_token_is_not_a_client_token() {
+        let client_id = ClientId::parse("report-job").unwrap();
+        let client_store = client_store(&client_id).await;
+        let email = Email::parse("test@example.com").unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let access_token =
+            generate_access_token(&email, &SessionId::default(), &client_id, "reports:read")
+                .unwrap();
+        let result = validate_client_token(&access_token, banned_token_store, client_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decode_magic_link_token() {
+        let email = Email::parse("test@example.com").unwrap();
+        let link_id = MagicLinkId::default();
+        let token = generate_magic_link_token(&email, &link_id).unwrap();
+        let claims = decode_magic_link_token(&token).unwrap();
+        assert_eq!(claims.sub, email.as_ref());
+        assert_eq!(claims.jti, link_id.as_ref());
+        assert_eq!(claims.exp - claims.iat, MAGIC_LINK_TTL_SECONDS as usize);
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_is_not_a_magic_link_token() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, &SessionId::default()).unwrap();
+        assert!(decode_magic_link_token(&token).is_err());
+    }
 }
@@ -4,8 +4,31 @@ use std::env as std_env;
 
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const JWT_SIGNING_ALGORITHM_ENV_VAR: &str = "JWT_SIGNING_ALGORITHM";
+    pub const JWT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_PRIVATE_KEY_PATH";
+    pub const JWT_PUBLIC_KEY_PATH_ENV_VAR: &str = "JWT_PUBLIC_KEY_PATH";
+    pub const JWT_KEY_ID_ENV_VAR: &str = "JWT_KEY_ID";
+    pub const JWT_KEY_RING_PATH_ENV_VAR: &str = "JWT_KEY_RING_PATH";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
+    pub const ALLOW_UNVERIFIED_LOGIN_ENV_VAR: &str = "ALLOW_UNVERIFIED_LOGIN";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const TOTP_SKEW_STEPS_ENV_VAR: &str = "TOTP_SKEW_STEPS";
+    pub const OAUTH_CLIENTS_ENV_VAR: &str = "OAUTH_CLIENTS";
+    pub const ADMIN_API_TOKEN_ENV_VAR: &str = "ADMIN_API_TOKEN";
+    pub const OIDC_PROVIDERS_PATH_ENV_VAR: &str = "OIDC_PROVIDERS_PATH";
+    pub const LOGIN_ACCOUNT_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_ACCOUNT_LOCKOUT_THRESHOLD";
+    pub const LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_IP_LOCKOUT_THRESHOLD";
+    pub const LOGIN_LOCKOUT_SECONDS_ENV_VAR: &str = "LOGIN_LOCKOUT_SECONDS";
+    pub const RATE_LIMITS_PATH_ENV_VAR: &str = "RATE_LIMITS_PATH";
+    pub const EMAIL_CLIENT_ENV_VAR: &str = "EMAIL_CLIENT";
+    pub const EMAIL_FROM_ENV_VAR: &str = "EMAIL_FROM";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
 }
 
 pub mod prod {
@@ -18,23 +41,89 @@ pub mod test {
 
 lazy_static! {
     pub static ref JWT_SECRET: String = set_env(env::JWT_SECRET_ENV_VAR, None);
+    // HS256 signs with JWT_SECRET, while RS256 and EdDSA sign with the PEM key pair
+    pub static ref JWT_SIGNING_ALGORITHM: String =
+        set_env(env::JWT_SIGNING_ALGORITHM_ENV_VAR, Some("HS256"));
+    pub static ref JWT_PRIVATE_KEY_PATH: String =
+        set_env(env::JWT_PRIVATE_KEY_PATH_ENV_VAR, None);
+    pub static ref JWT_PUBLIC_KEY_PATH: String = set_env(env::JWT_PUBLIC_KEY_PATH_ENV_VAR, None);
+    // Derived from the public key when not set
+    pub static ref JWT_KEY_ID: String = set_env(env::JWT_KEY_ID_ENV_VAR, Some(""));
+    // JSON file listing several keys with activation and retirement times,
+    // which replaces the single key settings above when set
+    pub static ref JWT_KEY_RING_PATH: String = set_env(env::JWT_KEY_RING_PATH_ENV_VAR, Some(""));
     pub static ref DATABASE_URL: String = set_env(env::DATABASE_URL_ENV_VAR, None);
     pub static ref REDIS_HOST_NAME: String =
         set_env(env::REDIS_HOST_NAME_ENV_VAR, Some("127.0.0.1"));
+    // Public base URL used to build links sent in emails
+    pub static ref AUTH_SERVICE_URL: String =
+        set_env(env::AUTH_SERVICE_URL_ENV_VAR, Some("http://localhost:3000"));
+    pub static ref ALLOW_UNVERIFIED_LOGIN: bool =
+        set_env(env::ALLOW_UNVERIFIED_LOGIN_ENV_VAR, Some("false"))
+            .parse()
+            .expect("ALLOW_UNVERIFIED_LOGIN must be true or false.");
+    pub static ref TOTP_ENCRYPTION_KEY: String = set_env(env::TOTP_ENCRYPTION_KEY_ENV_VAR, None);
+    // Number of 30 second steps a TOTP code may be early or late to allow for clock drift
+    pub static ref TOTP_SKEW_STEPS: u8 = set_env(env::TOTP_SKEW_STEPS_ENV_VAR, Some("1"))
+        .parse()
+        .expect("TOTP_SKEW_STEPS must be a number between 0 and 255.");
+    // Comma separated client_id:client_secret[:redirect_uris] entries registered at
+    // startup, where redirect_uris is a space separated list for OpenID Connect clients
+    pub static ref OAUTH_CLIENTS: String = set_env(env::OAUTH_CLIENTS_ENV_VAR, Some(""));
+    // Bearer token for the /admin API, which is disabled when this is not set
+    pub static ref ADMIN_API_TOKEN: String = set_env(env::ADMIN_API_TOKEN_ENV_VAR, Some(""));
+    // JSON file listing the upstream OpenID Connect providers users can sign in with
+    pub static ref OIDC_PROVIDERS_PATH: String =
+        set_env(env::OIDC_PROVIDERS_PATH_ENV_VAR, Some(""));
+    // Failed logins for an account, from anywhere, before it is locked
+    pub static ref LOGIN_ACCOUNT_LOCKOUT_THRESHOLD: u32 =
+        set_env(env::LOGIN_ACCOUNT_LOCKOUT_THRESHOLD_ENV_VAR, Some("20"))
+            .parse()
+            .expect("LOGIN_ACCOUNT_LOCKOUT_THRESHOLD must be a number.");
+    // Failed logins for an account from a single IP address before that address is
+    // locked out of it. Kept well below the account threshold so one attacker cannot
+    // lock the owner out.
+    pub static ref LOGIN_IP_LOCKOUT_THRESHOLD: u32 =
+        set_env(env::LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR, Some("5"))
+            .parse()
+            .expect("LOGIN_IP_LOCKOUT_THRESHOLD must be a number.");
+    // How long the first lockout lasts, doubling with each failure after it
+    pub static ref LOGIN_LOCKOUT_SECONDS: u64 =
+        set_env(env::LOGIN_LOCKOUT_SECONDS_ENV_VAR, Some("60"))
+            .parse()
+            .expect("LOGIN_LOCKOUT_SECONDS must be a number of seconds.");
+    // JSON file listing the rate limit rules, which replace the built in ones when set
+    pub static ref RATE_LIMITS_PATH: String = set_env(env::RATE_LIMITS_PATH_ENV_VAR, Some(""));
+    // Either mock, which only logs emails, or smtp to deliver them with the settings below
+    pub static ref EMAIL_CLIENT: String = set_env(env::EMAIL_CLIENT_ENV_VAR, Some("mock"));
+    // Mailbox emails are sent from, such as "Auth Service <no-reply@example.com>"
+    pub static ref EMAIL_FROM: String =
+        set_env(env::EMAIL_FROM_ENV_VAR, Some("no-reply@localhost"));
+    pub static ref SMTP_HOST: String = set_env(env::SMTP_HOST_ENV_VAR, Some("localhost"));
+    // Defaults to 25, 587 or 465 depending on SMTP_TLS
+    pub static ref SMTP_PORT: String = set_env(env::SMTP_PORT_ENV_VAR, Some(""));
+    // none, starttls or tls for implicit TLS
+    pub static ref SMTP_TLS: String = set_env(env::SMTP_TLS_ENV_VAR, Some("starttls"));
+    // The server is used without authentication when no username is set
+    pub static ref SMTP_USERNAME: String = set_env(env::SMTP_USERNAME_ENV_VAR, Some(""));
+    pub static ref SMTP_PASSWORD: String = set_env(env::SMTP_PASSWORD_ENV_VAR, Some(""));
 }
 
 fn set_env(name: &str, default: Option<&str>) -> String {
     dotenv().ok();
     match std_env::var(name) {
         Ok(value) if value.is_empty() => default
-            .expect(&format!("{name} must not be empty."))
+            .unwrap_or_else(|| panic!("{name} must not be empty."))
             .to_string(),
         Ok(value) => value,
         Err(_) if default.is_none() => panic!("{name} has no default."),
         Err(_) => default
-            .expect(&format!("{name} default failed!"))
+            .unwrap_or_else(|| panic!("{name} default failed!"))
             .to_string(),
     }
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_TOKEN_COOKIE_NAME: &str = "refresh_token";
+pub const OIDC_STATE_COOKIE_NAME: &str = "oidc_state";
+pub const LOGIN_ATTEMPT_COOKIE_NAME: &str = "login_attempt";
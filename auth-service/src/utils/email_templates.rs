@@ -0,0 +1,123 @@
+use askama::Template;
+
+use crate::{
+    domain::{EmailMessage, PasswordResetToken, TwoFACode},
+    utils::auth::LOGIN_ATTEMPT_TTL_SECONDS,
+};
+
+// Each message has a plain text and an HTML template under templates/emails,
+// which are rendered from the same values so the two bodies never drift apart
+
+#[derive(Template)]
+#[template(path = "emails/two_fa_code.txt")]
+struct TwoFACodeText<'a> {
+    code: &'a str,
+    expires_in_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/two_fa_code.html")]
+struct TwoFACodeHtml<'a> {
+    code: &'a str,
+    expires_in_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/verification.txt")]
+struct VerificationText<'a> {
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/verification.html")]
+struct VerificationHtml<'a> {
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/password_reset.txt")]
+struct PasswordResetText<'a> {
+    token: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/password_reset.html")]
+struct PasswordResetHtml<'a> {
+    token: &'a str,
+}
+
+pub fn two_fa_code_email(subject: &str, code: &TwoFACode) -> Result<EmailMessage, askama::Error> {
+    let code = code.as_ref();
+    let expires_in_minutes = LOGIN_ATTEMPT_TTL_SECONDS / 60;
+
+    Ok(EmailMessage {
+        subject: subject.to_owned(),
+        text: TwoFACodeText {
+            code,
+            expires_in_minutes,
+        }
+        .render()?,
+        html: Some(
+            TwoFACodeHtml {
+                code,
+                expires_in_minutes,
+            }
+            .render()?,
+        ),
+    })
+}
+
+pub fn verification_email(link: &str) -> Result<EmailMessage, askama::Error> {
+    Ok(EmailMessage {
+        subject: "Verify Your Email Address".to_owned(),
+        text: VerificationText { link }.render()?,
+        html: Some(VerificationHtml { link }.render()?),
+    })
+}
+
+pub fn password_reset_email(token: &PasswordResetToken) -> Result<EmailMessage, askama::Error> {
+    let token = token.as_ref();
+
+    Ok(EmailMessage {
+        subject: "Your Password Reset Token".to_owned(),
+        text: PasswordResetText { token }.render()?,
+        html: Some(PasswordResetHtml { token }.render()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_fa_code_email_contains_code() {
+        let code = TwoFACode::parse("123456").unwrap();
+        let message = two_fa_code_email("Your Login Authentication Code", &code).unwrap();
+
+        assert_eq!(message.subject, "Your Login Authentication Code");
+        assert!(message.text.contains("123456"));
+        let html = message.html.unwrap();
+        assert!(html.contains("123456"));
+        assert!(html.contains("10 minutes"));
+    }
+
+    #[test]
+    fn test_verification_email_escapes_link_in_html() {
+        let link = "http://localhost:3000/verify-email?email=a%40b.com&token=abc";
+        let message = verification_email(link).unwrap();
+
+        assert!(message.text.contains(link));
+        let html = message.html.unwrap();
+        assert!(html.contains("http://localhost:3000/verify-email?email=a%40b.com&amp;token=abc"));
+        assert!(!html.contains(link));
+    }
+
+    #[test]
+    fn test_password_reset_email_contains_token() {
+        let token = PasswordResetToken::default();
+        let message = password_reset_email(&token).unwrap();
+
+        assert!(message.text.contains(token.as_ref()));
+        assert!(message.html.unwrap().contains(token.as_ref()));
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::HashSet,
+    fs,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{jwk::JwkSet, Algorithm};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use super::{
+    constants::JWT_KEY_RING_PATH,
+    signing_key::{SigningKey, SigningKeyError},
+};
+
+lazy_static! {
+    static ref KEY_RING: RwLock<Arc<KeyRing>> = RwLock::new(Arc::new(
+        KeyRing::from_config().expect("Failed to load JWT key ring")
+    ));
+}
+
+// The key ring currently in use. Callers hold on to the snapshot they were given,
+// so a reload never changes keys halfway through a request.
+pub fn key_ring() -> Arc<KeyRing> {
+    KEY_RING
+        .read()
+        .map(|key_ring| key_ring.clone())
+        .unwrap_or_else(|poisoned| poisoned.into_inner().clone())
+}
+
+// Re-read the key ring configuration, keeping the current keys if it is invalid
+pub fn reload_key_ring() -> Result<(), KeyRingError> {
+    let key_ring = KeyRing::from_config()?;
+
+    match KEY_RING.write() {
+        Ok(mut current) => *current = Arc::new(key_ring),
+        Err(poisoned) => *poisoned.into_inner() = Arc::new(key_ring),
+    }
+
+    Ok(())
+}
+
+// Every key that may verify JWTs. At any moment the most recently activated key
+// that is not retired signs new tokens, while older keys keep verifying the
+// tokens they signed until they are retired.
+pub struct KeyRing {
+    keys: Vec<ScheduledKey>,
+}
+
+pub struct ScheduledKey {
+    pub key: SigningKey,
+    // The key signs tokens from this time, or from the start if unset
+    pub activate_at: Option<DateTime<Utc>>,
+    // The key stops signing and verifying tokens at this time
+    pub retire_at: Option<DateTime<Utc>>,
+}
+
+impl ScheduledKey {
+    fn is_retired(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.retire_at, Some(retire_at) if retire_at <= now)
+    }
+
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        !self.is_retired(now) && !matches!(self.activate_at, Some(activate_at) if activate_at > now)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyRingError {
+    DuplicateKeyId(String),
+    InvalidConfig(String),
+    InvalidKey(SigningKeyError),
+    NoSigningKey,
+}
+
+impl KeyRing {
+    pub fn new(keys: Vec<ScheduledKey>) -> Result<Self, KeyRingError> {
+        let mut kids = HashSet::new();
+        for scheduled in &keys {
+            if !kids.insert(scheduled.key.kid.as_str()) {
+                return Err(KeyRingError::DuplicateKeyId(scheduled.key.kid.clone()));
+            }
+        }
+
+        let key_ring = Self { keys };
+        key_ring.signing_key(Utc::now())?;
+
+        Ok(key_ring)
+    }
+
+    // Load the ring file named by JWT_KEY_RING_PATH, or a ring holding the
+    // single key described by the other JWT_* variables when it is not set
+    pub fn from_config() -> Result<Self, KeyRingError> {
+        match JWT_KEY_RING_PATH.as_str() {
+            "" => Self::new(vec![ScheduledKey {
+                key: SigningKey::from_config().map_err(KeyRingError::InvalidKey)?,
+                activate_at: None,
+                retire_at: None,
+            }]),
+            path => Self::from_file(path),
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, KeyRingError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| KeyRingError::InvalidConfig(format!("{path}: {e}")))?;
+        let config: KeyRingConfig = serde_json::from_str(&contents)
+            .map_err(|e| KeyRingError::InvalidConfig(format!("{path}: {e}")))?;
+
+        let keys = config
+            .keys
+            .into_iter()
+            .map(KeyConfig::load)
+            .collect::<Result<_, _>>()?;
+
+        Self::new(keys)
+    }
+
+    pub fn signing_key(&self, now: DateTime<Utc>) -> Result<&SigningKey, KeyRingError> {
+        self.keys
+            .iter()
+            .filter(|scheduled| scheduled.is_active(now) && scheduled.key.encoding_key().is_some())
+            .max_by_key(|scheduled| scheduled.activate_at)
+            .map(|scheduled| &scheduled.key)
+            .ok_or(KeyRingError::NoSigningKey)
+    }
+
+    pub fn verification_key(&self, kid: &str, now: DateTime<Utc>) -> Option<&SigningKey> {
+        self.keys
+            .iter()
+            .find(|scheduled| scheduled.key.kid == kid && !scheduled.is_retired(now))
+            .map(|scheduled| &scheduled.key)
+    }
+
+    // Keys scheduled for promotion are published early so that resource servers
+    // already have them cached when the first token they sign arrives
+    pub fn jwks(&self, now: DateTime<Utc>) -> JwkSet {
+        let keys = self
+            .keys
+            .iter()
+            .filter(|scheduled| !scheduled.is_retired(now))
+            .filter_map(|scheduled| scheduled.key.jwk().cloned())
+            .collect();
+
+        JwkSet { keys }
+    }
+}
+
+#[derive(Deserialize)]
+struct KeyRingConfig {
+    keys: Vec<KeyConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyConfig {
+    kid: Option<String>,
+    algorithm: String,
+    secret: Option<String>,
+    private_key_path: Option<String>,
+    public_key_path: Option<String>,
+    activate_at: Option<String>,
+    retire_at: Option<String>,
+}
+
+impl KeyConfig {
+    fn load(self) -> Result<ScheduledKey, KeyRingError> {
+        let invalid_config = |message: String| KeyRingError::InvalidConfig(message);
+        let read = |path: &str| fs::read(path).map_err(|e| invalid_config(format!("{path}: {e}")));
+        let parse_time = |time: Option<String>| {
+            time.map(|time| {
+                DateTime::parse_from_rfc3339(&time)
+                    .map(|time| time.with_timezone(&Utc))
+                    .map_err(|e| invalid_config(format!("{time}: {e}")))
+            })
+            .transpose()
+        };
+
+        let algorithm = Algorithm::from_str(&self.algorithm).map_err(|_| {
+            KeyRingError::InvalidKey(SigningKeyError::UnsupportedAlgorithm(
+                self.algorithm.clone(),
+            ))
+        })?;
+
+        let key = match (algorithm, self.secret, self.public_key_path) {
+            (Algorithm::HS256, Some(secret), _) => SigningKey::from_secret(self.kid, &secret),
+            (Algorithm::HS256, None, _) => {
+                return Err(invalid_config("HS256 keys need a secret".to_owned()))
+            }
+            (_, _, Some(public_key_path)) => {
+                let private_pem = self.private_key_path.as_deref().map(read).transpose()?;
+                let public_pem = read(&public_key_path)?;
+
+                SigningKey::from_pem(algorithm, self.kid, private_pem.as_deref(), &public_pem)
+                    .map_err(KeyRingError::InvalidKey)?
+            }
+            (_, _, None) => {
+                return Err(invalid_config(format!(
+                    "{} keys need a publicKeyPath",
+                    self.algorithm
+                )))
+            }
+        };
+
+        Ok(ScheduledKey {
+            key,
+            activate_at: parse_time(self.activate_at)?,
+            retire_at: parse_time(self.retire_at)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const EDDSA_PUBLIC: &[u8] = include_bytes!("../../tests/keys/eddsa_public.pem");
+
+    fn scheduled(
+        kid: &str,
+        activate_at: Option<DateTime<Utc>>,
+        retire_at: Option<DateTime<Utc>>,
+    ) -> ScheduledKey {
+        ScheduledKey {
+            key: SigningKey::from_secret(Some(kid.to_owned()), kid),
+            activate_at,
+            retire_at,
+        }
+    }
+
+    #[test]
+    fn most_recently_activated_key_signs() {
+        let now = Utc::now();
+        let key_ring = KeyRing::new(vec![
+            scheduled("old", None, None),
+            scheduled("current", Some(now - Duration::days(1)), None),
+            scheduled("next", Some(now + Duration::days(1)), None),
+        ])
+        .unwrap();
+
+        assert_eq!(key_ring.signing_key(now).unwrap().kid, "current");
+        assert_eq!(
+            key_ring.signing_key(now + Duration::days(2)).unwrap().kid,
+            "next"
+        );
+    }
+
+    #[test]
+    fn older_keys_verify_until_retired() {
+        let now = Utc::now();
+        let key_ring = KeyRing::new(vec![
+            scheduled("old", None, Some(now + Duration::hours(1))),
+            scheduled("current", Some(now - Duration::days(1)), None),
+        ])
+        .unwrap();
+
+        assert!(key_ring.verification_key("old", now).is_some());
+        assert!(key_ring
+            .verification_key("old", now + Duration::hours(2))
+            .is_none());
+        assert!(key_ring.verification_key("unknown", now).is_none());
+    }
+
+    #[test]
+    fn scheduled_keys_are_published_before_promotion() {
+        let now = Utc::now();
+        let key_ring = KeyRing::new(vec![
+            scheduled("current", None, None),
+            ScheduledKey {
+                key: SigningKey::from_pem(
+                    Algorithm::EdDSA,
+                    Some("next".to_owned()),
+                    None,
+                    EDDSA_PUBLIC,
+                )
+                .unwrap(),
+                activate_at: Some(now + Duration::days(1)),
+                retire_at: None,
+            },
+        ])
+        .unwrap();
+
+        let jwks = key_ring.jwks(now);
+        assert!(jwks.find("next").is_some());
+        // Without its private key the scheduled key never takes over signing
+        assert_eq!(
+            key_ring.signing_key(now + Duration::days(2)).unwrap().kid,
+            "current"
+        );
+    }
+
+    #[test]
+    fn duplicate_key_ids_return_err() {
+        let result = KeyRing::new(vec![
+            scheduled("same", None, None),
+            scheduled("same", None, None),
+        ]);
+
+        assert_eq!(
+            result.err(),
+            Some(KeyRingError::DuplicateKeyId("same".to_owned()))
+        );
+    }
+
+    #[test]
+    fn ring_without_active_signing_key_returns_err() {
+        let now = Utc::now();
+        let result = KeyRing::new(vec![scheduled("next", Some(now + Duration::days(1)), None)]);
+
+        assert_eq!(result.err(), Some(KeyRingError::NoSigningKey));
+    }
+
+    #[test]
+    fn from_file_loads_scheduled_keys() {
+        let keys_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/keys");
+        let config = serde_json::json!({
+            "keys": [
+                {
+                    "kid": "current",
+                    "algorithm": "EdDSA",
+                    "privateKeyPath": format!("{keys_dir}/eddsa_private.pem"),
+                    "publicKeyPath": format!("{keys_dir}/eddsa_public.pem"),
+                    "retireAt": "2999-01-01T00:00:00Z"
+                },
+                {
+                    "kid": "next",
+                    "algorithm": "RS256",
+                    "privateKeyPath": format!("{keys_dir}/rs256_private.pem"),
+                    "publicKeyPath": format!("{keys_dir}/rs256_public.pem"),
+                    "activateAt": "2998-01-01T00:00:00Z"
+                }
+            ]
+        });
+        let path = std::env::temp_dir().join(format!("key_ring_{}.json", uuid::Uuid::new_v4()));
+        fs::write(&path, config.to_string()).unwrap();
+
+        let key_ring = KeyRing::from_file(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(key_ring.signing_key(Utc::now()).unwrap().kid, "current");
+        assert_eq!(key_ring.jwks(Utc::now()).keys.len(), 2);
+    }
+}
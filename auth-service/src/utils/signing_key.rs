@@ -0,0 +1,237 @@
+use std::{fs, str::FromStr};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey,
+};
+use rsa::{pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPublicKey};
+use sha2::{Digest, Sha256};
+
+use super::constants::{
+    JWT_KEY_ID, JWT_PRIVATE_KEY_PATH, JWT_PUBLIC_KEY_PATH, JWT_SECRET, JWT_SIGNING_ALGORITHM,
+};
+
+// A key used to verify JWTs, and to sign them when its private half is available
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    // Only asymmetric keys can be published for resource servers
+    jwk: Option<Jwk>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SigningKeyError {
+    InvalidKey(String),
+    UnsupportedAlgorithm(String),
+}
+
+impl SigningKey {
+    // Load the single key described by the JWT_* environment variables
+    pub fn from_config() -> Result<Self, SigningKeyError> {
+        let algorithm = Algorithm::from_str(&JWT_SIGNING_ALGORITHM)
+            .map_err(|_| SigningKeyError::UnsupportedAlgorithm(JWT_SIGNING_ALGORITHM.clone()))?;
+
+        let kid = match JWT_KEY_ID.as_str() {
+            "" => None,
+            kid => Some(kid.to_owned()),
+        };
+
+        if algorithm == Algorithm::HS256 {
+            return Ok(Self::from_secret(kid, &JWT_SECRET));
+        }
+
+        let read = |path: &str| {
+            fs::read(path).map_err(|e| SigningKeyError::InvalidKey(format!("{path}: {e}")))
+        };
+        let private_pem = read(&JWT_PRIVATE_KEY_PATH)?;
+        let public_pem = read(&JWT_PUBLIC_KEY_PATH)?;
+
+        Self::from_pem(algorithm, kid, Some(&private_pem), &public_pem)
+    }
+
+    // The key id is public, so it is never derived from the secret
+    pub fn from_secret(kid: Option<String>, secret: &str) -> Self {
+        Self {
+            kid: kid.unwrap_or_else(|| "hs256".to_owned()),
+            algorithm: Algorithm::HS256,
+            encoding_key: Some(EncodingKey::from_secret(secret.as_bytes())),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            jwk: None,
+        }
+    }
+
+    // Without an explicit key id one is derived from the public key. Keys loaded
+    // without a private key can only verify tokens.
+    pub fn from_pem(
+        algorithm: Algorithm,
+        kid: Option<String>,
+        private_pem: Option<&[u8]>,
+        public_pem: &[u8],
+    ) -> Result<Self, SigningKeyError> {
+        let invalid_key =
+            |e: jsonwebtoken::errors::Error| SigningKeyError::InvalidKey(e.to_string());
+        let kid = kid.unwrap_or_else(|| key_id(public_pem));
+
+        let (encoding_key, decoding_key, parameters, key_algorithm) = match algorithm {
+            Algorithm::RS256 => (
+                private_pem
+                    .map(EncodingKey::from_rsa_pem)
+                    .transpose()
+                    .map_err(invalid_key)?,
+                DecodingKey::from_rsa_pem(public_pem).map_err(invalid_key)?,
+                rsa_parameters(public_pem)?,
+                KeyAlgorithm::RS256,
+            ),
+            Algorithm::EdDSA => (
+                private_pem
+                    .map(EncodingKey::from_ed_pem)
+                    .transpose()
+                    .map_err(invalid_key)?,
+                DecodingKey::from_ed_pem(public_pem).map_err(invalid_key)?,
+                ed25519_parameters(public_pem)?,
+                KeyAlgorithm::EdDSA,
+            ),
+            _ => {
+                return Err(SigningKeyError::UnsupportedAlgorithm(format!(
+                    "{algorithm:?}"
+                )))
+            }
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub fn encoding_key(&self) -> Option<&EncodingKey> {
+        self.encoding_key.as_ref()
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    pub fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+}
+
+fn key_id(key: &[u8]) -> String {
+    BASE64_URL.encode(&Sha256::digest(key)[..12])
+}
+
+fn rsa_parameters(public_pem: &[u8]) -> Result<AlgorithmParameters, SigningKeyError> {
+    let pem = std::str::from_utf8(public_pem)
+        .map_err(|_| SigningKeyError::InvalidKey("RSA public key is not PEM".to_owned()))?;
+    let public_key = RsaPublicKey::from_public_key_pem(pem)
+        .map_err(|e| SigningKeyError::InvalidKey(e.to_string()))?;
+
+    Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: BASE64_URL.encode(public_key.n().to_bytes_be()),
+        e: BASE64_URL.encode(public_key.e().to_bytes_be()),
+    }))
+}
+
+// DER prefix of an Ed25519 SubjectPublicKeyInfo, which is followed by the 32 byte key
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+fn ed25519_parameters(public_pem: &[u8]) -> Result<AlgorithmParameters, SigningKeyError> {
+    let pem = pem::parse(public_pem).map_err(|e| SigningKeyError::InvalidKey(e.to_string()))?;
+
+    let public_key = pem
+        .contents()
+        .strip_prefix(&ED25519_SPKI_PREFIX)
+        .filter(|key| key.len() == 32)
+        .ok_or_else(|| SigningKeyError::InvalidKey("Not an Ed25519 public key".to_owned()))?;
+
+    Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+        key_type: OctetKeyPairType::OctetKeyPair,
+        curve: EllipticCurve::Ed25519,
+        x: BASE64_URL.encode(public_key),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Throwaway keys generated with openssl for these tests only
+    const RS256_PRIVATE: &[u8] = include_bytes!("../../tests/keys/rs256_private.pem");
+    const RS256_PUBLIC: &[u8] = include_bytes!("../../tests/keys/rs256_public.pem");
+    const EDDSA_PRIVATE: &[u8] = include_bytes!("../../tests/keys/eddsa_private.pem");
+    const EDDSA_PUBLIC: &[u8] = include_bytes!("../../tests/keys/eddsa_public.pem");
+
+    #[test]
+    fn rs256_key_publishes_rsa_jwk() {
+        let key = SigningKey::from_pem(
+            Algorithm::RS256,
+            Some("rs256".to_owned()),
+            Some(RS256_PRIVATE),
+            RS256_PUBLIC,
+        )
+        .unwrap();
+
+        let jwk = key.jwk().unwrap();
+        assert_eq!(jwk.common.key_id.as_deref(), Some("rs256"));
+        assert!(matches!(
+            &jwk.algorithm,
+            AlgorithmParameters::RSA(parameters) if parameters.e == "AQAB"
+        ));
+    }
+
+    #[test]
+    fn eddsa_key_publishes_okp_jwk() {
+        let key = SigningKey::from_pem(Algorithm::EdDSA, None, Some(EDDSA_PRIVATE), EDDSA_PUBLIC)
+            .unwrap();
+
+        let jwk = key.jwk().unwrap();
+        assert_eq!(jwk.common.key_id.as_ref(), Some(&key.kid));
+        assert!(matches!(
+            &jwk.algorithm,
+            AlgorithmParameters::OctetKeyPair(parameters) if parameters.x.len() == 43
+        ));
+    }
+
+    #[test]
+    fn mismatched_key_type_returns_err() {
+        let result =
+            SigningKey::from_pem(Algorithm::EdDSA, None, Some(RS256_PRIVATE), RS256_PUBLIC);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn secret_key_is_not_published() {
+        let key = SigningKey::from_secret(None, "secret");
+        assert!(key.jwk().is_none());
+    }
+
+    #[test]
+    fn public_key_only_cannot_sign() {
+        let key = SigningKey::from_pem(Algorithm::EdDSA, None, None, EDDSA_PUBLIC).unwrap();
+        assert!(key.encoding_key().is_none());
+        assert!(key.jwk().is_some());
+    }
+}
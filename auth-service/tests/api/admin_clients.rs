@@ -0,0 +1,182 @@
+use crate::helpers::TestApp;
+use serde_json::{json, Value};
+
+async fn create_client(app: &TestApp, client_id: &str) -> String {
+    let response = app
+        .post_admin_clients(&json!({
+            "clientId": client_id,
+            "redirectUris": ["https://reports.example.com/callback"],
+            "scopes": ["reports:read", "reports:write"]
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["clientId"], client_id);
+
+    body["clientSecret"]
+        .as_str()
+        .expect("No client secret in response body")
+        .to_owned()
+}
+
+async fn request_client_token(app: &TestApp, client_id: &str, secret: &str) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}/token", &app.address))
+        .basic_auth(client_id, Some(secret))
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("scope", "reports:write"),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn access_token(response: reqwest::Response) -> String {
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["scope"], "reports:write");
+
+    body["access_token"]
+        .as_str()
+        .expect("No access token in response body")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_create_client_that_can_request_tokens() {
+    let mut app = TestApp::new().await;
+
+    let secret = create_client(&app, "report-job").await;
+
+    let response = request_client_token(&app, "report-job", &secret).await;
+    access_token(response).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_client_already_exists() {
+    let mut app = TestApp::new().await;
+
+    create_client(&app, "report-job").await;
+
+    let response = app
+        .post_admin_clients(&json!({ "clientId": "report-job" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_client_metadata() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        json!({ "clientId": "report job" }),
+        json!({ "clientId": "report-job", "redirectUris": ["ftp://example.com"] }),
+        json!({ "clientId": "report-job", "scopes": ["reports read"] }),
+    ];
+
+    for test_case in test_cases {
+        let response = app.post_admin_clients(&test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_with_wrong_admin_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/admin/clients", &app.address))
+        .bearer_auth("wrong-admin-api-token")
+        .json(&json!({ "clientId": "report-job" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .http_client
+        .post(format!("{}/admin/clients/report-job/disable", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn rotating_secret_should_replace_old_secret() {
+    let mut app = TestApp::new().await;
+
+    let old_secret = create_client(&app, "report-job").await;
+
+    let response = app.post_admin_rotate_secret("report-job").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    let new_secret = body["clientSecret"]
+        .as_str()
+        .expect("No client secret in response body");
+    assert_ne!(new_secret, old_secret);
+
+    let response = request_client_token(&app, "report-job", &old_secret).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = request_client_token(&app, "report-job", new_secret).await;
+    access_token(response).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disabling_client_should_invalidate_its_tokens() {
+    let mut app = TestApp::new().await;
+
+    let secret = create_client(&app, "report-job").await;
+    let response = request_client_token(&app, "report-job", &secret).await;
+    let token = access_token(response).await;
+
+    let response = app.post_admin_disable_client("report-job").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = request_client_token(&app, "report-job", &secret).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_introspect(&[("token", token.as_str())]).await;
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["active"], false);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_client() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_admin_rotate_secret("missing-client").await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.post_admin_disable_client("missing-client").await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.clean_up().await;
+}
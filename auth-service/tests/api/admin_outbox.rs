@@ -0,0 +1,81 @@
+use std::sync::atomic::Ordering;
+
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::services::MAX_EMAIL_DELIVERY_ATTEMPTS;
+use chrono::Utc;
+use serde_json::{json, Value};
+
+async fn failed_emails(app: &TestApp) -> Vec<Value> {
+    let response = app.get_admin_failed_emails().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    body["emails"]
+        .as_array()
+        .expect("No emails in response body")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_list_emails_that_could_not_be_delivered() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.email_client.failing.store(true, Ordering::SeqCst);
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "Password123!",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Retries are at most an hour apart, so stepping an hour at a time tries every one
+    let mut now = Utc::now().timestamp() as usize;
+    for _ in 0..MAX_EMAIL_DELIVERY_ATTEMPTS - 1 {
+        assert_eq!(app.outbox_worker.deliver_due(now).await, Ok(0));
+        assert!(failed_emails(&app).await.is_empty());
+        now += 3_600;
+    }
+    assert_eq!(app.outbox_worker.deliver_due(now).await, Ok(0));
+
+    let emails = failed_emails(&app).await;
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0]["recipient"], email);
+    assert_eq!(emails[0]["subject"], "Verify Your Email Address");
+    assert_eq!(emails[0]["attempts"], MAX_EMAIL_DELIVERY_ATTEMPTS);
+    assert_eq!(emails[0]["lastError"], "Mail provider unavailable");
+    assert_eq!(emails[0]["failedAt"], now);
+    // The verification link must not leak through the admin API
+    assert!(emails[0].get("text").is_none());
+
+    // Given up on emails are not sent once the provider recovers
+    app.email_client.failing.store(false, Ordering::SeqCst);
+    assert_eq!(app.outbox_worker.deliver_due(now + 3_600).await, Ok(0));
+    assert!(app
+        .last_email(&email, "Verify Your Email Address")
+        .await
+        .is_none());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_with_wrong_admin_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/admin/outbox/failed", &app.address))
+        .bearer_auth("wrong-admin-api-token")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
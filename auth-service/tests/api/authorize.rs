@@ -0,0 +1,150 @@
+use crate::helpers::{
+    get_random_email, TestApp, TEST_CLIENT_ID, TEST_CODE_CHALLENGE, TEST_REDIRECT_URI,
+};
+use reqwest::Url;
+use serde_json::json;
+
+async fn sign_in(app: &TestApp, email: &str) {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// Parameters for a valid authorization request, optionally without one of them
+fn authorize_query(without: &str) -> Vec<(&'static str, &'static str)> {
+    [
+        ("response_type", "code"),
+        ("client_id", TEST_CLIENT_ID),
+        ("redirect_uri", TEST_REDIRECT_URI),
+        ("scope", "openid email"),
+        ("state", "xyz"),
+        ("code_challenge", TEST_CODE_CHALLENGE),
+        ("code_challenge_method", "S256"),
+    ]
+    .into_iter()
+    .filter(|(name, _)| *name != without)
+    .collect()
+}
+
+fn location(response: &reqwest::Response) -> String {
+    assert_eq!(response.status().as_u16(), 303);
+
+    response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .expect("No redirect location found")
+        .to_owned()
+}
+
+fn query_param(location: &str, name: &str) -> Option<String> {
+    Url::parse(location)
+        .expect("Redirect location is not a URL")
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+#[tokio::test]
+async fn should_redirect_to_login_when_signed_out() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_authorize(&authorize_query("")).await;
+
+    let location = location(&response);
+    let return_to = query_param(&format!("http://127.0.0.1{location}"), "return_to")
+        .expect("No return_to in login redirect");
+    assert!(return_to.starts_with("/authorize?"));
+    assert!(return_to.contains("code_challenge_method=S256"));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_redirect_to_client_with_code_and_state() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+
+    let response = app.get_authorize(&authorize_query("")).await;
+
+    let location = location(&response);
+    assert!(location.starts_with(TEST_REDIRECT_URI));
+    assert!(query_param(&location, "code").is_some());
+    assert_eq!(query_param(&location, "state").as_deref(), Some("xyz"));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_redirect_errors_to_client() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+
+    let test_cases = [
+        ("response_type", "unsupported_response_type"),
+        ("scope", "invalid_scope"),
+        ("code_challenge", "invalid_request"),
+        ("code_challenge_method", "invalid_request"),
+    ];
+
+    for (without, error) in test_cases {
+        let response = app.get_authorize(&authorize_query(without)).await;
+
+        let location = location(&response);
+        assert!(location.starts_with(TEST_REDIRECT_URI));
+        assert_eq!(
+            query_param(&location, "error").as_deref(),
+            Some(error),
+            "Failed for request without {without}"
+        );
+        assert!(query_param(&location, "code").is_none());
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_redirect_to_unregistered_uri() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+
+    let mut query = authorize_query("redirect_uri");
+    query.push(("redirect_uri", "http://attacker.example.com/callback"));
+
+    let response = app.get_authorize(&query).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_authorize(&authorize_query("redirect_uri")).await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_client() {
+    let mut app = TestApp::new().await;
+
+    let mut query = authorize_query("client_id");
+    query.push(("client_id", "unknown-client"));
+
+    let response = app.get_authorize(&query).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
@@ -0,0 +1,178 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use reqwest::Url;
+use serde_json::json;
+
+// The default number of failed logins for an account from one address before it is locked
+const IP_LOCKOUT_THRESHOLD: usize = 5;
+
+#[tokio::test]
+async fn should_return_200_and_rotate_token_if_valid_input() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let response = app.signup_and_login(&random_email, false).await;
+    let old_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "Password123!",
+            "newPassword": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    assert_ne!(new_token, old_token);
+
+    let response = app.post_verify_token(&json!({ "token": old_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_token(&json!({ "token": new_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_current_password() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "WrongPassword123!",
+            "newPassword": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The password is unchanged
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "Password123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_new_password() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "Password123!",
+            "newPassword": "weak",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "Password123!",
+            "newPassword": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_repeated_incorrect_current_passwords() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    // Wrong guesses here count towards the same lockout as failed logins
+    for _ in 0..IP_LOCKOUT_THRESHOLD {
+        let response = app
+            .post_change_password(&json!({
+                "currentPassword": "WrongPassword123!",
+                "newPassword": "NewPassword123!",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "Password123!",
+            "newPassword": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "Password123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "Password123!",
+            "newPassword": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_change_password(&json!({ "currentPassword": "Password123!" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+    app.clean_up().await;
+}
@@ -1,42 +1,168 @@
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
+    app_state::{
+        AppState, BannedTokenStoreType, EmailOutboxType, EmailVerificationTokenStoreType,
+        PasswordResetTokenStoreType, TwoFACodeStoreType,
+    },
+    domain::{
+        Client, ClientId, ClientSecret, ClientStore, Email, LoginAttemptId, RedirectUri, TwoFACode,
+    },
     get_postgres_pool, get_redis_client,
-    services::{MockEmailClient, PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
-    utils::constants::{test, DATABASE_URL, REDIS_HOST_NAME},
+    routes::TwoFactorAuthResponse,
+    services::{
+        HashmapRateLimitStore, OidcProviders, OutboxWorker, PostgresClientStore,
+        PostgresEmailOutbox, PostgresUserStore, RateLimiter, RedisAuthorizationCodeStore,
+        RedisBannedTokenStore, RedisEmailVerificationTokenStore, RedisFailedLoginStore,
+        RedisMagicLinkStore, RedisOidcLoginStore, RedisPasskeyChallengeStore,
+        RedisPasswordResetTokenStore, RedisRefreshTokenStore, RedisSessionStore,
+        RedisTwoFACodeStore,
+    },
+    utils::constants::{
+        test, DATABASE_URL, JWT_COOKIE_NAME, REDIS_HOST_NAME, REFRESH_TOKEN_COOKIE_NAME,
+    },
     Application,
 };
+use chrono::Utc;
 use reqwest::cookie::Jar;
+
+use crate::{
+    mock_idp::{MockIdp, MOCK_IDP_NAME},
+    recording_email_client::{RecordingEmailClient, SentEmail},
+};
+use serde_json::json;
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Connection, Executor, PgConnection, PgPool,
 };
-use std::{ops::Drop, str::FromStr, sync::Arc};
+use std::{
+    ops::Drop,
+    str::FromStr,
+    sync::{Arc, Once},
+};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+// OAuth client registered with every test app
+pub const TEST_CLIENT_ID: &str = "test-client";
+pub const TEST_CLIENT_SECRET: &str = "test-client-secret-0123456789abcdef";
+pub const TEST_REDIRECT_URI: &str = "http://127.0.0.1/callback";
+pub const TEST_CLIENT_SCOPE: &str = "reports:read";
+
+// A second client, for checking that clients cannot reach each other's tokens
+pub const OTHER_CLIENT_ID: &str = "other-client";
+pub const OTHER_CLIENT_SECRET: &str = "other-client-secret-0123456789abcdef";
+
+// Bearer token for the /admin API, set before the configuration is first read
+pub const TEST_ADMIN_API_TOKEN: &str = "test-admin-api-token";
+
+static ADMIN_API_TOKEN_INIT: Once = Once::new();
+
+// PKCE verifier and S256 challenge from RFC 7636 appendix B
+pub const TEST_CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+pub const TEST_CODE_CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
 pub struct TestApp {
     pub address: String,
     pub banned_token_store: BannedTokenStoreType,
     pub clean_up_called: bool,
     pub cookie_jar: Arc<Jar>,
     pub db_name: String,
+    pub email_client: Arc<RecordingEmailClient>,
+    pub email_verification_token_store: EmailVerificationTokenStoreType,
     pub http_client: reqwest::Client,
+    pub mock_idp: MockIdp,
+    // Not run in the background, so tests decide when queued emails are delivered
+    pub outbox_worker: OutboxWorker,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
+        ADMIN_API_TOKEN_INIT
+            .call_once(|| std::env::set_var("ADMIN_API_TOKEN", TEST_ADMIN_API_TOKEN));
+
         let redis_conn = Arc::new(RwLock::new(configure_redis()));
+        let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+            redis_conn.clone(),
+        )));
         let banned_token_store =
             Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
-        let mock_email_client = Arc::new(MockEmailClient::default());
+        let email_client = Arc::new(RecordingEmailClient::default());
+        let email_verification_token_store = Arc::new(RwLock::new(
+            RedisEmailVerificationTokenStore::new(redis_conn.clone()),
+        ));
+        let mock_idp = MockIdp::start().await;
+        let failed_login_store =
+            Arc::new(RwLock::new(RedisFailedLoginStore::new(redis_conn.clone())));
+        let magic_link_store = Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_conn.clone())));
+        let oidc_login_store = Arc::new(RwLock::new(RedisOidcLoginStore::new(redis_conn.clone())));
+        let oidc_providers = Arc::new(OidcProviders::new(vec![mock_idp.provider()]));
+        let passkey_challenge_store = Arc::new(RwLock::new(RedisPasskeyChallengeStore::new(
+            redis_conn.clone(),
+        )));
+        let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(
+            redis_conn.clone(),
+        )));
+        // Every test app connects from 127.0.0.1, so each keeps its own buckets
+        let rate_limiter = Arc::new(
+            RateLimiter::new(
+                RateLimiter::default_rules(),
+                Arc::new(RwLock::new(HashmapRateLimitStore::default())),
+            )
+            .expect("Failed to create rate limiter"),
+        );
+        let refresh_token_store =
+            Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+        let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_conn.clone())));
         let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
         let (pg_pool, db_name) = configure_postgresql().await;
+        let client_store = Arc::new(RwLock::new(PostgresClientStore::new(pg_pool.clone())));
+        client_store
+            .write()
+            .await
+            .set_client(
+                Client::new(
+                    ClientId::parse(TEST_CLIENT_ID).unwrap(),
+                    vec![RedirectUri::parse(TEST_REDIRECT_URI).unwrap()],
+                    vec![TEST_CLIENT_SCOPE.to_owned()],
+                ),
+                ClientSecret::parse(TEST_CLIENT_SECRET).unwrap(),
+            )
+            .await
+            .expect("Failed to register test client");
+        client_store
+            .write()
+            .await
+            .set_client(
+                Client::new(
+                    ClientId::parse(OTHER_CLIENT_ID).unwrap(),
+                    Vec::new(),
+                    vec![TEST_CLIENT_SCOPE.to_owned()],
+                ),
+                ClientSecret::parse(OTHER_CLIENT_SECRET).unwrap(),
+            )
+            .await
+            .expect("Failed to register other client");
+        let email_outbox: EmailOutboxType =
+            Arc::new(RwLock::new(PostgresEmailOutbox::new(pg_pool.clone())));
+        let outbox_worker = OutboxWorker::new(email_outbox.clone(), email_client.clone());
         let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
 
         let app_state = AppState::new(
+            authorization_code_store,
             banned_token_store.clone(),
-            mock_email_client.clone(),
+            client_store,
+            email_outbox,
+            email_verification_token_store.clone(),
+            failed_login_store,
+            magic_link_store,
+            oidc_login_store,
+            oidc_providers,
+            passkey_challenge_store,
+            password_reset_token_store.clone(),
+            rate_limiter,
+            refresh_token_store,
+            session_store,
             two_fa_code_store.clone(),
             user_store,
         );
@@ -55,6 +181,8 @@ impl TestApp {
         let cookie_jar = Arc::new(Jar::default());
         let http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
+            // Let tests inspect redirects, such as those back to an OAuth client
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .unwrap();
 
@@ -64,7 +192,12 @@ impl TestApp {
             clean_up_called: false,
             db_name,
             cookie_jar,
+            email_client,
+            email_verification_token_store,
             http_client,
+            mock_idp,
+            outbox_worker,
+            password_reset_token_store,
             two_fa_code_store,
         }
     }
@@ -76,7 +209,346 @@ impl TestApp {
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_authorize<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Have the signed in user authorize the test client and return the code it was sent
+    pub async fn get_authorization_code(&self, scope: &str) -> String {
+        let response = self
+            .get_authorize(&[
+                ("response_type", "code"),
+                ("client_id", TEST_CLIENT_ID),
+                ("redirect_uri", TEST_REDIRECT_URI),
+                ("scope", scope),
+                ("state", "state"),
+                ("nonce", "nonce"),
+                ("code_challenge", TEST_CODE_CHALLENGE),
+                ("code_challenge_method", "S256"),
+            ])
+            .await;
+        assert_eq!(response.status().as_u16(), 303);
+
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| reqwest::Url::parse(location).ok())
+            .expect("No redirect location found");
+
+        location
+            .query_pairs()
+            .find(|(name, _)| name == "code")
+            .map(|(_, code)| code.into_owned())
+            .expect("No authorization code in redirect")
+    }
+
+    pub async fn post_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .basic_auth(TEST_CLIENT_ID, Some(TEST_CLIENT_SECRET))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_clients<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/clients", &self.address))
+            .bearer_auth(TEST_ADMIN_API_TOKEN)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_rotate_secret(&self, client_id: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!(
+                "{}/admin/clients/{}/rotate-secret",
+                &self.address, client_id
+            ))
+            .bearer_auth(TEST_ADMIN_API_TOKEN)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_disable_client(&self, client_id: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!(
+                "{}/admin/clients/{}/disable",
+                &self.address, client_id
+            ))
+            .bearer_auth(TEST_ADMIN_API_TOKEN)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_failed_emails(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/outbox/failed", &self.address))
+            .bearer_auth(TEST_ADMIN_API_TOKEN)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_oidc_providers(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/oidc/providers", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_oidc_login<Query>(&self, provider: &str, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/oidc/{}/login", &self.address, provider))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Start a login at the mock identity provider, which signs its user in straight
+    // away, and return the query it sends back to the callback
+    pub async fn get_mock_idp_callback_query(&self, return_to: Option<&str>) -> String {
+        let query: Vec<_> = return_to
+            .map(|return_to| ("return_to", return_to))
+            .into_iter()
+            .collect();
+        let response = self.get_oidc_login(MOCK_IDP_NAME, &query).await;
+        assert_eq!(response.status().as_u16(), 303);
+
+        let response = self
+            .http_client
+            .get(redirect_location(&response))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 303);
+
+        reqwest::Url::parse(&redirect_location(&response))
+            .expect("Invalid callback URL")
+            .query()
+            .expect("No query in callback URL")
+            .to_owned()
+    }
+
+    pub async fn get_oidc_callback(&self, provider: &str, query: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/oidc/{}/callback?{}",
+                &self.address, provider, query
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Sign in through the mock identity provider as whichever user it is set up with
+    pub async fn sign_in_with_mock_idp(&self, return_to: Option<&str>) -> reqwest::Response {
+        let query = self.get_mock_idp_callback_query(return_to).await;
+        self.get_oidc_callback(MOCK_IDP_NAME, &query).await
+    }
+
+    pub async fn get_userinfo(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/userinfo", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_2fa_enable<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/enable", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_2fa_email_code(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/email-code", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_2fa_disable<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/disable", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_2fa_enable<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/passkey/enable", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/passkeys/register/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_reauthenticate_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkeys/reauthenticate/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/passkeys/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/passkeys/login/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/passkeys/login/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -87,19 +559,114 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_introspect<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/introspect", &self.address))
+            .basic_auth(TEST_CLIENT_ID, Some(TEST_CLIENT_SECRET))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/revoke", &self.address))
+            .basic_auth(TEST_CLIENT_ID, Some(TEST_CLIENT_SECRET))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Follow a link from a magic link email, which points at the public service URL
+    pub async fn get_magic_link(&self, link: &str) -> reqwest::Response {
+        let link = reqwest::Url::parse(link).expect("Magic link was not a URL");
+
+        self.http_client
+            .get(format!(
+                "{}{}?{}",
+                &self.address,
+                link.path(),
+                link.query().unwrap_or_default()
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Confirm a magic link as its page does, by posting the token back
+    pub async fn post_magic_link_callback(&self, link: &str) -> reqwest::Response {
+        let link = reqwest::Url::parse(link).expect("Magic link was not a URL");
+        let token = link
+            .query_pairs()
+            .find(|(name, _)| name == "token")
+            .map(|(_, token)| token.into_owned())
+            .unwrap_or_default();
+
+        self.http_client
+            .post(format!("{}{}", &self.address, link.path()))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_login_disable<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-login/disable", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_login_enable<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-login/enable", &self.address))
             .json(body)
             .send()
             .await
@@ -108,7 +675,191 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_verify_email(&self, email: &str, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/verify-email", &self.address))
+            .query(&[("email", email), ("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email_resend<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Deliver every queued email that is due, as the outbox worker would
+    pub async fn deliver_emails(&self) {
+        let now = Utc::now().timestamp().try_into().unwrap();
+
+        while self
+            .outbox_worker
+            .deliver_due(now)
+            .await
+            .expect("Failed to deliver queued emails")
+            > 0
+        {}
+    }
+
+    // The most recent email delivered to the recipient with the given subject
+    pub async fn last_email(&self, recipient: &str, subject: &str) -> Option<SentEmail> {
+        self.deliver_emails().await;
+        self.email_client.last_email(recipient, subject)
+    }
+
+    // The code recorded for a login attempt, which is only emailed to some users
+    pub async fn get_two_fa_code(&self, login_attempt_id: impl AsRef<str>) -> TwoFACode {
+        let login_attempt_id =
+            LoginAttemptId::parse(login_attempt_id.as_ref()).expect("Invalid login attempt id");
+
+        self.two_fa_code_store
+            .read()
+            .await
+            .get_code(&login_attempt_id)
+            .await
+            .expect("No 2FA code stored")
+            .code
+    }
+
+    // Follow the verification link emailed to a newly signed up user
+    pub async fn verify_email(&self, email: impl AsRef<str>) {
+        let email = Email::parse(email.as_ref()).expect("Email was not parseable");
+        let token = self
+            .email_verification_token_store
+            .read()
+            .await
+            .get_token(&email)
+            .await
+            .expect("No email verification token found");
+
+        let response = self.get_verify_email(email.as_ref(), token.as_ref()).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Sign up with a verified email and log in with the password. Without 2FA the response
+    // carries the session cookies, otherwise it starts a login attempt.
+    pub async fn signup_and_login(
+        &self,
+        email: impl AsRef<str>,
+        requires_2fa: bool,
+    ) -> reqwest::Response {
+        let email = email.as_ref();
+        let signup_body = json!({
+            "email": email,
+            "password": "Password123!",
+            "requires2FA": requires_2fa
+        });
+
+        let response = self.post_signup(&signup_body).await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        self.verify_email(email).await;
+
+        let login_body = json!({
+            "email": email,
+            "password": "Password123!",
+        });
+
+        let response = self.post_login(&login_body).await;
+        match requires_2fa {
+            true => assert_eq!(response.status().as_u16(), 206),
+            false => assert_eq!(response.status().as_u16(), 200),
+        }
+
+        response
+    }
+
+    // The login attempt a 206 login response started and the code recorded for it
+    pub async fn get_login_attempt(
+        &self,
+        response: reqwest::Response,
+    ) -> (LoginAttemptId, TwoFACode) {
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+        let two_fa_code = self.get_two_fa_code(&login_attempt_id).await;
+
+        (
+            LoginAttemptId::parse(&login_attempt_id).expect("Invalid login attempt id"),
+            two_fa_code,
+        )
+    }
+
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
+            .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -147,6 +898,35 @@ impl Drop for TestApp {
     }
 }
 
+pub fn redirect_location(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .expect("No redirect location found")
+        .to_owned()
+}
+
+// Whether the response signed the user in by setting a non-empty JWT cookie
+pub fn has_auth_cookie(response: &reqwest::Response) -> bool {
+    response
+        .cookies()
+        .any(|cookie| cookie.name() == JWT_COOKIE_NAME && !cookie.value().is_empty())
+}
+
+// The JWT and refresh token cookies a login response set for the new session
+pub fn session_tokens(response: &reqwest::Response) -> (String, String) {
+    let cookie = |name: &str| {
+        response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .map(|cookie| cookie.value().to_owned())
+            .expect("No cookie found")
+    };
+
+    (cookie(JWT_COOKIE_NAME), cookie(REFRESH_TOKEN_COOKIE_NAME))
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
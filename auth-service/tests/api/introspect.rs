@@ -0,0 +1,253 @@
+use crate::helpers::{
+    get_random_email, session_tokens, TestApp, OTHER_CLIENT_ID, OTHER_CLIENT_SECRET,
+    TEST_CLIENT_ID, TEST_CLIENT_SCOPE, TEST_CLIENT_SECRET, TEST_CODE_VERIFIER, TEST_REDIRECT_URI,
+};
+use serde_json::{json, Value};
+
+// Run the authorization code flow for the signed in user and return the access token
+async fn access_token(app: &TestApp) -> String {
+    let code = app.get_authorization_code("openid").await;
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", TEST_REDIRECT_URI),
+            ("code_verifier", TEST_CODE_VERIFIER),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    body["access_token"]
+        .as_str()
+        .expect("No access token in response body")
+        .to_owned()
+}
+
+async fn client_token(app: &TestApp) -> String {
+    let response = app
+        .post_token(&[("grant_type", "client_credentials")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    body["access_token"]
+        .as_str()
+        .expect("No access token in response body")
+        .to_owned()
+}
+
+async fn introspect(app: &TestApp, token: &str) -> Value {
+    let response = app.post_introspect(&[("token", token)]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body")
+}
+
+#[tokio::test]
+async fn should_return_active_token_details() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let token = access_token(&app).await;
+
+    let body = introspect(&app, &token).await;
+
+    assert_eq!(body["active"], true);
+    assert_eq!(body["sub"], email);
+    assert_eq!(body["client_id"], TEST_CLIENT_ID);
+    assert_eq!(body["scope"], "openid");
+    assert_eq!(body["token_type"], "Bearer");
+    assert!(body["exp"].as_u64() > body["iat"].as_u64());
+    assert!(body["sid"].is_string());
+    assert!(body["session_created_at"].is_u64());
+    assert!(body["session_last_seen"].is_u64());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_client_token_details() {
+    let mut app = TestApp::new().await;
+
+    let token = client_token(&app).await;
+
+    let body = introspect(&app, &token).await;
+
+    assert_eq!(body["active"], true);
+    assert_eq!(body["sub"], TEST_CLIENT_ID);
+    assert_eq!(body["client_id"], TEST_CLIENT_ID);
+    assert_eq!(body["scope"], TEST_CLIENT_SCOPE);
+    assert!(body["sid"].is_null());
+
+    let response = app.post_revoke(&[("token", token.as_str())]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = introspect(&app, &token).await;
+    assert_eq!(body["active"], false);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_inactive_for_invalid_tokens() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let (_, refresh_token) = session_tokens(&app.signup_and_login(&email, false).await);
+
+    for token in ["", "invalid", &refresh_token] {
+        let body = introspect(&app, token).await;
+        assert_eq!(body, json!({ "active": false }));
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_browser_session_token_details() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let (token, _) = session_tokens(&app.signup_and_login(&email, false).await);
+
+    // Browser session tokens are not issued to any client, so every client sees them
+    for (client_id, client_secret) in [
+        (TEST_CLIENT_ID, TEST_CLIENT_SECRET),
+        (OTHER_CLIENT_ID, OTHER_CLIENT_SECRET),
+    ] {
+        let response = app
+            .http_client
+            .post(format!("{}/introspect", &app.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(&[("token", token.as_str())])
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 200);
+
+        let body = response
+            .json::<Value>()
+            .await
+            .expect("Could not deserialize response body");
+        assert_eq!(body["active"], true);
+        assert_eq!(body["sub"], email);
+        assert!(body["sid"].is_string());
+        assert!(body["session_last_seen"].is_u64());
+        assert!(body.get("client_id").is_none());
+        assert!(body.get("scope").is_none());
+    }
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = introspect(&app, &token).await;
+    assert_eq!(body, json!({ "active": false }));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_inactive_after_logout() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let token = access_token(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = introspect(&app, &token).await;
+    assert_eq!(body, json!({ "active": false }));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_inactive_for_tokens_of_other_clients() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let tokens = [access_token(&app).await, client_token(&app).await];
+
+    for token in tokens {
+        let response = app
+            .http_client
+            .post(format!("{}/introspect", &app.address))
+            .basic_auth(OTHER_CLIENT_ID, Some(OTHER_CLIENT_SECRET))
+            .form(&[("token", token.as_str())])
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 200);
+
+        let body = response
+            .json::<Value>()
+            .await
+            .expect("Could not deserialize response body");
+        assert_eq!(body, json!({ "active": false }));
+
+        // The client the token was issued to still sees it as active
+        let body = introspect(&app, &token).await;
+        assert_eq!(body["active"], true);
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_client_credentials_in_body() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let (token, _) = session_tokens(&app.signup_and_login(&email, false).await);
+
+    let response = app
+        .http_client
+        .post(format!("{}/introspect", &app.address))
+        .form(&[
+            ("token", token.as_str()),
+            ("client_id", TEST_CLIENT_ID),
+            ("client_secret", TEST_CLIENT_SECRET),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unauthenticated_client() {
+    let mut app = TestApp::new().await;
+
+    let requests = [
+        app.http_client
+            .post(format!("{}/introspect", &app.address))
+            .form(&[("token", "token")]),
+        app.http_client
+            .post(format!("{}/introspect", &app.address))
+            .basic_auth(TEST_CLIENT_ID, Some("wrong-secret-0123456789abcdefghijkl"))
+            .form(&[("token", "token")]),
+        app.http_client
+            .post(format!("{}/introspect", &app.address))
+            .basic_auth("unknown-client", Some(TEST_CLIENT_SECRET))
+            .form(&[("token", "token")]),
+    ];
+
+    for request in requests {
+        let response = request.send().await.expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    app.clean_up().await;
+}
@@ -0,0 +1,24 @@
+use crate::helpers::TestApp;
+use serde_json::Value;
+
+#[tokio::test]
+async fn should_return_200_with_key_set() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    // Symmetric keys are never published, so the set may be empty
+    let keys = body["keys"].as_array().expect("No keys in response body");
+    for key in keys {
+        assert!(key["kid"].is_string());
+        assert_eq!(key["use"], "sig");
+    }
+    app.clean_up().await;
+}
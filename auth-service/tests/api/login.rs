@@ -1,10 +1,13 @@
 use crate::helpers::{get_random_email, TestApp};
 use auth_service::{
-    domain::{Email, LoginAttemptId},
+    domain::{Email, LoginAttemptId, TwoFAMethod},
     routes::TwoFactorAuthResponse,
     utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
 };
+use chrono::Utc;
 use serde_json::json;
+use std::sync::atomic::Ordering;
 
 #[tokio::test]
 async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
@@ -22,6 +25,8 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "Password123!",
@@ -56,6 +61,8 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "Password123!",
@@ -65,24 +72,111 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(response.status().as_u16(), 206);
 
+    // The auth cookie must not be issued until the second factor is verified
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
     let json_body = response
         .json::<TwoFactorAuthResponse>()
         .await
         .expect("Could not deserialize response body to TwoFactorAuthResponse");
 
     assert_eq!(json_body.message, "2FA required".to_owned());
+    assert_eq!(json_body.two_fa_method, TwoFAMethod::Email);
 
-    let (login_attempt_id, _) = app
+    let login_attempt_id = LoginAttemptId::parse(&json_body.login_attempt_id).unwrap();
+    let challenge = app
         .two_fa_code_store
         .read()
         .await
-        .get_code(&random_email)
+        .get_code(&login_attempt_id)
         .await
         .unwrap();
 
+    assert_eq!(challenge.email, random_email);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_206_and_email_code_later_if_email_provider_is_down() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "Password123!",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+    app.deliver_emails().await;
+
+    app.email_client.failing.store(true, Ordering::SeqCst);
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "Password123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(app
+        .last_email(&email, "Your Login Authentication Code")
+        .await
+        .is_none());
+
+    // The code stays queued and is sent on a later attempt once the provider recovers
+    app.email_client.failing.store(false, Ordering::SeqCst);
+    let later = Utc::now().timestamp() as usize + 60;
+    assert_eq!(app.outbox_worker.deliver_due(later).await, Ok(1));
+    assert!(app
+        .last_email(&email, "Your Login Authentication Code")
+        .await
+        .is_some());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_email_not_verified() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .is_none());
+
+    let expected_response = ErrorResponse {
+        error: "Email not verified".to_string(),
+    };
+
     assert_eq!(
-        LoginAttemptId::parse(&json_body.login_attempt_id).unwrap(),
-        login_attempt_id
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse"),
+        expected_response
     );
     app.clean_up().await;
 }
@@ -117,8 +211,10 @@ async fn should_return_400_if_invalid_input() {
 #[tokio::test]
 async fn should_return_401_if_incorrect_credentials() {
     let mut app = TestApp::new().await;
+    // Failed logins are counted in Redis, which outlives the test database
+    let email = get_random_email();
     let user_json = json!({
-        "email": "mreynolds@serenity.co",
+        "email": email,
         "password": "N0thingInTheverse!",
         "requires2FA": false
     });
@@ -128,7 +224,7 @@ async fn should_return_401_if_incorrect_credentials() {
     assert_eq!(response.status().as_u16(), 201);
 
     let invalid_password = json!({
-        "email": "mreynolds@serenity.co",
+        "email": email,
         "password": "Noth1ngInTheverse?",
     });
 
@@ -137,3 +233,123 @@ async fn should_return_401_if_incorrect_credentials() {
     assert_eq!(response.status().as_u16(), 401);
     app.clean_up().await;
 }
+
+// The default number of failed logins for an account from one address before it is locked
+const IP_LOCKOUT_THRESHOLD: usize = 5;
+
+async fn signup_and_verify(app: &TestApp, email: &str) {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+}
+
+async fn fail_logins(app: &TestApp, email: &str, attempts: usize) {
+    let login_body = json!({
+        "email": email,
+        "password": "WrongPassword1!",
+    });
+
+    for _ in 0..attempts {
+        let response = app.post_login(&login_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
+
+#[tokio::test]
+async fn should_return_429_after_repeated_failed_logins() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_verify(&app, &email).await;
+
+    fail_logins(&app, &email, IP_LOCKOUT_THRESHOLD).await;
+
+    // Even the right password is refused until the lockout ends
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 60);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Account locked"
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_email_user_when_account_is_locked() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_verify(&app, &email).await;
+
+    fail_logins(&app, &email, IP_LOCKOUT_THRESHOLD - 1).await;
+    assert!(app
+        .last_email(&email, "Your Account Has Been Locked")
+        .await
+        .is_none());
+
+    fail_logins(&app, &email, 1).await;
+    assert!(app
+        .last_email(&email, "Your Account Has Been Locked")
+        .await
+        .is_some());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_forget_failed_logins_after_successful_login() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_verify(&app, &email).await;
+
+    fail_logins(&app, &email, IP_LOCKOUT_THRESHOLD - 1).await;
+
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    fail_logins(&app, &email, IP_LOCKOUT_THRESHOLD - 1).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_lock_unknown_emails_like_registered_ones() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    fail_logins(&app, &email, IP_LOCKOUT_THRESHOLD).await;
+
+    let login_body = json!({
+        "email": email,
+        "password": "WrongPassword1!",
+    });
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    app.clean_up().await;
+}
@@ -0,0 +1,97 @@
+use crate::helpers::{get_random_email, session_tokens, TestApp};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+use reqwest::Url;
+use serde_json::json;
+
+// Log in and return the JWT and refresh token issued for the new session
+async fn login(app: &TestApp, email: &str) -> (String, String) {
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    session_tokens(&response)
+}
+
+#[tokio::test]
+async fn should_return_200_and_revoke_every_session() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&email).await;
+
+    let (first_token, first_refresh_token) = login(&app, &email).await;
+    let (second_token, _) = login(&app, &email).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+
+    for token in [first_token, second_token] {
+        let response = app.post_verify_token(&json!({ "token": token })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_TOKEN_COOKIE_NAME, first_refresh_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Signing in again afterwards still works
+    let (token, _) = login(&app, &email).await;
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
@@ -0,0 +1,223 @@
+use crate::helpers::{get_random_email, has_auth_cookie, redirect_location, TestApp};
+use auth_service::{routes::MagicLinkResponse, utils::constants::JWT_COOKIE_NAME};
+use serde_json::json;
+
+const MAGIC_LINK_SUBJECT: &str = "Your Sign In Link";
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": requires_2fa
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+// Ask for a magic link and return the link from the email
+async fn request_magic_link(app: &TestApp, email: &str) -> String {
+    let response = app.post_magic_link(&json!({ "email": email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.last_email(email, MAGIC_LINK_SUBJECT)
+        .await
+        .expect("No magic link email was sent")
+        .content
+}
+
+#[tokio::test]
+async fn should_sign_in_with_magic_link() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    let link = request_magic_link(&app, &email).await;
+    let response = app.post_magic_link_callback(&link).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(redirect_location(&response), "/");
+    assert!(has_auth_cookie(&response));
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_show_confirmation_page_without_using_link() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    let link = request_magic_link(&app, &email).await;
+
+    // Opening the link, as a mail scanner would, neither signs in nor uses it up
+    for _ in 0..2 {
+        let response = app.get_magic_link(&link).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert!(!has_auth_cookie(&response));
+        let page = response.text().await.expect("No page in response body");
+        assert!(page.contains(r#"<form method="post" action="/login/magic-link/callback">"#));
+    }
+
+    let response = app.post_magic_link_callback(&link).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(has_auth_cookie(&response));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_verify_email_of_user_following_magic_link() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let link = request_magic_link(&app, &email).await;
+    let response = app.post_magic_link_callback(&link).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(has_auth_cookie(&response));
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_discard_setup_of_unverified_account() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    // Someone else registers the address with their own password and 2FA
+    signup(&app, &email, true).await;
+
+    let link = request_magic_link(&app, &email).await;
+    let response = app.post_magic_link_callback(&link).await;
+
+    // The owner of the address is signed in without the other person's second factor
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(redirect_location(&response), "/");
+    assert!(has_auth_cookie(&response));
+
+    // and the password they chose no longer works
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_without_sending_email_for_unknown_user() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app.post_magic_link(&json!({ "email": email })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<MagicLinkResponse>()
+        .await
+        .expect("Could not deserialize response body to MagicLinkResponse");
+    assert_eq!(
+        body.message,
+        "If the account exists, a sign in link has been sent"
+    );
+    assert!(app.last_email(&email, MAGIC_LINK_SUBJECT).await.is_none());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_magic_link(&json!({ "email": "invalid" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_magic_link_is_reused() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    let link = request_magic_link(&app, &email).await;
+    let response = app.post_magic_link_callback(&link).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let response = app.post_magic_link_callback(&link).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(!has_auth_cookie(&response));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_magic_link_is_tampered_with() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    // Changing any part of the signed token breaks its signature
+    let link = request_magic_link(&app, &email).await;
+    let (signed, signature) = link.rsplit_once('.').unwrap();
+    let replacement = if signature.starts_with('A') { "B" } else { "A" };
+    let link = format!("{}.{}{}", signed, replacement, &signature[1..]);
+
+    let response = app.get_magic_link(&link).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_magic_link_callback(&link).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_auth_token_in_place_of_magic_link() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+    let response = app.post_login(&login_body).await;
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let link = format!("http://localhost/login/magic-link/callback?token={auth_token}");
+    let response = app.get_magic_link(&link).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_after_magic_link() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, true).await;
+    app.verify_email(&email).await;
+
+    let link = request_magic_link(&app, &email).await;
+    let response = app.post_magic_link_callback(&link).await;
+
+    // The login page is given the attempt to finish with a code
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(redirect_location(&response).contains("login_attempt_id="));
+    assert!(!has_auth_cookie(&response));
+    app.clean_up().await;
+}
@@ -1,7 +1,36 @@
+mod admin_clients;
+mod admin_outbox;
+mod authorize;
+mod change_password;
 mod helpers;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod logout_all;
+mod magic_link;
+mod mock_idp;
+mod oidc_login;
+mod openid_configuration;
+mod passkeys;
+mod password_login;
+mod password_reset;
+mod rate_limit;
+mod recording_email_client;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
+mod revoke;
 mod root;
+mod sessions;
 mod signup;
+mod smtp_email_client;
+mod smtp_sink;
+mod soft_authenticator;
+mod token;
+mod totp;
+mod two_fa;
+mod userinfo;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
@@ -0,0 +1,223 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use auth_service::{
+    services::{OidcProvider, OidcProviderConfig},
+    utils::signing_key::SigningKey,
+};
+use axum::{
+    extract::{Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL},
+    Engine,
+};
+use chrono::Utc;
+use jsonwebtoken::{encode, jwk::JwkSet, Algorithm, EncodingKey, Header};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub const MOCK_IDP_NAME: &str = "mock";
+const MOCK_IDP_CLIENT_ID: &str = "auth-service";
+const MOCK_IDP_CLIENT_SECRET: &str = "mock-idp-client-secret";
+const MOCK_IDP_KEY_ID: &str = "mock-idp";
+
+const RS256_PRIVATE: &[u8] = include_bytes!("../keys/rs256_private.pem");
+const RS256_PUBLIC: &[u8] = include_bytes!("../keys/rs256_public.pem");
+
+// A local OpenID Connect provider that signs in whichever user the test picked,
+// without asking them anything
+#[derive(Clone)]
+pub struct MockIdp {
+    pub issuer: String,
+    state: Arc<Mutex<MockIdpState>>,
+}
+
+struct MockIdpState {
+    issuer: String,
+    email: String,
+    email_verified: bool,
+    codes: HashMap<String, PendingCode>,
+}
+
+// What the provider remembers about an authorization request until its code is redeemed
+struct PendingCode {
+    email: String,
+    email_verified: bool,
+    nonce: Option<String>,
+    redirect_uri: String,
+    code_challenge: String,
+}
+
+impl MockIdp {
+    pub async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind mock identity provider");
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = Arc::new(Mutex::new(MockIdpState {
+            issuer: issuer.clone(),
+            email: "user@example.com".to_owned(),
+            email_verified: true,
+            codes: HashMap::new(),
+        }));
+
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/authorize", get(authorize))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(state.clone());
+
+        #[allow(clippy::let_underscore_future)]
+        let _ = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self { issuer, state }
+    }
+
+    // Choose the user the provider signs in from now on
+    pub fn set_user(&self, email: &str, email_verified: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.email = email.to_owned();
+        state.email_verified = email_verified;
+    }
+
+    pub fn provider(&self) -> OidcProvider {
+        OidcProvider::new(OidcProviderConfig {
+            name: MOCK_IDP_NAME.to_owned(),
+            issuer: self.issuer.clone(),
+            client_id: MOCK_IDP_CLIENT_ID.to_owned(),
+            client_secret: MOCK_IDP_CLIENT_SECRET.to_owned(),
+            scopes: vec!["openid".to_owned(), "email".to_owned()],
+        })
+        .expect("Invalid mock identity provider")
+    }
+}
+
+type SharedState = Arc<Mutex<MockIdpState>>;
+
+async fn discovery(State(state): State<SharedState>) -> impl IntoResponse {
+    let issuer = state.lock().unwrap().issuer.clone();
+
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/authorize"),
+        "token_endpoint": format!("{issuer}/token"),
+        "jwks_uri": format!("{issuer}/jwks"),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"]
+    }))
+}
+
+async fn authorize(
+    State(state): State<SharedState>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Redirect, StatusCode> {
+    let param = |name: &str| params.get(name).cloned().ok_or(StatusCode::BAD_REQUEST);
+
+    if param("client_id")? != MOCK_IDP_CLIENT_ID || param("code_challenge_method")? != "S256" {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut state = state.lock().unwrap();
+    let code = Uuid::new_v4().to_string();
+    let redirect_uri = param("redirect_uri")?;
+    let pending_code = PendingCode {
+        email: state.email.clone(),
+        email_verified: state.email_verified,
+        nonce: params.get("nonce").cloned(),
+        redirect_uri: redirect_uri.clone(),
+        code_challenge: param("code_challenge")?,
+    };
+    state.codes.insert(code.clone(), pending_code);
+
+    let query = serde_urlencoded::to_string([("code", code), ("state", param("state")?)])
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to(&format!("{redirect_uri}?{query}")))
+}
+
+async fn token(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Form(params): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let expected_credentials = format!(
+        "Basic {}",
+        BASE64.encode(format!("{MOCK_IDP_CLIENT_ID}:{MOCK_IDP_CLIENT_SECRET}"))
+    );
+    let credentials = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    if credentials != Some(expected_credentials.as_str()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let param = |name: &str| params.get(name).cloned().unwrap_or_default();
+
+    let mut state = state.lock().unwrap();
+    let pending_code = state
+        .codes
+        .remove(&param("code"))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let code_challenge = BASE64_URL.encode(Sha256::digest(param("code_verifier")));
+    if param("grant_type") != "authorization_code"
+        || param("redirect_uri") != pending_code.redirect_uri
+        || code_challenge != pending_code.code_challenge
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let now = Utc::now().timestamp();
+    let claims = json!({
+        "iss": state.issuer,
+        "aud": MOCK_IDP_CLIENT_ID,
+        "sub": Uuid::new_v4().to_string(),
+        "exp": now + 300,
+        "iat": now,
+        "nonce": pending_code.nonce,
+        "email": pending_code.email,
+        "email_verified": pending_code.email_verified
+    });
+    let header = Header {
+        kid: Some(MOCK_IDP_KEY_ID.to_owned()),
+        ..Header::new(Algorithm::RS256)
+    };
+    let id_token = encode(
+        &header,
+        &claims,
+        &EncodingKey::from_rsa_pem(RS256_PRIVATE).unwrap(),
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(json!({
+        "access_token": Uuid::new_v4().to_string(),
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": id_token
+    })))
+}
+
+async fn jwks() -> impl IntoResponse {
+    let key = SigningKey::from_pem(
+        Algorithm::RS256,
+        Some(MOCK_IDP_KEY_ID.to_owned()),
+        None,
+        RS256_PUBLIC,
+    )
+    .unwrap();
+
+    Json(JwkSet {
+        keys: vec![key.jwk().unwrap().clone()],
+    })
+}
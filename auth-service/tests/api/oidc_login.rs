@@ -0,0 +1,199 @@
+use crate::{
+    helpers::{get_random_email, has_auth_cookie, redirect_location, TestApp},
+    mock_idp::MOCK_IDP_NAME,
+};
+use serde_json::{json, Value};
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": requires_2fa
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn should_list_configured_providers() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_oidc_providers().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["providers"], json!([MOCK_IDP_NAME]));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_create_user_and_sign_in() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.mock_idp.set_user(&email, true);
+
+    let response = app.sign_in_with_mock_idp(None).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(redirect_location(&response), "/");
+    assert!(has_auth_cookie(&response));
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_sign_in_existing_user_and_keep_password() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+    app.mock_idp.set_user(&email, true);
+
+    let response = app.sign_in_with_mock_idp(None).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(has_auth_cookie(&response));
+
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_replace_password_of_unverified_account() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.mock_idp.set_user(&email, true);
+
+    let response = app.sign_in_with_mock_idp(None).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(has_auth_cookie(&response));
+
+    // Whoever registered the email without verifying it can no longer sign in
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_for_users_with_2fa() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, true).await;
+    app.verify_email(&email).await;
+    app.mock_idp.set_user(&email, true);
+
+    let response = app.sign_in_with_mock_idp(None).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(!has_auth_cookie(&response));
+
+    let location = reqwest::Url::parse("http://localhost")
+        .unwrap()
+        .join(&redirect_location(&response))
+        .unwrap();
+    let login_attempt_id = location
+        .query_pairs()
+        .find(|(name, _)| name == "login_attempt_id")
+        .map(|(_, value)| value.into_owned())
+        .expect("No login attempt id in redirect");
+
+    let challenge = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&auth_service::domain::LoginAttemptId::parse(&login_attempt_id).unwrap())
+        .await
+        .expect("No 2FA code stored");
+    assert_eq!(challenge.email.as_ref(), email);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_to_authorize_only() {
+    let mut app = TestApp::new().await;
+    app.mock_idp.set_user(&get_random_email(), true);
+
+    let return_to = "/authorize?client_id=test-client";
+    let response = app.sign_in_with_mock_idp(Some(return_to)).await;
+    assert_eq!(redirect_location(&response), return_to);
+
+    let response = app
+        .sign_in_with_mock_idp(Some("https://evil.example.com"))
+        .await;
+    assert_eq!(redirect_location(&response), "/");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_upstream_email_not_verified() {
+    let mut app = TestApp::new().await;
+    app.mock_idp.set_user(&get_random_email(), false);
+
+    let response = app.sign_in_with_mock_idp(None).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(!has_auth_cookie(&response));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_callback_is_from_another_browser() {
+    let mut app = TestApp::new().await;
+    app.mock_idp.set_user(&get_random_email(), true);
+
+    let query = app.get_mock_idp_callback_query(None).await;
+
+    // A client without the state cookie stands in for the victim's browser
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/oidc/{}/callback?{}",
+            &app.address, MOCK_IDP_NAME, query
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_callback_is_replayed() {
+    let mut app = TestApp::new().await;
+    app.mock_idp.set_user(&get_random_email(), true);
+
+    let query = app.get_mock_idp_callback_query(None).await;
+
+    let response = app.get_oidc_callback(MOCK_IDP_NAME, &query).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let response = app.get_oidc_callback(MOCK_IDP_NAME, &query).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_provider() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .get_oidc_login("unknown", &Vec::<(&str, &str)>::new())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    app.clean_up().await;
+}
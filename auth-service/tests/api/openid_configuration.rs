@@ -0,0 +1,26 @@
+use crate::helpers::TestApp;
+use serde_json::Value;
+
+#[tokio::test]
+async fn should_return_200_with_provider_metadata() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    let issuer = body["issuer"].as_str().expect("No issuer in response body");
+    assert_eq!(
+        body["authorization_endpoint"],
+        format!("{issuer}/authorize")
+    );
+    assert_eq!(body["token_endpoint"], format!("{issuer}/token"));
+    assert_eq!(body["jwks_uri"], format!("{issuer}/.well-known/jwks.json"));
+    assert_eq!(body["code_challenge_methods_supported"][0], "S256");
+    assert!(body["id_token_signing_alg_values_supported"][0].is_string());
+    app.clean_up().await;
+}
@@ -0,0 +1,412 @@
+use crate::{
+    helpers::{get_random_email, has_auth_cookie, TestApp},
+    soft_authenticator::SoftAuthenticator,
+};
+use auth_service::{domain::TwoFAMethod, routes::TwoFactorAuthResponse};
+use serde_json::{json, Value};
+
+// The password every test user signs up with, as the body of a re-authentication
+fn password() -> Value {
+    json!({ "password": "Password123!" })
+}
+
+async fn register_passkey(app: &TestApp, authenticator: &SoftAuthenticator) {
+    let response = app.post_passkey_register_start(&password()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response.json::<Value>().await.unwrap();
+
+    let response = app
+        .post_passkey_register_finish(&authenticator.create(&options))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+// Start a passwordless login and answer it with the authenticator
+async fn sign_in_with_passkey(
+    app: &TestApp,
+    authenticator: &mut SoftAuthenticator,
+) -> reqwest::Response {
+    let response = app.post_passkey_login_start(&json!({})).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response.json::<Value>().await.unwrap();
+
+    app.post_passkey_login_finish(&authenticator.get(&options))
+        .await
+}
+
+// Sign in with the password of a user who requires a passkey, returning the login attempt id
+async fn login_with_passkey_2fa(app: &TestApp, email: &str) -> String {
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(json_body.two_fa_method, TwoFAMethod::Passkey);
+
+    json_body.login_attempt_id
+}
+
+#[tokio::test]
+async fn should_register_passkey_and_sign_in_without_password() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = sign_in_with_passkey(&app, &mut authenticator).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(has_auth_cookie(&response));
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn register_start_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_passkey_register_start(&password()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn register_start_should_return_401_if_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_passkey_register_start(&json!({ "password": "WrongPassword123!" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn register_should_require_passkey_once_it_is_the_second_factor() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+    let response = app.post_passkey_2fa_enable(&password()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_passkey_register_start(&password()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_passkey_reauthenticate_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response.json::<Value>().await.unwrap();
+
+    let response = app
+        .post_passkey_register_start(&json!({
+            "password": "Password123!",
+            "passkey": authenticator.get(&options),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response.json::<Value>().await.unwrap();
+
+    let response = app
+        .post_passkey_register_finish(&SoftAuthenticator::default().create(&options))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn register_should_return_409_if_passkey_already_registered() {
+    let mut app = TestApp::new().await;
+    let authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let response = app.post_passkey_register_start(&password()).await;
+    let options = response.json::<Value>().await.unwrap();
+    assert_eq!(
+        options["excludeCredentials"],
+        json!([{ "type": "public-key", "id": authenticator.credential_id() }])
+    );
+
+    let response = app
+        .post_passkey_register_finish(&authenticator.create(&options))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_should_return_401_if_challenge_is_replayed() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let response = app.post_passkey_login_start(&json!({})).await;
+    let options = response.json::<Value>().await.unwrap();
+    let assertion = authenticator.get(&options);
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_should_return_401_if_origin_is_wrong() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+    app.post_logout().await;
+
+    // A phishing site relaying the challenge cannot make the browser report our origin
+    authenticator.origin = "https://evil.example.com".to_owned();
+    let response = sign_in_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(!has_auth_cookie(&response));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_should_return_401_without_user_verification() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    authenticator.user_verified = false;
+    let response = sign_in_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_should_return_401_if_sign_count_goes_backwards() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let response = sign_in_with_passkey(&app, &mut authenticator).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // A copy of the credential carries on from an older count
+    authenticator.sign_count = 0;
+    let response = sign_in_with_passkey(&app, &mut authenticator).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_should_return_401_for_unknown_passkey() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = SoftAuthenticator::default();
+
+    let response = sign_in_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enable_2fa_should_return_409_without_passkey() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app.post_passkey_2fa_enable(&password()).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_passkey_as_2fa_after_password() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &authenticator).await;
+    let response = app.post_passkey_2fa_enable(&password()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = login_with_passkey_2fa(&app, &email).await;
+
+    let response = app
+        .post_passkey_login_start(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response.json::<Value>().await.unwrap();
+    assert_eq!(
+        options["allowCredentials"],
+        json!([{ "type": "public-key", "id": authenticator.credential_id() }])
+    );
+
+    // A security key without a PIN is enough after the password
+    authenticator.user_verified = false;
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "passkey": authenticator.get(&options),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(has_auth_cookie(&response));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_return_401_for_another_users_passkey() {
+    let mut app = TestApp::new().await;
+    let victim = get_random_email();
+    let attacker = get_random_email();
+    let victim_authenticator = SoftAuthenticator::default();
+    let mut attacker_authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&victim, false).await;
+    register_passkey(&app, &victim_authenticator).await;
+    app.post_passkey_2fa_enable(&password()).await;
+
+    app.signup_and_login(&attacker, false).await;
+    register_passkey(&app, &attacker_authenticator).await;
+
+    // The attacker knows the victim's password but holds only their own passkey
+    let login_attempt_id = login_with_passkey_2fa(&app, &victim).await;
+    let response = app
+        .post_passkey_login_start(&json!({
+            "email": victim,
+            "loginAttemptId": login_attempt_id,
+        }))
+        .await;
+    let options = response.json::<Value>().await.unwrap();
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": victim,
+            "loginAttemptId": login_attempt_id,
+            "passkey": attacker_authenticator.get(&options),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_reject_stored_code_for_passkey_user() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &SoftAuthenticator::default()).await;
+    app.post_passkey_2fa_enable(&password()).await;
+
+    let login_attempt_id = login_with_passkey_2fa(&app, &email).await;
+
+    // The code recorded with the login attempt is never sent to passkey users
+    let code = app.get_two_fa_code(&login_attempt_id).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_2fa_should_require_passkey_for_passkey_user() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let mut authenticator = SoftAuthenticator::default();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &authenticator).await;
+    app.post_passkey_2fa_enable(&password()).await;
+
+    // An emailed code does not stand in for the passkey
+    let response = app.post_2fa_email_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let two_fa_code = app
+        .last_email(&email, "Your Authentication Code")
+        .await
+        .expect("No 2FA code was emailed")
+        .two_fa_code();
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": two_fa_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Nor does an assertion for a challenge issued for something else
+    let response = app.post_passkey_login_start(&json!({})).await;
+    let options = response.json::<Value>().await.unwrap();
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "passkey": authenticator.get(&options),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_passkey_reauthenticate_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response.json::<Value>().await.unwrap();
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "passkey": authenticator.get(&options),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "Password123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
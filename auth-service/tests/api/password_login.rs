@@ -0,0 +1,144 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::routes::{PasswordLoginResponse, RecoveryCodesResponse};
+use serde_json::json;
+
+fn login_body(email: &str, password: &str) -> serde_json::Value {
+    json!({
+        "email": email,
+        "password": password,
+    })
+}
+
+fn reauth_body() -> serde_json::Value {
+    json!({ "password": "Password123!" })
+}
+
+#[tokio::test]
+async fn should_return_403_for_password_login_when_disabled() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let response = app.post_password_login_disable(&reauth_body()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<PasswordLoginResponse>()
+            .await
+            .expect("Could not deserialize response body to PasswordLoginResponse"),
+        PasswordLoginResponse {
+            password_login_disabled: true
+        }
+    );
+    app.post_logout().await;
+
+    let response = app.post_login(&login_body(&email, "Password123!")).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_wrong_password_when_disabled() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+    app.post_password_login_disable(&reauth_body()).await;
+
+    // Someone without the password cannot learn that it has been turned off
+    let response = app.post_login(&login_body(&email, "WrongPassword1!")).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_sign_in_with_magic_link_when_password_login_disabled() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+    app.post_password_login_disable(&reauth_body()).await;
+    app.post_logout().await;
+
+    let response = app.post_magic_link(&json!({ "email": email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let link = app
+        .last_email(&email, "Your Sign In Link")
+        .await
+        .expect("No magic link email was sent")
+        .content;
+
+    let response = app.post_magic_link_callback(&link).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_password_again_when_enabled() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+    app.post_password_login_disable(&reauth_body()).await;
+
+    let response = app.post_password_login_enable(&reauth_body()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body(&email, "Password123!")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_password_login_disable(&reauth_body()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_and_keep_password_login_if_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    // A stolen session alone cannot change how the user signs in
+    let response = app
+        .post_password_login_disable(&json!({ "password": "WrongPassword1!" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&login_body(&email, "Password123!")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_second_factor_when_2fa_is_on() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let response = app.post_2fa_enable(&reauth_body()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    let response = app.post_password_login_disable(&reauth_body()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_password_login_disable(&json!({
+            "password": "Password123!",
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
@@ -0,0 +1,210 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::{Email, PasswordResetToken},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use serde_json::json;
+
+#[tokio::test]
+async fn should_return_200_and_store_token_if_user_exists() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_password_reset_request(&json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app
+        .password_reset_token_store
+        .read()
+        .await
+        .get_token(&random_email)
+        .await
+        .expect("No password reset token was stored");
+
+    // The token is emailed in both the plain text and HTML bodies
+    let email = app
+        .last_email(random_email.as_ref(), "Your Password Reset Token")
+        .await
+        .expect("No password reset email was sent");
+    assert!(email.content.contains(token.as_ref()));
+    assert!(email.html.is_some_and(|html| html.contains(token.as_ref())));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_without_token_if_user_does_not_exist() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let response = app
+        .post_password_reset_request(&json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(app
+        .password_reset_token_store
+        .read()
+        .await
+        .get_token(&random_email)
+        .await
+        .is_err());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_password_reset_request(&json!({ "email": "not_an_email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_password_reset_request(&json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app
+        .post_password_reset_confirm(&json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reset_password_and_revoke_existing_tokens() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    let old_token = auth_cookie.value().to_owned();
+
+    let response = app
+        .post_password_reset_request(&json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app
+        .password_reset_token_store
+        .read()
+        .await
+        .get_token(&random_email)
+        .await
+        .unwrap();
+
+    let confirm_body = json!({
+        "email": random_email,
+        "token": token,
+        "newPassword": "NewPassword123!",
+    });
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The token is single-use
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Tokens issued before the reset are no longer valid
+    let response = app.post_verify_token(&json!({ "token": old_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_password_reset_request(&json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let confirm_body = json!({
+        "email": random_email,
+        "token": PasswordResetToken::default(),
+        "newPassword": "NewPassword123!",
+    });
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_new_password() {
+    let mut app = TestApp::new().await;
+
+    let confirm_body = json!({
+        "email": get_random_email(),
+        "token": PasswordResetToken::default(),
+        "newPassword": "weak",
+    });
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
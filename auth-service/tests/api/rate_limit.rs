@@ -0,0 +1,111 @@
+use crate::helpers::{get_random_email, TestApp, TEST_CLIENT_ID};
+use auth_service::ErrorResponse;
+use reqwest::header::RETRY_AFTER;
+use serde_json::json;
+
+// Default limits on the routes exercised below
+const MAGIC_LINK_EMAIL_LIMIT: usize = 5;
+const SIGNUP_IP_LIMIT: usize = 10;
+const TOKEN_CLIENT_LIMIT: usize = 120;
+
+async fn assert_rate_limited(response: reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many requests".to_owned()
+    );
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_requests_for_an_email() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    for _ in 0..MAGIC_LINK_EMAIL_LIMIT {
+        let response = app.post_magic_link(&json!({ "email": email })).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app
+        .post_magic_link(&json!({ "email": email.to_uppercase() }))
+        .await;
+    assert_rate_limited(response).await;
+
+    // Other emails have their own limit
+    let response = app
+        .post_magic_link(&json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_requests_from_an_ip() {
+    let mut app = TestApp::new().await;
+
+    // Rejected requests count too, and are quick enough that no tokens refill meanwhile
+    let signup_body = json!({
+        "email": "not-an-email",
+        "password": "Password123!",
+        "requires2FA": false
+    });
+    for _ in 0..SIGNUP_IP_LIMIT {
+        let response = app.post_signup(&signup_body).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    let response = app.post_signup(&signup_body).await;
+    assert_rate_limited(response).await;
+
+    // Routes without a limit of their own are only held to the overall one
+    let response = app.get_root().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_count_authenticated_requests_against_a_client() {
+    let mut app = TestApp::new().await;
+
+    // Anyone can send the client's id, but without its secret they do not use up its limit
+    for _ in 0..TOKEN_CLIENT_LIMIT {
+        let response = app
+            .http_client
+            .post(format!("{}/token", &app.address))
+            .basic_auth(TEST_CLIENT_ID, Some("wrong-secret-0123456789abcdefghijkl"))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    for _ in 0..TOKEN_CLIENT_LIMIT {
+        let response = app
+            .post_token(&[("grant_type", "client_credentials")])
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app
+        .post_token(&[("grant_type", "client_credentials")])
+        .await;
+    assert_rate_limited(response).await;
+
+    app.clean_up().await;
+}
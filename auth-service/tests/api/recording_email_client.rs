@@ -0,0 +1,85 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use auth_service::domain::{Email, EmailClient, EmailMessage};
+
+// Keeps every email the app sends so tests can follow the links inside them
+#[derive(Default)]
+pub struct RecordingEmailClient {
+    sent: Mutex<Vec<SentEmail>>,
+    // Set to make every send fail, as if the mail provider were down
+    pub failing: AtomicBool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub content: String,
+    // Only set for templated emails, which also have a plain text body in content
+    pub html: Option<String>,
+}
+
+impl SentEmail {
+    // The 6 digit code in a 2FA code email
+    pub fn two_fa_code(&self) -> String {
+        self.content
+            .split_whitespace()
+            .find(|word| word.len() == 6 && word.chars().all(|c| c.is_ascii_digit()))
+            .expect("No 2FA code in email")
+            .to_owned()
+    }
+}
+
+impl RecordingEmailClient {
+    // The most recent email sent to the recipient with the given subject
+    pub fn last_email(&self, recipient: &str, subject: &str) -> Option<SentEmail> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|email| email.recipient == recipient && email.subject == subject)
+            .cloned()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for RecordingEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), String> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err("Mail provider unavailable".to_owned());
+        }
+
+        self.sent.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+            html: None,
+        });
+
+        Ok(())
+    }
+
+    async fn send_message(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err("Mail provider unavailable".to_owned());
+        }
+
+        self.sent.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().to_owned(),
+            subject: message.subject.clone(),
+            content: message.text.clone(),
+            html: message.html.clone(),
+        });
+
+        Ok(())
+    }
+}
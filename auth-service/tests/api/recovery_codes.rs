@@ -0,0 +1,275 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::Email,
+    routes::{RecoveryCodesResponse, SignupResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use serde_json::json;
+
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let signup_response = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+
+    app.verify_email(email).await;
+
+    signup_response.recovery_codes
+}
+
+// Log in with the password and return the login attempt id for the second step
+async fn start_login(app: &TestApp, email: &str) -> String {
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+async fn login_with_recovery_code(
+    app: &TestApp,
+    email: &str,
+    recovery_code: &str,
+) -> reqwest::Response {
+    let login_attempt_id = start_login(app, email).await;
+
+    let verify_2fa_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": recovery_code
+    });
+
+    app.post_verify_2fa(&verify_2fa_body).await
+}
+
+#[tokio::test]
+async fn signup_with_2fa_should_return_recovery_codes() {
+    let mut app = TestApp::new().await;
+
+    let recovery_codes = signup_with_2fa(&app, &get_random_email()).await;
+
+    assert_eq!(recovery_codes.len(), 10);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_accept_recovery_code_once() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_recovery_code(&app, &random_email, &recovery_codes[0]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let response = login_with_recovery_code(&app, &random_email, &recovery_codes[0]).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_return_401_if_recovery_code_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_recovery_code(&app, &random_email, "aaaaa-aaaaa").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_end_login_attempt_if_recovery_code_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "aaaaa-aaaaa"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": recovery_codes[0]
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The code was not burned by the attempt that could not use it
+    let response = login_with_recovery_code(&app, &random_email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn regenerate_should_return_new_codes_and_invalidate_old_ones() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let old_codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_recovery_code(&app, &random_email, &old_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_recovery_codes(&json!({
+            "password": "Password123!",
+            "2FACode": old_codes[2],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(new_codes.len(), 10);
+
+    let response = login_with_recovery_code(&app, &random_email, &old_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login_with_recovery_code(&app, &random_email, &new_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn regenerate_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_recovery_codes(&json!({ "password": "Password123!" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn regenerate_should_return_401_if_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_recovery_code(&app, &random_email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_recovery_codes(&json!({
+            "password": "WrongPassword123!",
+            "2FACode": recovery_codes[1],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn regenerate_should_return_401_without_second_factor() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_recovery_code(&app, &random_email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // A stolen session and password are not enough to mint codes that bypass 2FA
+    let response = app
+        .post_recovery_codes(&json!({ "password": "Password123!" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_recovery_codes(&json!({
+            "password": "Password123!",
+            "2FACode": "aaaaa-aaaaa",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn regenerate_should_return_409_if_2fa_not_enabled() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_recovery_codes(&json!({ "password": "Password123!" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "2FA not enabled".to_owned()
+    );
+    app.clean_up().await;
+}
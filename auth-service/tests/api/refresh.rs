@@ -0,0 +1,138 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+use reqwest::Url;
+use serde_json::json;
+
+fn refresh_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_TOKEN_COOKIE_NAME)
+        .expect("No refresh token cookie found")
+        .value()
+        .to_owned()
+}
+
+// Put a previously issued refresh token back into the client's cookie jar
+fn set_refresh_token(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_TOKEN_COOKIE_NAME, token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let mut app = TestApp::new().await;
+
+    let old_token = refresh_token(&app.signup_and_login(get_random_email(), false).await);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+    assert_ne!(refresh_token(&response), old_token);
+
+    let response = app
+        .post_verify_token(&json!({ "token": auth_cookie.value() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_refresh_token_invalid() {
+    let mut app = TestApp::new().await;
+
+    set_refresh_token(&app, "invalid");
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn reusing_rotated_token_should_revoke_family() {
+    let mut app = TestApp::new().await;
+
+    let old_token = refresh_token(&app.signup_and_login(get_random_email(), false).await);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_token = refresh_token(&response);
+
+    set_refresh_token(&app, &old_token);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The legitimate holder of the newest token is signed out as well
+    set_refresh_token(&app, &new_token);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn logout_should_revoke_refresh_token() {
+    let mut app = TestApp::new().await;
+
+    let token = refresh_token(&app.signup_and_login(get_random_email(), false).await);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_token(&app, &token);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_issued_before_tokens_revoked() {
+    let mut app = TestApp::new().await;
+
+    let old_token = refresh_token(&app.signup_and_login(get_random_email(), false).await);
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "Password123!",
+            "newPassword": "NewPassword123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_token = refresh_token(&response);
+
+    set_refresh_token(&app, &old_token);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    set_refresh_token(&app, &new_token);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
@@ -0,0 +1,167 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::{Email, LoginAttemptId, TwoFACode},
+    routes::TwoFactorAuthResponse,
+    utils::auth::MAX_TWO_FA_RESENDS,
+    ErrorResponse,
+};
+use reqwest::header::RETRY_AFTER;
+use serde_json::json;
+
+const TWO_FA_SUBJECT: &str = "Your Login Authentication Code";
+
+async fn assert_error(response: reqwest::Response, status: u16, error: &str) {
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        error.to_owned()
+    );
+}
+
+#[tokio::test]
+async fn should_email_a_new_code_for_the_login_attempt() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    let response = app.signup_and_login(&email, true).await;
+    let (login_attempt_id, old_code) = app.get_login_attempt(response).await;
+
+    let resend_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+    });
+    let response = app.post_resend_2fa(&resend_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(json_body.login_attempt_id, login_attempt_id.to_string());
+
+    let new_code = app
+        .last_email(email.as_ref(), TWO_FA_SUBJECT)
+        .await
+        .expect("No 2FA email was sent")
+        .two_fa_code();
+    let stored_code = app.get_two_fa_code(&login_attempt_id).await;
+    assert_eq!(stored_code.as_ref(), new_code);
+
+    // Only the latest code works
+    if old_code.as_ref() != new_code {
+        let verify_2fa_body = json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": old_code
+        });
+        let response = app.post_verify_2fa(&verify_2fa_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let verify_2fa_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": new_code
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_resent_again_too_soon() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    let response = app.signup_and_login(&email, true).await;
+    let (login_attempt_id, _) = app.get_login_attempt(response).await;
+
+    let resend_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+    });
+    let response = app.post_resend_2fa(&resend_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_resend_2fa(&resend_body).await;
+    assert!(response.headers().get(RETRY_AFTER).is_some());
+    assert_error(response, 429, "Too many requests").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_after_too_many_resends() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    let response = app.signup_and_login(&email, true).await;
+    let (login_attempt_id, _) = app.get_login_attempt(response).await;
+
+    // Resends made long enough ago that the cooldown has passed
+    for _ in 0..MAX_TWO_FA_RESENDS {
+        app.two_fa_code_store
+            .write()
+            .await
+            .resend_code(&login_attempt_id, TwoFACode::default(), 0)
+            .await
+            .unwrap();
+    }
+
+    let resend_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+    });
+    let response = app.post_resend_2fa(&resend_body).await;
+    assert_error(
+        response,
+        403,
+        "Too many 2FA codes sent, please log in again",
+    )
+    .await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_login_attempt_is_unknown() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&email, true).await;
+
+    let resend_body = json!({
+        "email": email,
+        "loginAttemptId": LoginAttemptId::default(),
+    });
+    let response = app.post_resend_2fa(&resend_body).await;
+    assert_error(response, 401, "Incorrect credentials").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    let resend_body = json!({
+        "email": "not-an-email",
+        "loginAttemptId": LoginAttemptId::default(),
+    });
+    let response = app.post_resend_2fa(&resend_body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_resend_2fa(&json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
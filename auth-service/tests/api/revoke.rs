@@ -0,0 +1,155 @@
+use crate::helpers::{
+    get_random_email, session_tokens, TestApp, OTHER_CLIENT_ID, OTHER_CLIENT_SECRET,
+    TEST_CLIENT_ID, TEST_CODE_VERIFIER, TEST_REDIRECT_URI,
+};
+use serde_json::{json, Value};
+
+// Run the authorization code flow for the signed in user and return the access token
+async fn access_token(app: &TestApp) -> String {
+    let code = app.get_authorization_code("openid").await;
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", TEST_REDIRECT_URI),
+            ("code_verifier", TEST_CODE_VERIFIER),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    body["access_token"]
+        .as_str()
+        .expect("No access token in response body")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_return_200_and_ban_access_token() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let token = access_token(&app).await;
+
+    let response = app
+        .post_revoke(&[
+            ("token", token.as_str()),
+            ("token_type_hint", "access_token"),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let contains_token = app
+        .banned_token_store
+        .read()
+        .await
+        .contains_token(&token)
+        .await
+        .expect("Failed to check if token is banned");
+    assert!(contains_token);
+
+    let response = app.get_userinfo(&token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_tokens_of_other_clients() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let token = access_token(&app).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/revoke", &app.address))
+        .basic_auth(OTHER_CLIENT_ID, Some(OTHER_CLIENT_SECRET))
+        .form(&[("token", token.as_str())])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_userinfo(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_family() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let (_, refresh_token) = session_tokens(&app.signup_and_login(&email, false).await);
+
+    let response = app
+        .post_revoke(&[
+            ("token", refresh_token.as_str()),
+            ("token_type_hint", "refresh_token"),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_browser_session_jwts() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let (token, _) = session_tokens(&app.signup_and_login(&email, false).await);
+
+    let response = app.post_revoke(&[("token", token.as_str())]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_for_unknown_tokens() {
+    let mut app = TestApp::new().await;
+
+    let unknown_refresh_token = "a".repeat(32);
+    for token in ["", "invalid", "a.b.c", &unknown_refresh_token] {
+        let response = app.post_revoke(&[("token", token)]).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unauthenticated_client() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let (token, _) = session_tokens(&app.signup_and_login(&email, false).await);
+
+    let response = app
+        .http_client
+        .post(format!("{}/revoke", &app.address))
+        .form(&[
+            ("token", token.as_str()),
+            ("client_id", TEST_CLIENT_ID),
+            ("client_secret", "wrong-secret-0123456789abcdefghijkl"),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
@@ -0,0 +1,172 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use serde_json::{json, Value};
+
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+}
+
+// Log in and return the JWT issued for the new session
+async fn login(app: &TestApp, email: &str) -> String {
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+async fn get_sessions(app: &TestApp) -> Vec<Value> {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    body["sessions"]
+        .as_array()
+        .expect("No sessions in response body")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_return_200_with_current_session() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    signup(&app, &email).await;
+    login(&app, &email).await;
+
+    let sessions = get_sessions(&app).await;
+
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["current"], true);
+    assert_eq!(sessions[0]["ipAddress"], "127.0.0.1");
+    assert!(sessions[0]["id"].is_string());
+    assert!(sessions[0]["createdAt"].is_u64());
+    assert!(sessions[0]["lastSeen"].is_u64());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn revoking_session_should_invalidate_its_token() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    signup(&app, &email).await;
+    let old_token = login(&app, &email).await;
+    let new_token = login(&app, &email).await;
+
+    let sessions = get_sessions(&app).await;
+    assert_eq!(sessions.len(), 2);
+
+    let other = sessions
+        .iter()
+        .find(|session| session["current"] == false)
+        .expect("No other session found");
+
+    let response = app
+        .delete_session(other["id"].as_str().expect("Session id is not a string"))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_token(&json!({ "token": old_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_token(&json!({ "token": new_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(get_sessions(&app).await.len(), 1);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn revoking_current_session_should_sign_out() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    signup(&app, &email).await;
+    login(&app, &email).await;
+
+    let sessions = get_sessions(&app).await;
+    let id = sessions[0]["id"]
+        .as_str()
+        .expect("Session id is not a string");
+
+    let response = app.delete_session(id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_session_not_found() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    signup(&app, &email).await;
+    login(&app, &email).await;
+
+    for id in ["not-a-uuid", "2f4c8a2e-5d3b-4a8e-9c1f-7b6d5e4a3c2b"] {
+        let response = app.delete_session(id).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_another_users_session() {
+    let mut app = TestApp::new().await;
+    let first_email = get_random_email();
+    let second_email = get_random_email();
+
+    signup(&app, &first_email).await;
+    signup(&app, &second_email).await;
+
+    login(&app, &first_email).await;
+    let sessions = get_sessions(&app).await;
+    let id = sessions[0]["id"]
+        .as_str()
+        .expect("Session id is not a string")
+        .to_owned();
+
+    login(&app, &second_email).await;
+
+    let response = app.delete_session(&id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    app.clean_up().await;
+}
@@ -50,6 +50,7 @@ async fn should_return_201_if_valid_input() {
 
     let expected_response = SignupResponse {
         message: "User created successfully!".to_owned(),
+        recovery_codes: vec![],
     };
 
     // Assert that we are getting the correct response body!
@@ -70,7 +71,7 @@ async fn should_return_400_if_invalid_input() {
     // - The email is empty or does not contain '@'
     // - The password is less than 8 characters
     let mut app = TestApp::new().await;
-    let invalid_signups = vec![
+    let invalid_signups = [
         // No '@' in email
         json!({
             "email": "mreynolds_serenity.co",
@@ -0,0 +1,185 @@
+use auth_service::{
+    domain::{Email, EmailClient, PasswordResetToken, TwoFACode},
+    services::{SmtpConfig, SmtpEmailClient, SmtpTls},
+    utils::email_templates::{password_reset_email, two_fa_code_email, verification_email},
+};
+
+use crate::smtp_sink::{ReceivedEmail, SmtpSink};
+
+const FROM: &str = "Auth Service <no-reply@example.com>";
+
+fn smtp_client(sink: &SmtpSink, credentials: Option<(&str, &str)>) -> SmtpEmailClient {
+    SmtpEmailClient::new(SmtpConfig {
+        host: "127.0.0.1".to_owned(),
+        port: Some(sink.port),
+        tls: SmtpTls::None,
+        credentials: credentials
+            .map(|(username, password)| (username.to_owned(), password.to_owned())),
+        from: FROM.to_owned(),
+    })
+    .expect("Failed to create SMTP email client")
+}
+
+fn recipient() -> Email {
+    Email::parse("user@example.com").unwrap()
+}
+
+fn only_email(sink: &SmtpSink) -> ReceivedEmail {
+    let mut received = sink.received();
+    assert_eq!(received.len(), 1);
+    received.remove(0)
+}
+
+// Both bodies of a multipart/alternative email, plain text first
+fn alternative_bodies(email: &ReceivedEmail) -> (String, String) {
+    assert!(email.data.contains("Content-Type: multipart/alternative"));
+
+    (
+        email
+            .body("text/plain; charset=utf-8")
+            .expect("No plain text body"),
+        email
+            .body("text/html; charset=utf-8")
+            .expect("No HTML body"),
+    )
+}
+
+#[tokio::test]
+async fn should_send_2fa_code_as_plain_text_and_html() {
+    let sink = SmtpSink::start().await;
+    let client = smtp_client(&sink, None);
+    let code = TwoFACode::parse("123456").unwrap();
+    let message = two_fa_code_email("Your Login Authentication Code", &code).unwrap();
+
+    client
+        .send_message(&recipient(), &message)
+        .await
+        .expect("Failed to send email");
+
+    let email = only_email(&sink);
+    assert_eq!(email.mail_from, "no-reply@example.com");
+    assert_eq!(email.rcpt_to, vec!["user@example.com".to_owned()]);
+    assert!(email
+        .data
+        .contains("From: \"Auth Service\" <no-reply@example.com>"));
+    assert!(email.data.contains("To: user@example.com"));
+    assert!(email
+        .data
+        .contains("Subject: Your Login Authentication Code"));
+    let (text, html) = alternative_bodies(&email);
+    assert!(text.starts_with("Your authentication code is 123456"));
+    assert!(html.contains(">123456</p>"));
+}
+
+#[tokio::test]
+async fn should_send_verification_link_as_plain_text_and_html() {
+    let sink = SmtpSink::start().await;
+    let client = smtp_client(&sink, None);
+    let link = "http://localhost:3000/verify-email?email=user%40example.com&token=abc";
+
+    client
+        .send_message(&recipient(), &verification_email(link).unwrap())
+        .await
+        .expect("Failed to send email");
+
+    let email = only_email(&sink);
+    assert!(email.data.contains("Subject: Verify Your Email Address"));
+    let (text, html) = alternative_bodies(&email);
+    assert!(text.contains(link));
+    assert!(html.contains(
+        "href=\"http://localhost:3000/verify-email?email=user%40example.com&amp;token=abc\""
+    ));
+}
+
+#[tokio::test]
+async fn should_send_password_reset_token_as_plain_text_and_html() {
+    let sink = SmtpSink::start().await;
+    let client = smtp_client(&sink, None);
+    let token = PasswordResetToken::default();
+
+    client
+        .send_message(&recipient(), &password_reset_email(&token).unwrap())
+        .await
+        .expect("Failed to send email");
+
+    let email = only_email(&sink);
+    assert!(email.data.contains("Subject: Your Password Reset Token"));
+    let (text, html) = alternative_bodies(&email);
+    assert!(text.contains(token.as_ref()));
+    assert!(html.contains(token.as_ref()));
+}
+
+#[tokio::test]
+async fn should_send_other_emails_as_plain_text() {
+    let sink = SmtpSink::start().await;
+    let client = smtp_client(&sink, None);
+
+    client
+        .send_email(
+            &recipient(),
+            "Your Account Has Been Locked",
+            "Your account has been locked.",
+        )
+        .await
+        .expect("Failed to send email");
+
+    let email = only_email(&sink);
+    assert!(email.data.contains("Subject: Your Account Has Been Locked"));
+    assert!(!email.data.contains("multipart"));
+    assert_eq!(
+        email.body("text/plain; charset=utf-8").as_deref(),
+        Some("Your account has been locked.")
+    );
+}
+
+#[tokio::test]
+async fn should_authenticate_with_configured_credentials() {
+    let sink = SmtpSink::start().await;
+    let client = smtp_client(&sink, Some(("mailer", "hunter2")));
+
+    client
+        .send_email(&recipient(), "Test", "Test")
+        .await
+        .expect("Failed to send email");
+
+    assert_eq!(
+        only_email(&sink).credentials,
+        Some(("mailer".to_owned(), "hunter2".to_owned()))
+    );
+}
+
+#[tokio::test]
+async fn should_not_authenticate_without_credentials() {
+    let sink = SmtpSink::start().await;
+    let client = smtp_client(&sink, None);
+
+    client
+        .send_email(&recipient(), "Test", "Test")
+        .await
+        .expect("Failed to send email");
+
+    assert_eq!(only_email(&sink).credentials, None);
+}
+
+#[tokio::test]
+async fn should_fail_when_server_is_unreachable() {
+    // Nothing listens on a port once the listener that was given it is dropped
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let client = SmtpEmailClient::new(SmtpConfig {
+        host: "127.0.0.1".to_owned(),
+        port: Some(port),
+        tls: SmtpTls::None,
+        credentials: None,
+        from: FROM.to_owned(),
+    })
+    .expect("Failed to create SMTP email client");
+
+    assert!(client
+        .send_email(&recipient(), "Test", "Test")
+        .await
+        .is_err());
+}
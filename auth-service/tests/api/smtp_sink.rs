@@ -0,0 +1,169 @@
+use std::sync::{Arc, Mutex};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+// A local SMTP server that accepts every email and keeps it for the test to inspect.
+// It only speaks as much of the protocol as the SMTP email client uses, without TLS.
+#[derive(Clone)]
+pub struct SmtpSink {
+    pub port: u16,
+    received: Arc<Mutex<Vec<ReceivedEmail>>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReceivedEmail {
+    // The username and password the client authenticated with, if it did
+    pub credentials: Option<(String, String)>,
+    pub mail_from: String,
+    pub rcpt_to: Vec<String>,
+    // The message as sent after DATA, with the dot stuffing undone
+    pub data: String,
+}
+
+impl ReceivedEmail {
+    // The decoded body of the first part with the given content type, such as text/html
+    pub fn body(&self, content_type: &str) -> Option<String> {
+        let start = self.data.find(&format!("Content-Type: {content_type}"))?;
+        let part = &self.data[start..];
+        let (headers, body) = part.split_once("\r\n\r\n")?;
+        let body = body.split("\r\n--").next().unwrap_or_default();
+
+        if headers.contains("Content-Transfer-Encoding: quoted-printable") {
+            decode_quoted_printable(body)
+        } else if headers.contains("Content-Transfer-Encoding: base64") {
+            String::from_utf8(BASE64.decode(body.replace("\r\n", "")).ok()?).ok()
+        } else {
+            Some(body.to_owned())
+        }
+    }
+}
+
+impl SmtpSink {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind SMTP sink");
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let sink_received = received.clone();
+        #[allow(clippy::let_underscore_future)]
+        let _ = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, sink_received.clone()));
+            }
+        });
+
+        Self { port, received }
+    }
+
+    pub fn received(&self) -> Vec<ReceivedEmail> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+async fn handle_connection(stream: TcpStream, received: Arc<Mutex<Vec<ReceivedEmail>>>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut email = ReceivedEmail::default();
+
+    if writer
+        .write_all(b"220 localhost SMTP sink\r\n")
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let command = line.to_ascii_uppercase();
+        let reply: &[u8] = if command.starts_with("EHLO") {
+            b"250-localhost\r\n250-AUTH PLAIN\r\n250 8BITMIME\r\n"
+        } else if command.starts_with("HELO") {
+            b"250 localhost\r\n"
+        } else if command.starts_with("AUTH PLAIN ") {
+            email.credentials = decode_auth_plain(&line["AUTH PLAIN ".len()..]);
+            b"235 2.7.0 Authentication successful\r\n"
+        } else if command.starts_with("MAIL FROM:") {
+            email.mail_from = address(&line["MAIL FROM:".len()..]);
+            b"250 OK\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            email.rcpt_to.push(address(&line["RCPT TO:".len()..]));
+            b"250 OK\r\n"
+        } else if command == "DATA" {
+            if writer
+                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                .await
+                .is_err()
+            {
+                return;
+            }
+
+            let mut data = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line == "." {
+                    break;
+                }
+                data.push(line.strip_prefix('.').unwrap_or(&line).to_owned());
+            }
+            email.data = data.join("\r\n");
+            received.lock().unwrap().push(std::mem::take(&mut email));
+            b"250 OK\r\n"
+        } else if command == "RSET" {
+            email = ReceivedEmail::default();
+            b"250 OK\r\n"
+        } else if command == "NOOP" {
+            b"250 OK\r\n"
+        } else if command == "QUIT" {
+            let _ = writer.write_all(b"221 Bye\r\n").await;
+            return;
+        } else {
+            b"502 Command not implemented\r\n"
+        };
+
+        if writer.write_all(reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+// The address inside the angle brackets of MAIL FROM and RCPT TO, ignoring any parameters
+fn address(argument: &str) -> String {
+    argument
+        .trim()
+        .trim_start_matches('<')
+        .split('>')
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+// AUTH PLAIN sends base64 of an authorization identity, username and password,
+// separated by NUL bytes
+fn decode_auth_plain(response: &str) -> Option<(String, String)> {
+    let decoded = String::from_utf8(BASE64.decode(response.trim()).ok()?).ok()?;
+    let mut fields = decoded.split('\0').skip(1);
+
+    Some((fields.next()?.to_owned(), fields.next()?.to_owned()))
+}
+
+fn decode_quoted_printable(body: &str) -> Option<String> {
+    let joined = body.replace("=\r\n", "");
+    let mut bytes = joined.bytes();
+    let mut decoded = Vec::new();
+
+    while let Some(byte) = bytes.next() {
+        if byte == b'=' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
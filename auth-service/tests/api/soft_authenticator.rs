@@ -0,0 +1,139 @@
+use auth_service::{domain::RelyingParty, utils::constants::AUTH_SERVICE_URL};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use ciborium::Value as CborValue;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::RngCore;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// Stands in for the browser and a platform authenticator holding a single passkey,
+// answering the options the service sends with the JSON a browser would return
+pub struct SoftAuthenticator {
+    // The origin the browser reports, which a phishing site could not fake
+    pub origin: String,
+    // Whether the authenticator checked the user's PIN or biometrics
+    pub user_verified: bool,
+    pub sign_count: u32,
+    credential_id: Vec<u8>,
+    signing_key: SigningKey,
+}
+
+impl Default for SoftAuthenticator {
+    fn default() -> Self {
+        let mut credential_id = vec![0u8; 16];
+        rand::thread_rng().fill_bytes(&mut credential_id);
+
+        Self {
+            origin: RelyingParty::parse(&AUTH_SERVICE_URL).unwrap().origin,
+            user_verified: true,
+            sign_count: 0,
+            credential_id,
+            signing_key: SigningKey::random(&mut rand::rngs::OsRng),
+        }
+    }
+}
+
+impl SoftAuthenticator {
+    pub fn credential_id(&self) -> String {
+        BASE64_URL.encode(&self.credential_id)
+    }
+
+    // Answer the options from /passkeys/register/start
+    pub fn create(&self, options: &Value) -> Value {
+        let rp_id = options["rp"]["id"].as_str().expect("No relying party id");
+        let point = self.signing_key.verifying_key().to_encoded_point(false);
+        let cose_key = CborValue::Map(vec![
+            (CborValue::from(1), CborValue::from(2)),
+            (CborValue::from(3), CborValue::from(-7)),
+            (CborValue::from(-1), CborValue::from(1)),
+            (
+                CborValue::from(-2),
+                CborValue::from(point.x().unwrap().to_vec()),
+            ),
+            (
+                CborValue::from(-3),
+                CborValue::from(point.y().unwrap().to_vec()),
+            ),
+        ]);
+
+        let mut authenticator_data = self.authenticator_data(rp_id, FLAG_ATTESTED_CREDENTIAL_DATA);
+        authenticator_data.extend_from_slice(&[0u8; 16]);
+        authenticator_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        authenticator_data.extend_from_slice(&self.credential_id);
+        ciborium::ser::into_writer(&cose_key, &mut authenticator_data).unwrap();
+
+        let attestation_object = CborValue::Map(vec![
+            (CborValue::from("fmt"), CborValue::from("none")),
+            (CborValue::from("attStmt"), CborValue::Map(Vec::new())),
+            (
+                CborValue::from("authData"),
+                CborValue::from(authenticator_data),
+            ),
+        ]);
+        let mut attestation_bytes = Vec::new();
+        ciborium::ser::into_writer(&attestation_object, &mut attestation_bytes).unwrap();
+
+        json!({
+            "id": self.credential_id(),
+            "rawId": self.credential_id(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": self.client_data("webauthn.create", options),
+                "attestationObject": BASE64_URL.encode(attestation_bytes),
+            }
+        })
+    }
+
+    // Answer the options from /passkeys/login/start, counting the signature
+    pub fn get(&mut self, options: &Value) -> Value {
+        let rp_id = options["rpId"].as_str().expect("No relying party id");
+        self.sign_count += 1;
+
+        let client_data_json = self.client_data("webauthn.get", options);
+        let authenticator_data = self.authenticator_data(rp_id, 0);
+
+        let mut signed_data = authenticator_data.clone();
+        signed_data.extend_from_slice(&Sha256::digest(
+            BASE64_URL.decode(&client_data_json).unwrap(),
+        ));
+        let signature: Signature = self.signing_key.sign(&signed_data);
+
+        json!({
+            "id": self.credential_id(),
+            "rawId": self.credential_id(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": client_data_json,
+                "authenticatorData": BASE64_URL.encode(authenticator_data),
+                "signature": BASE64_URL.encode(signature.to_der()),
+            }
+        })
+    }
+
+    fn client_data(&self, ceremony_type: &str, options: &Value) -> String {
+        let client_data = json!({
+            "type": ceremony_type,
+            "challenge": options["challenge"],
+            "origin": self.origin,
+            "crossOrigin": false,
+        });
+
+        BASE64_URL.encode(client_data.to_string())
+    }
+
+    fn authenticator_data(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+        let flags = match self.user_verified {
+            true => flags | FLAG_USER_PRESENT | FLAG_USER_VERIFIED,
+            false => flags | FLAG_USER_PRESENT,
+        };
+
+        let mut authenticator_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        authenticator_data.push(flags);
+        authenticator_data.extend_from_slice(&self.sign_count.to_be_bytes());
+        authenticator_data
+    }
+}
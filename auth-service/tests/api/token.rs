@@ -0,0 +1,281 @@
+use crate::helpers::{
+    get_random_email, TestApp, TEST_CLIENT_ID, TEST_CLIENT_SCOPE, TEST_CODE_VERIFIER,
+    TEST_REDIRECT_URI,
+};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use reqwest::Url;
+use serde_json::{json, Value};
+
+async fn sign_in(app: &TestApp, email: &str) {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+fn token_request<'a>(code: &'a str, code_verifier: &'a str) -> Vec<(&'static str, &'a str)> {
+    vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", TEST_REDIRECT_URI),
+        ("code_verifier", code_verifier),
+    ]
+}
+
+// The claims of a JWT, read without checking its signature
+fn jwt_claims(token: &str) -> Value {
+    let payload = token.split('.').nth(1).expect("Token is not a JWT");
+    let payload = BASE64_URL.decode(payload).expect("Payload is not base64");
+    serde_json::from_slice(&payload).expect("Payload is not JSON")
+}
+
+#[tokio::test]
+async fn should_exchange_code_for_tokens() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+    let code = app.get_authorization_code("openid email").await;
+
+    let response = app
+        .post_token(&token_request(&code, TEST_CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get(reqwest::header::CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok()),
+        Some("no-store")
+    );
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["token_type"], "Bearer");
+    assert_eq!(body["scope"], "openid email");
+    assert!(body["expires_in"].is_u64());
+
+    let access_token = body["access_token"]
+        .as_str()
+        .expect("No access token in response body");
+    let claims = jwt_claims(access_token);
+    assert_eq!(claims["client_id"], TEST_CLIENT_ID);
+    let response = app.get_userinfo(access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let id_token = jwt_claims(body["id_token"].as_str().expect("No ID token"));
+    assert_eq!(id_token["sub"], email);
+    assert_eq!(id_token["aud"], TEST_CLIENT_ID);
+    assert_eq!(id_token["nonce"], "nonce");
+    assert_eq!(id_token["email"], email);
+    assert_eq!(id_token["email_verified"], true);
+    assert!(id_token["iss"].is_string());
+    assert!(id_token["auth_time"].is_u64());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_accept_access_token_as_session_token() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+    let code = app.get_authorization_code("openid").await;
+
+    let response = app
+        .post_token(&token_request(&code, TEST_CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    let access_token = body["access_token"].as_str().expect("No access token");
+
+    let response = app
+        .post_verify_token(&json!({ "token": access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Nor as the auth cookie of a browser session
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME, access_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_when_code_is_reused() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+    let code = app.get_authorization_code("openid").await;
+
+    let response = app
+        .post_token(&token_request(&code, TEST_CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_token(&token_request(&code, TEST_CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["error"], "invalid_grant");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_wrong_code_verifier() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+    let code = app.get_authorization_code("openid").await;
+
+    let wrong_verifier = "a".repeat(43);
+    let response = app.post_token(&token_request(&code, &wrong_verifier)).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // A failed attempt uses up the code
+    let response = app
+        .post_token(&token_request(&code, TEST_CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_after_user_signs_out() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+    let code = app.get_authorization_code("openid").await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_token(&token_request(&code, TEST_CODE_VERIFIER))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_issue_client_token_for_client_credentials() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_token(&[("grant_type", "client_credentials")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["token_type"], "Bearer");
+    assert_eq!(body["scope"], TEST_CLIENT_SCOPE);
+    assert!(body["id_token"].is_null());
+
+    let access_token = body["access_token"].as_str().expect("No access token");
+    let claims = jwt_claims(access_token);
+    assert_eq!(claims["sub"], TEST_CLIENT_ID);
+    assert_eq!(claims["client_id"], TEST_CLIENT_ID);
+    assert!(claims["sid"].is_null());
+
+    // Machine tokens are not accepted where a user is expected
+    let response = app.get_userinfo(access_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_verify_token(&json!({ "token": access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_scope_not_granted_to_client() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "client_credentials"),
+            ("scope", "reports:write"),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["error"], "invalid_scope");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_unsupported_grant_type() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_token(&[("grant_type", "password")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["error"], "unsupported_grant_type");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unauthenticated_client() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/token", &app.address))
+        .form(&[("grant_type", "authorization_code")])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body["error"], "invalid_client");
+    app.clean_up().await;
+}
@@ -0,0 +1,351 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::TwoFAMethod,
+    routes::{EnrollTotpResponse, RecoveryCodesResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use serde_json::json;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// Stand in for the user's authenticator app
+fn authenticator(secret: &str) -> TOTP {
+    let secret = Secret::Encoded(secret.to_owned())
+        .to_bytes()
+        .expect("Secret was not valid base32");
+
+    TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, secret, None, String::new())
+}
+
+fn current_code(secret: &str) -> String {
+    authenticator(secret)
+        .generate_current()
+        .expect("Could not generate TOTP code")
+}
+
+// Each code is accepted once, so a code used straight after another one has to come
+// from the next time step
+fn next_code(secret: &str) -> String {
+    let authenticator = authenticator(secret);
+    let next_step = authenticator
+        .next_step_current()
+        .expect("Could not read the clock");
+
+    authenticator.generate(next_step)
+}
+
+async fn enroll_and_confirm(app: &TestApp) -> String {
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let enrollment = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": current_code(&enrollment.secret),
+            "password": "Password123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse");
+    assert_eq!(recovery_codes.recovery_codes.len(), 10);
+
+    enrollment.secret
+}
+
+#[tokio::test]
+async fn enroll_should_return_200_with_secret_and_otpauth_uri() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let enrollment = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment
+        .otpauth_uri
+        .contains(&format!("secret={}", enrollment.secret)));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enroll_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enroll_should_return_409_if_totp_already_enabled() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+    enroll_and_confirm(&app).await;
+
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "TOTP already enabled".to_owned()
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_incorrect_code() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let enrollment = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    let code = current_code(&enrollment.secret);
+    let incorrect_code = if code == "123456" { "654321" } else { "123456" };
+
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": incorrect_code,
+            "password": "Password123!",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_not_enrolled() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": "123456",
+            "password": "Password123!",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let enrollment = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": current_code(&enrollment.secret),
+            "password": "WrongPassword123!",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_require_code_for_replaced_2fa_method() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_2fa_enable(&json!({ "password": "Password123!" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let enrollment = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": current_code(&enrollment.secret),
+            "password": "Password123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_2fa_email_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let current_two_fa_code = app
+        .last_email(&random_email, "Your Authentication Code")
+        .await
+        .expect("No 2FA code was emailed")
+        .two_fa_code();
+
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": current_code(&enrollment.secret),
+            "password": "Password123!",
+            "current2FACode": current_two_fa_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_should_require_totp_code_after_confirmation() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+    let secret = enroll_and_confirm(&app).await;
+
+    let login_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(json_body.two_fa_method, TwoFAMethod::Totp);
+
+    let verify_2fa_body = json!({
+        "email": random_email,
+        "loginAttemptId": json_body.login_attempt_id,
+        "2FACode": next_code(&secret)
+    });
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_reject_stored_email_code_for_totp_user() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+    enroll_and_confirm(&app).await;
+
+    let login_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    let verify_2fa_body = json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code
+    });
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_reject_replayed_totp_code() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup_and_login(&random_email, false).await;
+    let secret = enroll_and_confirm(&app).await;
+    let two_fa_code = next_code(&secret);
+
+    let login_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+    });
+
+    for expected_status in [200, 401] {
+        let response = app.post_login(&login_body).await;
+        assert_eq!(response.status().as_u16(), 206);
+
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        let verify_2fa_body = json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": two_fa_code
+        });
+
+        let response = app.post_verify_2fa(&verify_2fa_body).await;
+        assert_eq!(response.status().as_u16(), expected_status);
+    }
+    app.clean_up().await;
+}
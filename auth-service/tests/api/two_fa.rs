@@ -0,0 +1,374 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::{Email, TwoFAMethod},
+    routes::{RecoveryCodesResponse, TwoFactorAuthResponse},
+    utils::auth::MAX_TWO_FA_ATTEMPTS,
+    ErrorResponse,
+};
+use serde_json::json;
+
+// The default number of failed logins for an account from one address before it is locked
+const IP_LOCKOUT_THRESHOLD: usize = 5;
+
+fn login_body(email: &Email) -> serde_json::Value {
+    json!({
+        "email": email,
+        "password": "Password123!",
+    })
+}
+
+async fn enable_2fa(app: &TestApp) -> Vec<String> {
+    let response = app
+        .post_2fa_enable(&json!({ "password": "Password123!" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes
+}
+
+#[tokio::test]
+async fn enable_should_require_2fa_on_next_login() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+
+    let recovery_codes = enable_2fa(&app).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(json_body.two_fa_method, TwoFAMethod::Email);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enable_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_2fa_enable(&json!({ "password": "Password123!" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enable_should_return_409_if_2fa_already_enabled() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    enable_2fa(&app).await;
+
+    let response = app
+        .post_2fa_enable(&json!({ "password": "Password123!" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "2FA already enabled".to_owned()
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enable_should_return_401_if_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_2fa_enable(&json!({ "password": "WrongPassword123!" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // 2FA is still off
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_accept_emailed_code() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    enable_2fa(&app).await;
+
+    let response = app.post_2fa_email_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let two_fa_code = app
+        .last_email(random_email.as_ref(), "Your Authentication Code")
+        .await
+        .expect("No 2FA code was emailed")
+        .two_fa_code();
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": two_fa_code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_leave_login_attempts_of_other_devices_alone() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    enable_2fa(&app).await;
+
+    // Another device starts logging in while this one changes the settings
+    let response = reqwest::Client::new()
+        .post(format!("{}/login", &app.address))
+        .json(&login_body(&random_email))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app.post_2fa_email_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The emailed code is not a login attempt of its own
+    let pending = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_pending(&random_email)
+        .await
+        .expect("Could not get pending login attempts");
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].0.as_ref(), login_attempt_id);
+
+    let two_fa_code = app
+        .last_email(random_email.as_ref(), "Your Authentication Code")
+        .await
+        .expect("No 2FA code was emailed")
+        .two_fa_code();
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": two_fa_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Panics if the other device's login attempt was removed
+    app.get_two_fa_code(&login_attempt_id).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_accept_recovery_code() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    let recovery_codes = enable_2fa(&app).await;
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_return_401_if_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    let recovery_codes = enable_2fa(&app).await;
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "WrongPassword123!",
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_return_429_after_repeated_incorrect_passwords() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    let recovery_codes = enable_2fa(&app).await;
+
+    // Wrong guesses here count towards the same lockout as failed logins
+    for _ in 0..IP_LOCKOUT_THRESHOLD {
+        let response = app
+            .post_2fa_disable(&json!({
+                "password": "WrongPassword123!",
+                "2FACode": recovery_codes[0],
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 429);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_return_401_if_code_is_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    enable_2fa(&app).await;
+
+    let response = app.post_2fa_email_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let two_fa_code = app
+        .last_email(random_email.as_ref(), "Your Authentication Code")
+        .await
+        .expect("No 2FA code was emailed")
+        .two_fa_code();
+    let incorrect_code = if two_fa_code == "123456" {
+        "654321"
+    } else {
+        "123456"
+    };
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": incorrect_code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_return_403_after_too_many_incorrect_codes() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+    enable_2fa(&app).await;
+
+    let response = app.post_2fa_email_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let two_fa_code = app
+        .last_email(random_email.as_ref(), "Your Authentication Code")
+        .await
+        .expect("No 2FA code was emailed")
+        .two_fa_code();
+    let incorrect_code = if two_fa_code == "123456" {
+        "654321"
+    } else {
+        "123456"
+    };
+
+    let wrong_body = json!({
+        "password": "Password123!",
+        "2FACode": incorrect_code,
+    });
+    for _ in 1..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_2fa_disable(&wrong_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_2fa_disable(&wrong_body).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many incorrect 2FA codes, please log in again".to_owned()
+    );
+
+    // The code stops working, even when it is right
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": two_fa_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn disable_should_return_409_if_2fa_not_enabled() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_2fa_disable(&json!({
+            "password": "Password123!",
+            "2FACode": "123456",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    app.clean_up().await;
+}
@@ -0,0 +1,124 @@
+use crate::helpers::{get_random_email, TestApp, TEST_CODE_VERIFIER, TEST_REDIRECT_URI};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use serde_json::{json, Value};
+
+// Sign up and log in, returning the JWT issued to the browser
+async fn sign_in(app: &TestApp, email: &str) -> String {
+    let signup_body = json!({
+        "email": email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+// Run the authorization code flow for the signed in user and return the access token
+async fn access_token(app: &TestApp, scope: &str) -> String {
+    let code = app.get_authorization_code(scope).await;
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", TEST_REDIRECT_URI),
+            ("code_verifier", TEST_CODE_VERIFIER),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    body["access_token"]
+        .as_str()
+        .expect("No access token in response body")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_return_200_with_user_claims() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+    let token = access_token(&app, "openid email").await;
+
+    let response = app.get_userinfo(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(
+        body,
+        json!({ "sub": email, "email": email, "email_verified": true })
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_omit_email_without_email_scope() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    sign_in(&app, &email).await;
+    let token = access_token(&app, "openid").await;
+
+    let response = app.get_userinfo(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<Value>()
+        .await
+        .expect("Could not deserialize response body");
+    assert_eq!(body, json!({ "sub": email }));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_browser_token() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let token = sign_in(&app, &email).await;
+
+    let response = app.get_userinfo(&token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_token_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/userinfo", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
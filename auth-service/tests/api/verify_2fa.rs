@@ -2,7 +2,8 @@ use crate::helpers::{get_random_email, TestApp};
 use auth_service::{
     domain::{Email, LoginAttemptId, TwoFACode},
     routes::TwoFactorAuthResponse,
-    utils::constants::JWT_COOKIE_NAME,
+    utils::{auth::MAX_TWO_FA_ATTEMPTS, constants::JWT_COOKIE_NAME},
+    ErrorResponse,
 };
 use serde_json::json;
 
@@ -22,6 +23,8 @@ async fn should_return_200_if_correct_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "Password123!",
@@ -38,18 +41,8 @@ async fn should_return_200_if_correct_code() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let (login_attempt_id, two_fa_code) = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&random_email)
-        .await
-        .unwrap();
-
-    assert_eq!(
-        LoginAttemptId::parse(&json_body.login_attempt_id).unwrap(),
-        login_attempt_id
-    );
+    let login_attempt_id = json_body.login_attempt_id;
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
 
     let verify_2fa_body = json!({
         "email": random_email,
@@ -121,6 +114,8 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "Password123!",
@@ -137,18 +132,8 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let (login_attempt_id, two_fa_code) = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&random_email)
-        .await
-        .unwrap();
-
-    assert_eq!(
-        LoginAttemptId::parse(&json_body.login_attempt_id).unwrap(),
-        login_attempt_id
-    );
+    let login_attempt_id = json_body.login_attempt_id;
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
 
     let verify_2fa_body = json!({
         "email": random_email,
@@ -188,3 +173,180 @@ async fn should_return_422_if_malformed_input() {
     assert_eq!(response.status().as_u16(), 422);
     app.clean_up().await;
 }
+
+// Log in as a user with email 2FA, returning the code that was sent
+async fn login(app: &TestApp, email: &Email) -> (LoginAttemptId, TwoFACode) {
+    let login_body = json!({
+        "email": email,
+        "password": "Password123!",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    app.get_login_attempt(response).await
+}
+
+fn wrong_code(two_fa_code: &TwoFACode) -> TwoFACode {
+    match two_fa_code.as_ref() {
+        "000000" => TwoFACode::parse("111111").unwrap(),
+        _ => TwoFACode::parse("000000").unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn should_return_403_after_too_many_incorrect_codes() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    let response = app.signup_and_login(&email, true).await;
+    let (login_attempt_id, two_fa_code) = app.get_login_attempt(response).await;
+
+    let wrong_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": wrong_code(&two_fa_code)
+    });
+    for _ in 1..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&wrong_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_verify_2fa(&wrong_body).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many incorrect 2FA codes, please log in again".to_owned()
+    );
+
+    // The code stops working, even when it is right
+    let verify_2fa_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Logging in again gives a new code to try
+    let (login_attempt_id, two_fa_code) = login(&app, &email).await;
+    let verify_2fa_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_count_incorrect_codes_for_other_login_attempts() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    let response = app.signup_and_login(&email, true).await;
+    let (login_attempt_id, two_fa_code) = app.get_login_attempt(response).await;
+
+    let wrong_body = json!({
+        "email": email,
+        "loginAttemptId": LoginAttemptId::default(),
+        "2FACode": wrong_code(&two_fa_code)
+    });
+    for _ in 0..MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&wrong_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let verify_2fa_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_allow_concurrent_login_attempts_from_different_clients() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    let response = app.signup_and_login(&email, true).await;
+    let (first_attempt_id, first_code) = app.get_login_attempt(response).await;
+
+    // Another device logging in as the same user keeps its own cookie
+    let other_device = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = other_device
+        .post(format!("{}/login", &app.address))
+        .json(&json!({
+            "email": email,
+            "password": "Password123!",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 206);
+    let second_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let second_code = app.get_two_fa_code(&second_attempt_id).await;
+
+    let response = other_device
+        .post(format!("{}/verify-2fa", &app.address))
+        .json(&json!({
+            "email": email,
+            "loginAttemptId": second_attempt_id,
+            "2FACode": second_code
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let verify_2fa_body = json!({
+        "email": email,
+        "loginAttemptId": first_attempt_id,
+        "2FACode": first_code
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_is_from_another_client() {
+    let mut app = TestApp::new().await;
+    let email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+    let response = app.signup_and_login(&email, true).await;
+    let (login_attempt_id, two_fa_code) = app.get_login_attempt(response).await;
+
+    let verify_2fa_body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code
+    });
+
+    // A client without the login attempt cookie stands in for an attacker
+    let response = reqwest::Client::new()
+        .post(format!("{}/verify-2fa", &app.address))
+        .json(&verify_2fa_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
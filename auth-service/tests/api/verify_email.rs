@@ -0,0 +1,190 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::{Email, EmailVerificationToken},
+    routes::VerifyEmailResponse,
+};
+use serde_json::json;
+
+#[tokio::test]
+async fn should_return_200_if_valid_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app
+        .email_verification_token_store
+        .read()
+        .await
+        .get_token(&random_email)
+        .await
+        .expect("No email verification token found");
+
+    let response = app
+        .get_verify_email(random_email.as_ref(), token.as_ref())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<VerifyEmailResponse>()
+            .await
+            .expect("Could not deserialize response body to VerifyEmailResponse"),
+        VerifyEmailResponse {
+            message: "Email verified successfully!".to_owned(),
+        }
+    );
+
+    // The token is single-use
+    let response = app
+        .get_verify_email(random_email.as_ref(), token.as_ref())
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let login_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = EmailVerificationToken::default();
+    let response = app.get_verify_email(&random_email, token.as_ref()).await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .get_verify_email(&get_random_email(), "not_a_token")
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .http_client
+        .get(format!("{}/verify-email", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn resend_should_replace_token_for_unverified_user() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let first_token = app
+        .email_verification_token_store
+        .read()
+        .await
+        .get_token(&random_email)
+        .await
+        .expect("No email verification token found");
+
+    let response = app
+        .post_verify_email_resend(&json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second_token = app
+        .email_verification_token_store
+        .read()
+        .await
+        .get_token(&random_email)
+        .await
+        .expect("No email verification token found");
+    assert_ne!(first_token, second_token);
+
+    // Only the latest link works
+    let response = app
+        .get_verify_email(random_email.as_ref(), first_token.as_ref())
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .get_verify_email(random_email.as_ref(), second_token.as_ref())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn resend_should_return_200_without_token_if_already_verified() {
+    let mut app = TestApp::new().await;
+
+    let random_email = Email::parse(&get_random_email()).expect("Random email was not parseable");
+
+    let signup_body = json!({
+        "email": random_email,
+        "password": "Password123!",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let response = app
+        .post_verify_email_resend(&json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(app
+        .email_verification_token_store
+        .read()
+        .await
+        .get_token(&random_email)
+        .await
+        .is_err());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn resend_should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_verify_email_resend(&json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+    app.clean_up().await;
+}
@@ -17,6 +17,8 @@ async fn should_return_200_valid_token() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "Password123!",
@@ -70,6 +72,8 @@ async fn should_return_401_if_banned_token() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "Password123!",